@@ -0,0 +1,322 @@
+use serde::{Deserialize, Serialize};
+use std::path::{Component, Path};
+
+use crate::{auth::AuthUser, errors::AppError};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Permission {
+    Read,
+    Write,
+    Delete,
+}
+
+impl Permission {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Permission::Read => "read",
+            Permission::Write => "write",
+            Permission::Delete => "delete",
+        }
+    }
+}
+
+// 单条访问规则：path为相对base_dir的路径前缀，users/groups为空时对所有用户生效
+#[derive(Debug, Deserialize, Clone)]
+pub struct AclRule {
+    #[serde(default)]
+    pub path: String,
+    #[serde(default)]
+    pub users: Vec<String>,
+    #[serde(default)]
+    pub groups: Vec<String>,
+    pub permissions: Vec<Permission>,
+}
+
+struct CompiledRule {
+    components: Vec<String>,
+    rule: AclRule,
+}
+
+impl CompiledRule {
+    fn applies_to(&self, user: &AuthUser) -> bool {
+        if self.rule.users.is_empty() && self.rule.groups.is_empty() {
+            return true;
+        }
+        self.rule
+            .users
+            .iter()
+            .any(|u| u == "*" || *u == user.username)
+            || self.rule.groups.iter().any(|g| user.groups.contains(g))
+    }
+}
+
+pub struct Acl {
+    rules: Vec<CompiledRule>,
+    default_permissions: Vec<Permission>,
+}
+
+// 把相对路径拆分为规范化后的路径组件
+pub(crate) fn path_components(path: &str) -> Vec<String> {
+    let mut components: Vec<String> = Vec::new();
+    for component in Path::new(path.trim_matches('/')).components() {
+        match component {
+            Component::Normal(name) => components.push(name.to_string_lossy().to_string()),
+            Component::ParentDir => {
+                components.pop();
+            }
+            _ => {}
+        }
+    }
+    components
+}
+
+impl Acl {
+    pub fn new(config: &serde_json::Value) -> anyhow::Result<Self> {
+        let rules: Vec<AclRule> = match config.get("rules") {
+            Some(rules) => serde_json::from_value(rules.clone())?,
+            None => Vec::new(),
+        };
+
+        let default_permissions: Vec<Permission> = match config.get("default") {
+            Some(default) => serde_json::from_value(default.clone())?,
+            None => vec![Permission::Read, Permission::Write, Permission::Delete],
+        };
+
+        let rules = rules
+            .into_iter()
+            .map(|rule| CompiledRule {
+                components: path_components(&rule.path),
+                rule,
+            })
+            .collect();
+
+        Ok(Acl {
+            rules,
+            default_permissions,
+        })
+    }
+
+    // 按最长前缀匹配计算用户在某路径上的权限，同一长度的多条规则取并集
+    fn permissions(&self, user: &AuthUser, components: &[String]) -> Vec<Permission> {
+        let mut best_len: Option<usize> = None;
+        let mut permissions = Vec::new();
+
+        for compiled in &self.rules {
+            if !components.starts_with(&compiled.components) || !compiled.applies_to(user) {
+                continue;
+            }
+
+            let len = compiled.components.len();
+            match best_len {
+                Some(best) if len < best => continue,
+                Some(best) if len == best => {}
+                _ => {
+                    best_len = Some(len);
+                    permissions.clear();
+                }
+            }
+            permissions.extend(compiled.rule.permissions.iter().copied());
+        }
+
+        match best_len {
+            Some(_) => permissions,
+            None => self.default_permissions.clone(),
+        }
+    }
+
+    pub fn allows(&self, user: &AuthUser, path: &str, permission: Permission) -> bool {
+        let components = path_components(path);
+        scope_allows(user, &components, permission)
+            && self.permissions(user, &components).contains(&permission)
+    }
+
+    // 检查单个路径的权限
+    pub fn check(&self, user: &AuthUser, path: &str, permission: Permission) -> Result<(), AppError> {
+        if self.allows(user, path, permission) {
+            Ok(())
+        } else {
+            Err(denied(user, path, permission))
+        }
+    }
+
+    // 检查路径及其下所有更具体规则的权限，用于删除/移动/复制整个目录树
+    pub fn check_tree(&self, user: &AuthUser, path: &str, permission: Permission) -> Result<(), AppError> {
+        self.check(user, path, permission)?;
+
+        let components = path_components(path);
+        for compiled in &self.rules {
+            if compiled.components.len() > components.len()
+                && compiled.components.starts_with(&components)
+                && compiled.applies_to(user)
+                && !self
+                    .permissions(user, &compiled.components)
+                    .contains(&permission)
+            {
+                return Err(denied(user, &compiled.rule.path, permission));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Acl {
+    // 路径之下（不含自身）对该用户生效的规则路径，按路径排序去重；
+    // 用于统计整棵目录树时找出权限发生变化的子树
+    pub fn rule_paths_under(&self, user: &AuthUser, path: &str) -> Vec<String> {
+        let components = path_components(path);
+        let mut paths: Vec<String> = self
+            .rules
+            .iter()
+            .filter(|compiled| {
+                compiled.components.len() > components.len()
+                    && compiled.components.starts_with(&components)
+                    && compiled.applies_to(user)
+            })
+            .map(|compiled| compiled.components.join("/"))
+            .collect();
+        paths.sort();
+        paths.dedup();
+        paths
+    }
+}
+
+// API令牌只能在其范围路径下执行授予的操作
+fn scope_allows(user: &AuthUser, components: &[String], permission: Permission) -> bool {
+    match &user.token {
+        Some(scope) => {
+            components.starts_with(&path_components(&scope.path))
+                && scope.permissions.contains(&permission)
+        }
+        None => true,
+    }
+}
+
+fn denied(user: &AuthUser, path: &str, permission: Permission) -> AppError {
+    tracing::warn!(
+        "Permission denied: {} lacks {} on /{}",
+        user,
+        permission.as_str(),
+        path.trim_matches('/')
+    );
+    AppError::PermissionDenied(format!(
+        "Permission denied: {} access to /{}",
+        permission.as_str(),
+        path.trim_matches('/')
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::auth::TokenScope;
+
+    fn user(name: &str, groups: &[&str]) -> AuthUser {
+        AuthUser {
+            username: name.to_string(),
+            groups: groups.iter().map(|g| g.to_string()).collect(),
+            is_admin: false,
+            token: None,
+        }
+    }
+
+    fn acl() -> Acl {
+        Acl::new(&serde_json::json!({
+            "default": ["read"],
+            "rules": [
+                { "path": "", "groups": ["admin"], "permissions": ["read", "write", "delete"] },
+                { "path": "team", "groups": ["dev"], "permissions": ["read", "write"] },
+                { "path": "team/secret", "users": ["alice"], "permissions": ["read"] },
+                { "path": "team/secret", "groups": ["ops"], "permissions": ["delete"] },
+                { "path": "/public/", "permissions": ["read", "write"] }
+            ]
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn longest_prefix_wins() {
+        let acl = acl();
+        let bob = user("bob", &["dev"]);
+        assert!(acl.allows(&bob, "team/a.txt", Permission::Write));
+        assert!(!acl.allows(&bob, "team/a.txt", Permission::Delete));
+
+        // alice的team/secret规则比dev的team规则更具体，只剩读权限
+        let alice = user("alice", &["dev"]);
+        assert!(acl.allows(&alice, "team/secret/x", Permission::Read));
+        assert!(!acl.allows(&alice, "team/secret/x", Permission::Write));
+        assert!(acl.allows(&alice, "team/other", Permission::Write));
+    }
+
+    #[test]
+    fn rules_of_same_length_are_merged() {
+        let acl = acl();
+        // alice的用户规则和ops的组规则路径相同，权限取并集
+        let alice = user("alice", &["ops"]);
+        assert!(acl.allows(&alice, "team/secret", Permission::Read));
+        assert!(acl.allows(&alice, "team/secret", Permission::Delete));
+        assert!(!acl.allows(&alice, "team/secret", Permission::Write));
+    }
+
+    #[test]
+    fn prefix_matches_whole_components() {
+        let acl = acl();
+        let bob = user("bob", &["dev"]);
+        // "teammate" 不在 "team" 之下，回退到默认权限
+        assert!(!acl.allows(&bob, "teammate/a.txt", Permission::Write));
+        assert!(acl.allows(&bob, "teammate/a.txt", Permission::Read));
+    }
+
+    #[test]
+    fn paths_are_normalized() {
+        let acl = acl();
+        let guest = user("guest", &[]);
+        assert!(acl.allows(&guest, "/public/", Permission::Write));
+        assert!(acl.allows(&guest, "public//docs/./a", Permission::Write));
+        // ".." 先规范化再匹配，不能借此落入其他规则
+        assert!(!acl.allows(&guest, "public/../team/a", Permission::Write));
+        assert_eq!(path_components("a/../../b/./c/"), vec!["b", "c"]);
+    }
+
+    #[test]
+    fn check_tree_finds_restricted_subtrees() {
+        let acl = acl();
+        let bob = user("bob", &["dev"]);
+        assert!(acl.check(&bob, "team", Permission::Write).is_ok());
+        // dev不受team/secret规则影响，整棵树可写
+        assert!(acl.check_tree(&bob, "team", Permission::Write).is_ok());
+
+        let alice = user("alice", &["dev"]);
+        assert!(acl.check(&alice, "team", Permission::Write).is_ok());
+        assert!(acl.check_tree(&alice, "team", Permission::Write).is_err());
+    }
+
+    #[test]
+    fn rule_paths_under_lists_applicable_subtree_rules() {
+        let acl = acl();
+        let alice = user("alice", &["ops"]);
+        // 同一路径的两条规则只列出一次，路径本身和其他用户的规则不列出
+        assert_eq!(acl.rule_paths_under(&alice, ""), vec!["public", "team/secret"]);
+        assert_eq!(acl.rule_paths_under(&alice, "team"), vec!["team/secret"]);
+        assert!(acl.rule_paths_under(&alice, "team/secret").is_empty());
+
+        let bob = user("bob", &["dev"]);
+        assert_eq!(acl.rule_paths_under(&bob, ""), vec!["public", "team"]);
+    }
+
+    #[test]
+    fn token_scope_limits_path_and_permissions() {
+        let acl = acl();
+        let mut admin = user("root", &["admin"]);
+        admin.token = Some(TokenScope {
+            label: "ci".to_string(),
+            path: "builds".to_string(),
+            permissions: vec![Permission::Write],
+        });
+        assert!(acl.allows(&admin, "builds/1.zip", Permission::Write));
+        assert!(!acl.allows(&admin, "builds/1.zip", Permission::Delete));
+        assert!(!acl.allows(&admin, "buildsx/1.zip", Permission::Write));
+        assert!(!acl.allows(&admin, "team/a", Permission::Write));
+    }
+}
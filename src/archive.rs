@@ -0,0 +1,465 @@
+use axum::{
+    body::{Body, Bytes},
+    extract::{Path, Query, State},
+    http::header,
+    response::{IntoResponse, Response},
+    Json,
+};
+use std::collections::HashSet;
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+
+use crate::{
+    acl::{self, Permission},
+    auth::AuthUser,
+    errors::AppError,
+    models::*,
+    utils::*,
+    AppState,
+};
+
+// 每次发送给客户端的数据块大小
+const CHUNK_SIZE: usize = 64 * 1024;
+
+// 把同步Write写入的数据按块发送到异步通道，客户端断开时写入返回BrokenPipe
+struct ChannelWriter {
+    tx: mpsc::Sender<io::Result<Bytes>>,
+    buf: Vec<u8>,
+}
+
+impl ChannelWriter {
+    fn new(tx: mpsc::Sender<io::Result<Bytes>>) -> Self {
+        ChannelWriter {
+            tx,
+            buf: Vec::with_capacity(CHUNK_SIZE),
+        }
+    }
+
+    fn send_buf(&mut self) -> io::Result<()> {
+        if self.buf.is_empty() {
+            return Ok(());
+        }
+        let chunk = Bytes::from(std::mem::replace(&mut self.buf, Vec::with_capacity(CHUNK_SIZE)));
+        self.tx
+            .blocking_send(Ok(chunk))
+            .map_err(|_| io::Error::new(io::ErrorKind::BrokenPipe, "client disconnected"))
+    }
+}
+
+impl Write for ChannelWriter {
+    fn write(&mut self, data: &[u8]) -> io::Result<usize> {
+        self.buf.extend_from_slice(data);
+        if self.buf.len() >= CHUNK_SIZE {
+            self.send_buf()?;
+        }
+        Ok(data.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.send_buf()
+    }
+}
+
+impl Drop for ChannelWriter {
+    fn drop(&mut self) {
+        let _ = self.send_buf();
+    }
+}
+
+// 归档中的一项：name为归档内路径，rel_path为相对base_dir的路径
+struct ArchiveItem {
+    name: String,
+    rel_path: String,
+}
+
+// 写入归档条目的抽象，ZIP和tar.gz各自实现
+trait ArchiveSink {
+    fn add_dir(&mut self, name: &str) -> io::Result<()>;
+    fn add_file(&mut self, name: &str, reader: &mut dyn Read, size: u64, mtime: u64) -> io::Result<()>;
+    fn finish(self: Box<Self>) -> io::Result<()>;
+}
+
+struct ZipSink(zip::ZipWriter<zip::write::StreamWriter<ChannelWriter>>);
+
+impl ZipSink {
+    fn options(large: bool, mtime: u64) -> zip::write::SimpleFileOptions {
+        let options = zip::write::SimpleFileOptions::default()
+            .compression_method(zip::CompressionMethod::Deflated)
+            .large_file(large);
+
+        // ZIP使用本地时间的DOS时间格式，超出范围时保留默认值
+        use chrono::{Datelike, TimeZone, Timelike};
+        let modified = chrono::Local
+            .timestamp_opt(mtime as i64, 0)
+            .single()
+            .and_then(|t| {
+                zip::DateTime::from_date_and_time(
+                    t.year().try_into().ok()?,
+                    t.month() as u8,
+                    t.day() as u8,
+                    t.hour() as u8,
+                    t.minute() as u8,
+                    t.second() as u8,
+                )
+                .ok()
+            });
+        match modified {
+            Some(modified) => options.last_modified_time(modified),
+            None => options,
+        }
+    }
+}
+
+impl ArchiveSink for ZipSink {
+    fn add_dir(&mut self, name: &str) -> io::Result<()> {
+        self.0
+            .add_directory(
+                format!("{}/", name),
+                Self::options(false, chrono::Utc::now().timestamp() as u64),
+            )
+            .map_err(io::Error::other)
+    }
+
+    fn add_file(&mut self, name: &str, reader: &mut dyn Read, size: u64, mtime: u64) -> io::Result<()> {
+        self.0
+            .start_file(name, Self::options(size >= u32::MAX as u64, mtime))
+            .map_err(io::Error::other)?;
+        io::copy(reader, &mut self.0)?;
+        Ok(())
+    }
+
+    fn finish(self: Box<Self>) -> io::Result<()> {
+        self.0.finish().map_err(io::Error::other)?;
+        Ok(())
+    }
+}
+
+struct TarGzSink(tar::Builder<flate2::write::GzEncoder<ChannelWriter>>);
+
+impl ArchiveSink for TarGzSink {
+    fn add_dir(&mut self, name: &str) -> io::Result<()> {
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(tar::EntryType::Directory);
+        header.set_mode(0o755);
+        header.set_size(0);
+        header.set_mtime(chrono::Utc::now().timestamp() as u64);
+        self.0.append_data(&mut header, format!("{}/", name), io::empty())
+    }
+
+    fn add_file(&mut self, name: &str, reader: &mut dyn Read, size: u64, mtime: u64) -> io::Result<()> {
+        let mut header = tar::Header::new_gnu();
+        header.set_entry_type(tar::EntryType::Regular);
+        header.set_mode(0o644);
+        header.set_size(size);
+        header.set_mtime(mtime);
+        // 按记录的大小截断，文件在读取期间变长也不会破坏tar结构
+        self.0.append_data(&mut header, name, reader.take(size))
+    }
+
+    fn finish(self: Box<Self>) -> io::Result<()> {
+        self.0.into_inner()?.finish()?.flush()
+    }
+}
+
+// 遍历并写入归档，不可读或越权的条目跳过并记录原因
+struct ArchiveJob {
+    state: Arc<AppState>,
+    user: AuthUser,
+    canonical_base: PathBuf,
+    // 已展开的目录（规范化路径），防止符号链接造成循环
+    visited: HashSet<PathBuf>,
+    // 归档顶层已使用的名称，跳过清单文件不能与之重名
+    top_level: HashSet<String>,
+    skipped: Vec<(String, String)>,
+}
+
+impl ArchiveJob {
+    fn add_item(&mut self, sink: &mut dyn ArchiveSink, name: &str, rel_path: &str) -> io::Result<()> {
+        let full_path = self.state.base_dir.join(rel_path);
+        if !name.is_empty() && !name.contains('/') {
+            self.top_level.insert(name.to_string());
+        }
+
+        if !is_safe_path(&self.state.base_dir, &full_path) {
+            self.skipped.push((rel_path.to_string(), "Access denied".to_string()));
+            return Ok(());
+        }
+
+        if !self.state.acl.allows(&self.user, rel_path, Permission::Read) {
+            self.skipped.push((rel_path.to_string(), "Permission denied".to_string()));
+            return Ok(());
+        }
+
+        let metadata = match std::fs::symlink_metadata(&full_path) {
+            Ok(metadata) => metadata,
+            Err(e) => {
+                self.skipped.push((rel_path.to_string(), e.to_string()));
+                return Ok(());
+            }
+        };
+
+        // 符号链接只在目标仍位于根目录内时跟随
+        let metadata = if metadata.file_type().is_symlink() {
+            match std::fs::canonicalize(&full_path) {
+                Ok(target) if target.starts_with(&self.canonical_base) => match std::fs::metadata(&target) {
+                    Ok(metadata) => metadata,
+                    Err(e) => {
+                        self.skipped.push((rel_path.to_string(), e.to_string()));
+                        return Ok(());
+                    }
+                },
+                _ => {
+                    self.skipped
+                        .push((rel_path.to_string(), "Symlink points outside base directory".to_string()));
+                    return Ok(());
+                }
+            }
+        } else {
+            metadata
+        };
+
+        if metadata.is_dir() {
+            let canonical = std::fs::canonicalize(&full_path)?;
+            if !self.visited.insert(canonical) {
+                self.skipped.push((rel_path.to_string(), "Directory loop detected".to_string()));
+                return Ok(());
+            }
+
+            if !name.is_empty() {
+                sink.add_dir(name)?;
+            }
+
+            let mut children = match std::fs::read_dir(&full_path) {
+                Ok(read_dir) => read_dir
+                    .filter_map(|e| e.ok())
+                    .filter(|e| is_safe_path(&self.state.base_dir, &e.path()))
+                    .map(|e| e.file_name().to_string_lossy().to_string())
+                    .collect::<Vec<_>>(),
+                Err(e) => {
+                    self.skipped.push((rel_path.to_string(), e.to_string()));
+                    return Ok(());
+                }
+            };
+            children.sort();
+
+            for child in children {
+                let child_name = join_relative_path(name, &child);
+                let child_rel = join_relative_path(rel_path, &child);
+                self.add_item(sink, &child_name, &child_rel)?;
+            }
+            return Ok(());
+        }
+
+        let mut file = match File::open(&full_path) {
+            Ok(file) => file,
+            Err(e) => {
+                self.skipped.push((rel_path.to_string(), e.to_string()));
+                return Ok(());
+            }
+        };
+        let mtime = metadata
+            .modified()
+            .ok()
+            .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        sink.add_file(name, &mut file, metadata.len(), mtime)
+    }
+
+    fn run(mut self, mut sink: Box<dyn ArchiveSink>, items: Vec<ArchiveItem>) -> io::Result<()> {
+        for item in &items {
+            self.add_item(sink.as_mut(), &item.name, &item.rel_path)?;
+        }
+
+        // 跳过的条目写入清单文件，便于下载方知道缺少了哪些内容
+        if !self.skipped.is_empty() {
+            let report = self
+                .skipped
+                .iter()
+                .map(|(path, reason)| format!("{}: {}\n", path, reason))
+                .collect::<String>();
+            let mtime = chrono::Utc::now().timestamp() as u64;
+            let report_name = unique_name("_skipped.txt", false, |n| self.top_level.contains(n));
+            sink.add_file(&report_name, &mut report.as_bytes(), report.len() as u64, mtime)?;
+
+            tracing::warn!("{} archive skipped {} entries", self.user, self.skipped.len());
+        }
+
+        sink.finish()
+    }
+}
+
+// 启动后台归档任务并返回流式响应
+fn stream_archive(
+    state: Arc<AppState>,
+    user: AuthUser,
+    items: Vec<ArchiveItem>,
+    format: ArchiveFormat,
+    download_name: &str,
+) -> Result<Response, AppError> {
+    let canonical_base = std::fs::canonicalize(&state.base_dir)?;
+    let (tx, mut rx) = mpsc::channel::<io::Result<Bytes>>(8);
+
+    tracing::info!("{} downloading archive {}", user, download_name);
+
+    let error_tx = tx.clone();
+    tokio::task::spawn_blocking(move || {
+        let writer = ChannelWriter::new(tx);
+        let sink: Box<dyn ArchiveSink> = match format {
+            ArchiveFormat::Zip => Box::new(ZipSink(zip::ZipWriter::new_stream(writer))),
+            ArchiveFormat::TarGz => Box::new(TarGzSink(tar::Builder::new(
+                flate2::write::GzEncoder::new(writer, flate2::Compression::default()),
+            ))),
+        };
+
+        let job = ArchiveJob {
+            state,
+            user,
+            canonical_base,
+            visited: HashSet::new(),
+            top_level: HashSet::new(),
+            skipped: Vec::new(),
+        };
+        if let Err(e) = job.run(sink, items) {
+            if e.kind() != io::ErrorKind::BrokenPipe {
+                tracing::error!("Failed to build archive: {}", e);
+                // 通知响应流出错，让客户端看到不完整的传输而不是损坏的归档
+                let _ = error_tx.blocking_send(Err(e));
+            }
+        }
+    });
+
+    let stream = futures_util::stream::poll_fn(move |cx| rx.poll_recv(cx));
+    let (content_type, extension) = match format {
+        ArchiveFormat::Zip => ("application/zip", "zip"),
+        ArchiveFormat::TarGz => ("application/gzip", "tar.gz"),
+    };
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, content_type.to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"{}.{}\"", download_name, extension),
+            ),
+        ],
+        Body::from_stream(stream),
+    )
+        .into_response())
+}
+
+async fn archive_path_impl(
+    state: Arc<AppState>,
+    user: AuthUser,
+    path: &str,
+    format: ArchiveFormat,
+) -> Result<Response, AppError> {
+    let components = acl::path_components(path);
+    let safe_path = components.join("/");
+    let full_path = state.base_dir.join(&safe_path);
+
+    if !is_safe_path(&state.base_dir, &full_path) {
+        return Err(AppError::PermissionDenied("Access denied".to_string()));
+    }
+
+    state.acl.check(&user, &safe_path, Permission::Read)?;
+
+    if !full_path.exists() {
+        return Err(AppError::NotFound("Path not found".to_string()));
+    }
+
+    // 归档内以该目录名作为顶层目录，根目录则直接展开
+    let name = components.last().cloned().unwrap_or_default();
+    let download_name = if name.is_empty() { "files".to_string() } else { name.clone() };
+
+    let items = vec![ArchiveItem {
+        name,
+        rel_path: safe_path,
+    }];
+    stream_archive(state, user, items, format, &download_name)
+}
+
+// 打包下载根目录
+pub async fn archive_root(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Query(query): Query<ArchiveQuery>,
+) -> Result<Response, AppError> {
+    archive_path_impl(state, user, "", query.format).await
+}
+
+// 打包下载指定目录或文件
+pub async fn archive_path(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Path(path): Path<String>,
+    Query(query): Query<ArchiveQuery>,
+) -> Result<Response, AppError> {
+    archive_path_impl(state, user, &path, query.format).await
+}
+
+// 打包下载选中的多个文件和文件夹
+pub async fn archive_selection(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Json(req): Json<ArchiveRequest>,
+) -> Result<Response, AppError> {
+    if req.paths.is_empty() {
+        return Err(AppError::InvalidRequest("No paths selected".to_string()));
+    }
+
+    // 不同目录下的同名条目在归档顶层追加序号，避免写入重复的条目名
+    let mut items = Vec::new();
+    let mut names = HashSet::new();
+    for path in &req.paths {
+        // 先规范化路径，"."、"a/.."等同样指向根目录，条目名取规范化后的最后一段
+        let components = acl::path_components(path);
+        let Some(name) = components.last().cloned() else {
+            return Err(AppError::InvalidRequest("Cannot select the root directory".to_string()));
+        };
+        let safe_path = components.join("/");
+        let is_dir = state.base_dir.join(&safe_path).is_dir();
+        let name = unique_name(&name, is_dir, |n| names.contains(n));
+        names.insert(name.clone());
+        items.push(ArchiveItem {
+            name,
+            rel_path: safe_path,
+        });
+    }
+
+    let download_name = req.name.as_deref().filter(|n| is_valid_file_name(n)).unwrap_or("files");
+    stream_archive(state, user, items, req.format, download_name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::test_state;
+
+    fn selection(paths: &[&str]) -> Json<ArchiveRequest> {
+        Json(ArchiveRequest {
+            paths: paths.iter().map(|p| p.to_string()).collect(),
+            format: ArchiveFormat::Zip,
+            name: None,
+        })
+    }
+
+    #[tokio::test]
+    async fn selection_rejects_root_in_any_spelling() {
+        let (state, _dir) = test_state(serde_json::Value::Null).await;
+        std::fs::create_dir(state.base_dir.join("a")).unwrap();
+
+        for path in ["", "/", ".", "./", "a/..", "a/../."] {
+            let result = archive_selection(State(state.clone()), AuthUser::anonymous(), selection(&[path])).await;
+            assert!(matches!(result, Err(AppError::InvalidRequest(_))), "{:?} accepted", path);
+        }
+
+        let response = archive_selection(State(state.clone()), AuthUser::anonymous(), selection(&["./a/"]))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), axum::http::StatusCode::OK);
+    }
+}
@@ -0,0 +1,556 @@
+use argon2::{
+    password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    Argon2,
+};
+use axum::{
+    extract::{FromRequestParts, Request, State},
+    http::{header, request::Parts, HeaderMap},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use base64::Engine;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use crate::{acl::Permission, errors::AppError, models::*, tokens::TOKEN_PREFIX, AppState};
+
+pub const SESSION_COOKIE: &str = "fs_session";
+
+// 同一用户名连续登录失败达到该次数后开始限制
+const LOGIN_FREE_ATTEMPTS: u32 = 5;
+// 限制等待时间从1秒起每次失败翻倍，最长15分钟
+const LOGIN_MAX_BACKOFF_SECS: i64 = 900;
+// 最后一次失败超过该时间后清除失败记录
+const LOGIN_FAILURE_TTL_SECS: i64 = 3600;
+
+// Basic认证凭据验证结果的缓存时间（秒）
+const BASIC_CACHE_SECS: i64 = 300;
+
+// 用户定义，来自config.json的auth.users或auth.users_file
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct UserRecord {
+    pub username: String,
+    pub password_hash: String,
+    #[serde(default)]
+    pub groups: Vec<String>,
+}
+
+// 某个用户名的连续登录失败次数和最后一次失败的时间
+struct LoginFailures {
+    count: u32,
+    last: i64,
+}
+
+impl LoginFailures {
+    // 下次允许尝试的时间
+    fn retry_at(&self) -> i64 {
+        if self.count < LOGIN_FREE_ATTEMPTS {
+            return self.last;
+        }
+        let exponent = (self.count - LOGIN_FREE_ATTEMPTS).min(16);
+        self.last + (1i64 << exponent).min(LOGIN_MAX_BACKOFF_SECS)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Session {
+    username: String,
+    expires: i64,
+}
+
+// API令牌的作用范围：只能在path下执行permissions中的操作
+#[derive(Debug, Clone)]
+pub struct TokenScope {
+    pub label: String,
+    pub path: String,
+    pub permissions: Vec<Permission>,
+}
+
+// 已认证的用户，由认证中间件放入请求扩展中
+#[derive(Debug, Clone)]
+pub struct AuthUser {
+    pub username: String,
+    pub groups: Vec<String>,
+    pub is_admin: bool,
+    // 通过API令牌认证时的令牌范围
+    pub token: Option<TokenScope>,
+}
+
+impl AuthUser {
+    // 未启用认证时使用的匿名身份
+    pub fn anonymous() -> Self {
+        AuthUser {
+            username: "anonymous".to_string(),
+            groups: Vec::new(),
+            is_admin: false,
+            token: None,
+        }
+    }
+}
+
+// 日志中显示用户名，令牌请求附带令牌标签
+impl std::fmt::Display for AuthUser {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.token {
+            Some(scope) => write!(f, "{}[token:{}]", self.username, scope.label),
+            None => write!(f, "{}", self.username),
+        }
+    }
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for AuthUser {
+    type Rejection = AppError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        parts
+            .extensions
+            .get::<AuthUser>()
+            .cloned()
+            .ok_or_else(|| AppError::Unauthorized("Authentication required".to_string()))
+    }
+}
+
+pub struct AuthStore {
+    enabled: bool,
+    users: HashMap<String, UserRecord>,
+    admin_groups: Vec<String>,
+    sessions: Mutex<HashMap<String, Session>>,
+    session_secs: i64,
+    // 用户不存在时用于校验的哈希，使响应时间与用户存在时一致
+    dummy_hash: String,
+    // 按用户名记录的登录失败（不区分用户是否存在）
+    failures: Mutex<HashMap<String, LoginFailures>>,
+    // 已验证的Basic凭据：SHA-256(用户名:密码) -> 会话，避免每个请求都计算argon2
+    basic_cache: Mutex<HashMap<String, Session>>,
+}
+
+impl AuthStore {
+    pub fn new(config: &serde_json::Value) -> anyhow::Result<Self> {
+        let mut users = HashMap::new();
+
+        if let Some(list) = config["users"].as_array() {
+            for value in list {
+                let user: UserRecord = serde_json::from_value(value.clone())?;
+                users.insert(user.username.clone(), user);
+            }
+        }
+
+        if let Some(users_file) = config["users_file"].as_str() {
+            let content = std::fs::read_to_string(users_file)
+                .map_err(|e| anyhow::anyhow!("Failed to read users file {}: {}", users_file, e))?;
+            let list: Vec<UserRecord> = serde_json::from_str(&content)?;
+            for user in list {
+                users.insert(user.username.clone(), user);
+            }
+        }
+
+        for user in users.values() {
+            PasswordHash::new(&user.password_hash).map_err(|e| {
+                anyhow::anyhow!("Invalid password hash for user {}: {}", user.username, e)
+            })?;
+        }
+
+        let enabled = config["enabled"].as_bool().unwrap_or(!users.is_empty());
+        if enabled && users.is_empty() {
+            anyhow::bail!("Authentication is enabled but no users are configured");
+        }
+
+        let session_hours = config["session_hours"].as_u64().unwrap_or(12);
+        let admin_groups = match config.get("admin_groups") {
+            Some(groups) => serde_json::from_value(groups.clone())?,
+            None => vec!["admin".to_string()],
+        };
+
+        Ok(AuthStore {
+            enabled,
+            users,
+            admin_groups,
+            sessions: Mutex::new(HashMap::new()),
+            session_secs: (session_hours * 3600) as i64,
+            dummy_hash: if enabled {
+                hash_password(&uuid::Uuid::new_v4().simple().to_string())?
+            } else {
+                String::new()
+            },
+            failures: Mutex::new(HashMap::new()),
+            basic_cache: Mutex::new(HashMap::new()),
+        })
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn user(&self, username: &str) -> Option<AuthUser> {
+        self.users.get(username).map(|u| AuthUser {
+            username: u.username.clone(),
+            groups: u.groups.clone(),
+            is_admin: u.groups.iter().any(|g| self.admin_groups.contains(g)),
+            token: None,
+        })
+    }
+
+    // 校验用户名密码；用户不存在时同样计算一次哈希，避免通过响应时间判断用户名是否存在
+    async fn verify_password(&self, username: &str, password: &str) -> bool {
+        match self.users.get(username) {
+            Some(user) => verify_password_hash(&user.password_hash, password).await,
+            None => {
+                verify_password_hash(&self.dummy_hash, password).await;
+                false
+            }
+        }
+    }
+
+    fn lock_failures(&self) -> std::sync::MutexGuard<'_, HashMap<String, LoginFailures>> {
+        self.failures.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    // 连续失败过多时返回还需等待的秒数
+    fn login_retry_after(&self, username: &str, now: i64) -> Option<i64> {
+        let retry_at = self.lock_failures().get(username)?.retry_at();
+        (retry_at > now).then_some(retry_at - now)
+    }
+
+    fn record_login_failure(&self, username: &str, now: i64) {
+        let mut failures = self.lock_failures();
+        // 用户名由客户端任意提交，定期清除过期记录限制内存占用
+        if failures.len() >= 10_000 {
+            failures.retain(|_, f| now - f.last < LOGIN_FAILURE_TTL_SECS);
+        }
+        let entry = failures
+            .entry(username.to_string())
+            .or_insert(LoginFailures { count: 0, last: now });
+        if now - entry.last >= LOGIN_FAILURE_TTL_SECS {
+            entry.count = 0;
+        }
+        entry.count += 1;
+        entry.last = now;
+    }
+
+    fn clear_login_failures(&self, username: &str) {
+        self.lock_failures().remove(username);
+    }
+
+    fn create_session(&self, username: &str) -> (String, i64) {
+        let token = format!(
+            "{}{}",
+            uuid::Uuid::new_v4().simple(),
+            uuid::Uuid::new_v4().simple()
+        );
+        let now = Utc::now().timestamp();
+        let expires = now + self.session_secs;
+
+        let mut sessions = self.sessions.lock().unwrap();
+        sessions.retain(|_, s| s.expires > now);
+        sessions.insert(
+            token.clone(),
+            Session {
+                username: username.to_string(),
+                expires,
+            },
+        );
+        (token, expires)
+    }
+
+    fn session_user(&self, token: &str) -> Option<AuthUser> {
+        let now = Utc::now().timestamp();
+        let mut sessions = self.sessions.lock().unwrap();
+        let session = sessions.get(token)?.clone();
+        if session.expires <= now {
+            sessions.remove(token);
+            return None;
+        }
+        drop(sessions);
+        self.user(&session.username)
+    }
+
+    fn remove_session(&self, token: &str) {
+        self.sessions.lock().unwrap().remove(token);
+    }
+
+    // 根据会话令牌解析当前用户
+    pub fn authenticate(&self, headers: &HeaderMap) -> Option<AuthUser> {
+        request_token(headers).and_then(|token| self.session_user(&token))
+    }
+
+    // HTTP Basic认证（WebDAV客户端等无法使用登录会话的场景）
+    async fn authenticate_basic(&self, username: &str, password: &str) -> Option<AuthUser> {
+        let key = hex::encode(Sha256::digest(format!("{}:{}", username, password).as_bytes()));
+        let now = Utc::now().timestamp();
+
+        let cached = self.basic_cache.lock().unwrap().get(&key).cloned();
+        if let Some(session) = cached.filter(|s| s.expires > now) {
+            return self.user(&session.username);
+        }
+
+        // 与登录接口共用失败计数，避免绕过登录限制猜测密码
+        if self.login_retry_after(username, now).is_some() {
+            return None;
+        }
+        if !self.verify_password(username, password).await {
+            tracing::warn!("Failed basic authentication for user {}", username);
+            self.record_login_failure(username, now);
+            return None;
+        }
+        self.clear_login_failures(username);
+
+        let mut cache = self.basic_cache.lock().unwrap();
+        cache.retain(|_, s| s.expires > now);
+        cache.insert(
+            key,
+            Session {
+                username: username.to_string(),
+                expires: now + BASIC_CACHE_SECS,
+            },
+        );
+        drop(cache);
+        self.user(username)
+    }
+}
+
+// 从Authorization: Bearer或会话Cookie中取出令牌
+pub fn request_token(headers: &HeaderMap) -> Option<String> {
+    if let Some(token) = bearer_token(headers) {
+        return Some(token);
+    }
+
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(';'))
+        .filter_map(|pair| pair.trim().split_once('='))
+        .find(|(name, _)| *name == SESSION_COOKIE)
+        .map(|(_, value)| value.to_string())
+}
+
+// 解析Authorization: Basic中的用户名和密码
+fn basic_credentials(headers: &HeaderMap) -> Option<(String, String)> {
+    let encoded = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Basic "))?;
+    let decoded = base64::engine::general_purpose::STANDARD
+        .decode(encoded.trim())
+        .ok()?;
+    let decoded = String::from_utf8(decoded).ok()?;
+    let (username, password) = decoded.split_once(':')?;
+    Some((username.to_string(), password.to_string()))
+}
+
+pub fn bearer_token(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .map(|v| v.trim().to_string())
+}
+
+// 校验密码与argon2哈希是否匹配（argon2计算较重，放到阻塞线程中执行）
+pub async fn verify_password_hash(hash: &str, password: &str) -> bool {
+    let hash = hash.to_string();
+    let password = password.to_string();
+
+    tokio::task::spawn_blocking(move || match PasswordHash::new(&hash) {
+        Ok(parsed) => Argon2::default()
+            .verify_password(password.as_bytes(), &parsed)
+            .is_ok(),
+        Err(_) => false,
+    })
+    .await
+    .unwrap_or(false)
+}
+
+// 生成argon2密码哈希，用于 `file-server hash-password <密码>`
+pub fn hash_password(password: &str) -> anyhow::Result<String> {
+    let salt = SaltString::generate(&mut OsRng);
+    let hash = Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map_err(|e| anyhow::anyhow!("Failed to hash password: {}", e))?;
+    Ok(hash.to_string())
+}
+
+// 解析请求的用户：API令牌优先，其次是登录会话，最后是Basic认证（密码也可以是API令牌）
+pub(crate) async fn resolve_user(state: &AppState, headers: &HeaderMap) -> Option<AuthUser> {
+    if !state.auth.enabled() {
+        return Some(AuthUser::anonymous());
+    }
+
+    let token_user = bearer_token(headers)
+        .filter(|token| token.starts_with(TOKEN_PREFIX))
+        .and_then(|token| state.tokens.authenticate(&token, &state.auth));
+    if let Some(user) = token_user.or_else(|| state.auth.authenticate(headers)) {
+        return Some(user);
+    }
+
+    let (username, password) = basic_credentials(headers)?;
+    if password.starts_with(TOKEN_PREFIX) {
+        return state.tokens.authenticate(&password, &state.auth);
+    }
+    state.auth.authenticate_basic(&username, &password).await
+}
+
+// 认证中间件：拒绝未认证的API请求，并把用户信息放入请求扩展
+pub async fn require_auth(
+    State(state): State<Arc<AppState>>,
+    mut req: Request,
+    next: Next,
+) -> Result<Response, AppError> {
+    let user = resolve_user(&state, req.headers())
+        .await
+        .ok_or_else(|| AppError::Unauthorized("Authentication required".to_string()))?;
+
+    if user.token.is_some() {
+        tracing::info!("{} {} {}", user, req.method(), req.uri().path());
+    }
+
+    req.extensions_mut().insert(user);
+    Ok(next.run(req).await)
+}
+
+fn session_cookie(token: &str, max_age: i64) -> String {
+    format!(
+        "{}={}; Path=/; HttpOnly; SameSite=Lax; Max-Age={}",
+        SESSION_COOKIE, token, max_age
+    )
+}
+
+// 登录
+pub async fn login(
+    State(state): State<Arc<AppState>>,
+    Json(req): Json<LoginRequest>,
+) -> Result<Response, AppError> {
+    if !state.auth.enabled() {
+        return Err(AppError::InvalidRequest("Authentication is disabled".to_string()));
+    }
+
+    if let Some(wait) = state.auth.login_retry_after(&req.username, Utc::now().timestamp()) {
+        return Err(AppError::TooManyRequests(format!(
+            "Too many failed login attempts, retry in {} seconds",
+            wait
+        )));
+    }
+
+    if !state.auth.verify_password(&req.username, &req.password).await {
+        tracing::warn!("Failed login attempt for user {}", req.username);
+        state.auth.record_login_failure(&req.username, Utc::now().timestamp());
+        return Err(AppError::Unauthorized("Invalid username or password".to_string()));
+    }
+    state.auth.clear_login_failures(&req.username);
+
+    let (token, expires) = state.auth.create_session(&req.username);
+    tracing::info!("User {} logged in", req.username);
+
+    let cookie = session_cookie(&token, state.auth.session_secs);
+    let response = LoginResponse {
+        token,
+        username: req.username,
+        expires,
+    };
+
+    Ok((
+        [(header::SET_COOKIE, cookie)],
+        Json(ApiResponse::success(response)),
+    )
+        .into_response())
+}
+
+// 注销：删除服务端会话并清除Cookie
+pub async fn logout(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    if let Some(token) = request_token(&headers) {
+        state.auth.remove_session(&token);
+    }
+    tracing::info!("User {} logged out", user);
+
+    Ok((
+        [(header::SET_COOKIE, session_cookie("", 0))],
+        Json(ApiResponse::<()>::success(())),
+    )
+        .into_response())
+}
+
+// 当前用户信息
+pub async fn current_user(user: AuthUser) -> Json<ApiResponse<serde_json::Value>> {
+    Json(ApiResponse::success(serde_json::json!({
+        "username": user.username,
+        "groups": user.groups,
+        "is_admin": user.is_admin,
+        "token": user.token.as_ref().map(|scope| &scope.label),
+    })))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn store() -> AuthStore {
+        AuthStore::new(&serde_json::json!({
+            "users": [{ "username": "alice", "password_hash": hash_password("secret").unwrap() }]
+        }))
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn password_verification() {
+        let store = store();
+        assert!(store.verify_password("alice", "secret").await);
+        assert!(!store.verify_password("alice", "wrong").await);
+        // 不存在的用户同样校验一次哈希后失败
+        assert!(!store.verify_password("bob", "secret").await);
+        assert!(!store.verify_password("bob", "").await);
+    }
+
+    // 失败计数与是否启用认证无关，不需要计算哈希
+    fn disabled_store() -> AuthStore {
+        AuthStore::new(&serde_json::Value::Null).unwrap()
+    }
+
+    #[test]
+    fn failed_logins_back_off() {
+        let store = disabled_store();
+        let now = 1_000_000;
+        for _ in 0..LOGIN_FREE_ATTEMPTS - 1 {
+            store.record_login_failure("alice", now);
+        }
+        assert_eq!(store.login_retry_after("alice", now), None);
+
+        store.record_login_failure("alice", now);
+        assert_eq!(store.login_retry_after("alice", now), Some(1));
+        store.record_login_failure("alice", now);
+        assert_eq!(store.login_retry_after("alice", now), Some(2));
+        assert_eq!(store.login_retry_after("alice", now + 2), None);
+
+        // 等待时间有上限
+        for _ in 0..20 {
+            store.record_login_failure("alice", now);
+        }
+        assert_eq!(store.login_retry_after("alice", now), Some(LOGIN_MAX_BACKOFF_SECS));
+
+        // 其他用户名不受影响，登录成功后清除记录
+        assert_eq!(store.login_retry_after("bob", now), None);
+        store.clear_login_failures("alice");
+        assert_eq!(store.login_retry_after("alice", now), None);
+    }
+
+    #[test]
+    fn old_failures_are_forgotten() {
+        let store = disabled_store();
+        let now = 1_000_000;
+        for _ in 0..LOGIN_FREE_ATTEMPTS {
+            store.record_login_failure("alice", now);
+        }
+        assert!(store.login_retry_after("alice", now).is_some());
+
+        store.record_login_failure("alice", now + LOGIN_FAILURE_TTL_SECS);
+        assert_eq!(store.login_retry_after("alice", now + LOGIN_FAILURE_TTL_SECS), None);
+    }
+}
@@ -0,0 +1,657 @@
+use axum::{extract::State, Json};
+use chrono::{
+    format::{Item, StrftimeItems},
+    DateTime, Local,
+};
+use regex::Regex;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::SystemTime;
+use tokio::fs;
+
+use crate::{
+    acl::Permission,
+    auth::AuthUser,
+    errors::AppError,
+    events,
+    handlers::name_taken,
+    models::*,
+    search::build_regex,
+    utils::*,
+    AppState,
+};
+
+// 单次最多重命名的条目数
+const MAX_ITEMS: usize = 10000;
+const DEFAULT_DATE_FORMAT: &str = "%Y-%m-%d";
+const MAX_SEQUENCE_WIDTH: usize = 20;
+// 拒绝执行时错误信息中最多列出的问题条目数
+const MAX_REPORTED_PROBLEMS: usize = 5;
+
+// 新名称模板的片段
+enum Segment {
+    Literal(String),
+    // 序号，不足width位时补零
+    Sequence { width: usize },
+    // 修改时间，按strftime格式输出
+    Date(String),
+    Stem,
+    Ext,
+    Parent,
+}
+
+struct Template(Vec<Segment>);
+
+impl Template {
+    // 解析 {n}、{n:4}、{date}、{date:%Y%m}、{name}、{ext}、{parent}；{{ 和 }} 表示花括号本身，
+    // 正则替换中的 ${1} 原样保留
+    fn parse(template: &str) -> Result<Self, AppError> {
+        let mut segments = Vec::new();
+        let mut literal = String::new();
+        let mut chars = template.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '$' if chars.peek() == Some(&'{') => {
+                    literal.push('$');
+                    for c in chars.by_ref() {
+                        literal.push(c);
+                        if c == '}' {
+                            break;
+                        }
+                    }
+                }
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let mut token = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => token.push(c),
+                            None => {
+                                return Err(AppError::InvalidRequest("Unclosed '{' in replace".to_string()));
+                            }
+                        }
+                    }
+                    if !literal.is_empty() {
+                        segments.push(Segment::Literal(std::mem::take(&mut literal)));
+                    }
+                    segments.push(parse_token(&token)?);
+                }
+                c => literal.push(c),
+            }
+        }
+
+        if !literal.is_empty() {
+            segments.push(Segment::Literal(literal));
+        }
+        Ok(Template(segments))
+    }
+
+    // 作为正则替换串使用时，变量的值中的 $ 需要转义
+    fn render(&self, source: &Source, seq: u64, escape: bool) -> String {
+        let (stem, ext) = split_name(&source.name);
+        let mut out = String::new();
+        for segment in &self.0 {
+            let value = match segment {
+                Segment::Literal(text) => {
+                    out.push_str(text);
+                    continue;
+                }
+                Segment::Sequence { width } => format!("{:0width$}", seq, width = *width),
+                Segment::Date(format) => DateTime::<Local>::from(source.modified).format(format).to_string(),
+                Segment::Stem => stem.to_string(),
+                Segment::Ext => ext.to_string(),
+                Segment::Parent => source.parent_rel.rsplit('/').next().unwrap_or_default().to_string(),
+            };
+            if escape {
+                out.push_str(&value.replace('$', "$$"));
+            } else {
+                out.push_str(&value);
+            }
+        }
+        out
+    }
+}
+
+fn parse_token(token: &str) -> Result<Segment, AppError> {
+    let (name, arg) = match token.split_once(':') {
+        Some((name, arg)) => (name, Some(arg)),
+        None => (token, None),
+    };
+
+    match (name, arg) {
+        ("n", None) => Ok(Segment::Sequence { width: 1 }),
+        ("n", Some(width)) => width
+            .parse::<usize>()
+            .ok()
+            .filter(|width| (1..=MAX_SEQUENCE_WIDTH).contains(width))
+            .map(|width| Segment::Sequence { width })
+            .ok_or_else(|| AppError::InvalidRequest(format!("Invalid sequence width in {{{}}}", token))),
+        ("date", format) => {
+            let format = format.unwrap_or(DEFAULT_DATE_FORMAT);
+            // 无效的格式在输出时会panic，提前检查
+            if StrftimeItems::new(format).any(|item| matches!(item, Item::Error)) {
+                return Err(AppError::InvalidRequest(format!("Invalid date format in {{{}}}", token)));
+            }
+            Ok(Segment::Date(format.to_string()))
+        }
+        ("name", None) => Ok(Segment::Stem),
+        ("ext", None) => Ok(Segment::Ext),
+        ("parent", None) => Ok(Segment::Parent),
+        _ => Err(AppError::InvalidRequest(format!("Unknown token {{{}}}", token))),
+    }
+}
+
+// 拆分为主名和扩展名（含点）；以点开头且没有其他点的名称没有扩展名
+fn split_name(name: &str) -> (&str, &str) {
+    match name.rfind('.') {
+        Some(idx) if idx > 0 => (&name[..idx], &name[idx..]),
+        _ => (name, ""),
+    }
+}
+
+// 每个单词首字母大写，其余小写
+fn title_case(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut word_start = true;
+    for c in text.chars() {
+        if c.is_alphanumeric() {
+            if word_start {
+                out.extend(c.to_uppercase());
+            } else {
+                out.extend(c.to_lowercase());
+            }
+            word_start = false;
+        } else {
+            out.push(c);
+            word_start = true;
+        }
+    }
+    out
+}
+
+struct Rule {
+    find: Option<Regex>,
+    replace: Template,
+    case: CaseConversion,
+    start: u64,
+    step: u64,
+}
+
+impl Rule {
+    fn new(rule: &BulkRenameRule) -> Result<Self, AppError> {
+        let find = match rule.find.as_deref() {
+            Some(pattern) => Some(build_regex(pattern, !rule.ignore_case)?),
+            None => None,
+        };
+        let replace = match (&find, rule.replace.as_deref()) {
+            (_, Some(replace)) => replace,
+            (Some(_), None) => {
+                return Err(AppError::InvalidRequest("replace is required with find".to_string()));
+            }
+            (None, None) => "{name}{ext}",
+        };
+
+        Ok(Rule {
+            find,
+            replace: Template::parse(replace)?,
+            case: rule.case,
+            start: rule.start.unwrap_or(1),
+            step: rule.step.unwrap_or(1),
+        })
+    }
+
+    fn matches(&self, source: &Source) -> bool {
+        self.find.as_ref().is_none_or(|find| find.is_match(&source.name))
+    }
+
+    fn new_name(&self, source: &Source, seq: u64) -> String {
+        let name = match &self.find {
+            Some(find) => {
+                let replacement = self.replace.render(source, seq, true);
+                find.replace_all(&source.name, replacement.as_str()).into_owned()
+            }
+            None => self.replace.render(source, seq, false),
+        };
+
+        match self.case {
+            CaseConversion::None => name,
+            CaseConversion::Lower => name.to_lowercase(),
+            CaseConversion::Upper => name.to_uppercase(),
+            CaseConversion::Title => {
+                let (stem, ext) = split_name(&name);
+                format!("{}{}", title_case(stem), ext)
+            }
+        }
+    }
+}
+
+// 待重命名的条目
+struct Source {
+    rel: String,
+    name: String,
+    parent_rel: String,
+    modified: SystemTime,
+    // 无法重命名的原因
+    problem: Option<(BulkRenameStatus, String)>,
+}
+
+impl Source {
+    fn rejected(rel: String, status: BulkRenameStatus, error: &str) -> Self {
+        Source {
+            name: rel.rsplit('/').next().unwrap_or_default().to_string(),
+            parent_rel: String::new(),
+            rel,
+            modified: SystemTime::UNIX_EPOCH,
+            problem: Some((status, error.to_string())),
+        }
+    }
+}
+
+async fn load_source(state: &AppState, user: &AuthUser, path: &str) -> Source {
+    let safe_path = sanitize_path(path);
+    if safe_path.is_empty() {
+        return Source::rejected(path.to_string(), BulkRenameStatus::Invalid, "Cannot rename root directory");
+    }
+
+    let full_path = state.base_dir.join(&safe_path);
+    if !is_safe_path(&state.base_dir, &full_path) {
+        return Source::rejected(safe_path, BulkRenameStatus::Denied, "Access denied");
+    }
+    if let Err(e) = state.acl.check_tree(user, &safe_path, Permission::Delete) {
+        return Source::rejected(safe_path, BulkRenameStatus::Denied, &e.to_string());
+    }
+
+    let metadata = match fs::symlink_metadata(&full_path).await {
+        Ok(metadata) => metadata,
+        Err(_) => return Source::rejected(safe_path, BulkRenameStatus::Invalid, "Not found"),
+    };
+
+    let (parent_rel, name) = match safe_path.rsplit_once('/') {
+        Some((parent, name)) => (parent.to_string(), name.to_string()),
+        None => (String::new(), safe_path.clone()),
+    };
+    Source {
+        rel: safe_path,
+        name,
+        parent_rel,
+        modified: modified_time(&metadata),
+        problem: None,
+    }
+}
+
+// 按请求收集条目：指定的路径，或目录中的直接子项
+async fn collect_sources(
+    state: &AppState,
+    user: &AuthUser,
+    req: &BulkRenameRequest,
+) -> Result<Vec<Source>, AppError> {
+    let mut sources = Vec::new();
+
+    match &req.directory {
+        Some(_) if !req.paths.is_empty() => {
+            return Err(AppError::InvalidRequest("Specify either paths or directory".to_string()));
+        }
+        Some(directory) => {
+            let dir_safe = sanitize_path(directory);
+            let dir_path = state.base_dir.join(&dir_safe);
+            if !is_safe_path(&state.base_dir, &dir_path) {
+                return Err(AppError::PermissionDenied("Access denied".to_string()));
+            }
+            state.acl.check(user, &dir_safe, Permission::Read)?;
+            if !dir_path.is_dir() {
+                return Err(AppError::NotFound("Directory not found".to_string()));
+            }
+
+            let mut entries = fs::read_dir(&dir_path).await?;
+            while let Some(entry) = entries.next_entry().await? {
+                let path = entry.path();
+                if !is_safe_path(&state.base_dir, &path) || is_temp_path(&path) {
+                    continue;
+                }
+                if !req.include_dirs && entry.file_type().await?.is_dir() {
+                    continue;
+                }
+                let rel = join_relative_path(&dir_safe, &entry.file_name().to_string_lossy());
+                if !state.acl.allows(user, &rel, Permission::Read) {
+                    continue;
+                }
+                sources.push(load_source(state, user, &rel).await);
+                if sources.len() > MAX_ITEMS {
+                    break;
+                }
+            }
+        }
+        None => {
+            let mut seen = HashSet::new();
+            for path in &req.paths {
+                if seen.insert(sanitize_path(path)) {
+                    sources.push(load_source(state, user, path).await);
+                }
+            }
+        }
+    }
+
+    if sources.len() > MAX_ITEMS {
+        return Err(AppError::InvalidRequest(format!("At most {} items can be renamed at once", MAX_ITEMS)));
+    }
+
+    // 目录模式默认按名称排序，指定路径时默认保持请求中的顺序
+    match req.sort.or(req.directory.as_ref().map(|_| BulkRenameSort::Name)) {
+        Some(BulkRenameSort::Name) => sources.sort_by(|a, b| a.rel.cmp(&b.rel)),
+        Some(BulkRenameSort::Modified) => sources.sort_by(|a, b| a.modified.cmp(&b.modified).then(a.rel.cmp(&b.rel))),
+        None => {}
+    }
+    Ok(sources)
+}
+
+// 计算每个条目的新名称，并检查名称、权限和冲突
+async fn plan(
+    state: &AppState,
+    user: &AuthUser,
+    rule: &Rule,
+    sources: Vec<Source>,
+) -> Result<Vec<BulkRenameItem>, AppError> {
+    let mut items = Vec::with_capacity(sources.len());
+    let mut seq = rule.start;
+
+    for source in sources {
+        let mut item = BulkRenameItem {
+            path: source.rel.clone(),
+            new_path: source.rel.clone(),
+            status: BulkRenameStatus::Unchanged,
+            error: None,
+        };
+
+        if let Some((status, error)) = source.problem {
+            item.status = status;
+            item.error = Some(error);
+            items.push(item);
+            continue;
+        }
+        // 只有匹配的条目占用序号
+        if !rule.matches(&source) {
+            items.push(item);
+            continue;
+        }
+
+        let new_name = rule.new_name(&source, seq);
+        seq = seq.saturating_add(rule.step);
+        if new_name == source.name {
+            items.push(item);
+            continue;
+        }
+
+        item.new_path = join_relative_path(&source.parent_rel, &new_name);
+        if !is_valid_file_name(&new_name) {
+            item.status = BulkRenameStatus::Invalid;
+            item.error = Some("Invalid file name".to_string());
+        } else if !is_safe_path(&state.base_dir, &state.base_dir.join(&item.new_path)) {
+            item.status = BulkRenameStatus::Denied;
+            item.error = Some("Access denied".to_string());
+        } else if let Err(e) = state.acl.check_tree(user, &item.new_path, Permission::Write) {
+            item.status = BulkRenameStatus::Denied;
+            item.error = Some(e.to_string());
+        } else {
+            item.status = BulkRenameStatus::Rename;
+        }
+        items.push(item);
+    }
+
+    // 多个条目的新名称相同，或新名称已被不参与重命名的条目占用
+    let renaming: HashSet<String> = items
+        .iter()
+        .filter(|item| item.status == BulkRenameStatus::Rename)
+        .map(|item| item.path.clone())
+        .collect();
+    let mut targets: HashMap<String, usize> = HashMap::new();
+    for item in items.iter().filter(|item| item.status == BulkRenameStatus::Rename) {
+        *targets.entry(item.new_path.clone()).or_default() += 1;
+    }
+
+    for item in items.iter_mut().filter(|item| item.status == BulkRenameStatus::Rename) {
+        let conflict = if targets[&item.new_path] > 1 {
+            Some("Several items would get this name")
+        } else if !renaming.contains(&item.new_path) && target_taken(state, &item.path, &item.new_path).await? {
+            Some("Target already exists")
+        } else {
+            None
+        };
+        if let Some(conflict) = conflict {
+            item.status = BulkRenameStatus::Conflict;
+            item.error = Some(conflict.to_string());
+        }
+    }
+
+    Ok(items)
+}
+
+async fn target_taken(state: &AppState, rel: &str, new_rel: &str) -> std::io::Result<bool> {
+    let (parent, new_name) = new_rel.rsplit_once('/').unwrap_or(("", new_rel));
+    let old_name = rel.rsplit('/').next().unwrap_or(rel);
+    name_taken(&state.base_dir.join(parent), new_name, old_name).await
+}
+
+// 一次重命名：原路径、中转的临时路径、目标路径
+struct Move {
+    source: PathBuf,
+    staged: PathBuf,
+    target: PathBuf,
+}
+
+// 先把所有条目移到临时名称，再移到目标名称，使互换名称和链式重命名也能完成；任何一步失败都撤销已完成的步骤
+async fn apply(moves: &[Move]) -> Result<(), AppError> {
+    for (staged, m) in moves.iter().enumerate() {
+        if let Err(e) = fs::rename(&m.source, &m.staged).await {
+            rollback(&moves[..staged], 0).await;
+            return Err(e.into());
+        }
+    }
+
+    for (done, m) in moves.iter().enumerate() {
+        // rename会直接覆盖已有文件，计划之后出现的同名条目同样视为冲突
+        let result = if fs::symlink_metadata(&m.target).await.is_ok() {
+            Err(AppError::Conflict(format!("{} was created during the rename", m.target.display())))
+        } else {
+            fs::rename(&m.staged, &m.target).await.map_err(AppError::from)
+        };
+        if let Err(e) = result {
+            rollback(moves, done).await;
+            return Err(e);
+        }
+    }
+    Ok(())
+}
+
+async fn rollback(moves: &[Move], done: usize) {
+    for m in moves[..done].iter().rev() {
+        if let Err(e) = fs::rename(&m.target, &m.staged).await {
+            tracing::error!("Failed to roll back rename of {}: {}", m.target.display(), e);
+        }
+    }
+    for m in moves.iter().rev() {
+        if let Err(e) = fs::rename(&m.staged, &m.source).await {
+            tracing::error!("Failed to roll back rename of {}: {}", m.source.display(), e);
+        }
+    }
+}
+
+// 按规则批量重命名；dry_run时只返回计划
+pub async fn bulk_rename(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Json(req): Json<BulkRenameRequest>,
+) -> Result<Json<ApiResponse<BulkRenameResponse>>, AppError> {
+    let rule = Rule::new(&req.rule)?;
+    let sources = collect_sources(&state, &user, &req).await?;
+    let items = plan(&state, &user, &rule, sources).await?;
+
+    let problems: Vec<&BulkRenameItem> = items
+        .iter()
+        .filter(|item| {
+            !matches!(item.status, BulkRenameStatus::Rename | BulkRenameStatus::Unchanged)
+        })
+        .collect();
+    let renamed = items
+        .iter()
+        .filter(|item| item.status == BulkRenameStatus::Rename)
+        .count();
+
+    if !req.dry_run {
+        // 有任何条目无法重命名时整体不执行
+        if !problems.is_empty() {
+            let examples: Vec<String> = problems
+                .iter()
+                .take(MAX_REPORTED_PROBLEMS)
+                .map(|item| format!("{}: {}", item.path, item.error.as_deref().unwrap_or_default()))
+                .collect();
+            return Err(AppError::Conflict(format!(
+                "{} items cannot be renamed ({})",
+                problems.len(),
+                examples.join("; ")
+            )));
+        }
+
+        let planned: Vec<&BulkRenameItem> = items
+            .iter()
+            .filter(|item| item.status == BulkRenameStatus::Rename)
+            .collect();
+        let moves: Vec<Move> = planned
+            .iter()
+            .map(|item| {
+                let target = state.base_dir.join(&item.new_path);
+                Move {
+                    source: state.base_dir.join(&item.path),
+                    staged: temp_path_for(&target),
+                    target,
+                }
+            })
+            .collect();
+        apply(&moves).await?;
+
+        for item in &planned {
+            events::file_renamed(&state, &item.path, &item.new_path, &user.username).await;
+        }
+        tracing::info!("{} bulk renamed {} items", user, renamed);
+    }
+
+    let conflicts = problems.len();
+    Ok(Json(ApiResponse::success(BulkRenameResponse {
+        dry_run: req.dry_run,
+        items,
+        renamed,
+        conflicts,
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+
+    fn source(name: &str) -> Source {
+        Source {
+            rel: format!("photos/{}", name),
+            name: name.to_string(),
+            parent_rel: "photos".to_string(),
+            // 2024-05-03 12:00:00 UTC
+            modified: SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1714737600),
+            problem: None,
+        }
+    }
+
+    fn rule(find: Option<&str>, replace: &str) -> Rule {
+        Rule::new(&BulkRenameRule {
+            find: find.map(str::to_string),
+            replace: Some(replace.to_string()),
+            ..Default::default()
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn template_tokens() {
+        let template = Template::parse("{parent}_{n:4}_{date:%Y}{{x}}{name}{ext}").unwrap();
+        assert_eq!(template.render(&source("a.jpg"), 7, false), "photos_0007_2024{x}a.jpg");
+        assert_eq!(Template::parse("{n}").unwrap().render(&source("a"), 12, false), "12");
+
+        for invalid in ["{n:0}", "{n:x}", "{size}", "{name", "{date:%Q}"] {
+            assert!(Template::parse(invalid).is_err(), "{} accepted", invalid);
+        }
+    }
+
+    #[test]
+    fn regex_replacement() {
+        // 替换所有匹配，捕获组引用原样保留
+        assert_eq!(rule(Some("a"), "o").new_name(&source("banana"), 1), "bonono");
+        assert_eq!(rule(Some(r"(\d+)"), "<${1}>").new_name(&source("img12_3.jpg"), 1), "img<12>_<3>.jpg");
+        // 变量的值中的 $ 不会被当作捕获组引用
+        assert_eq!(rule(Some("^x"), "{name}_").new_name(&source("x$1.txt"), 1), "x$1_$1.txt");
+    }
+
+    fn moves(dir: &TempDir, pairs: &[(&str, &str)]) -> Vec<Move> {
+        pairs
+            .iter()
+            .map(|(from, to)| {
+                let target = dir.path().join(to);
+                Move {
+                    source: dir.path().join(from),
+                    staged: temp_path_for(&target),
+                    target,
+                }
+            })
+            .collect()
+    }
+
+    fn read(dir: &TempDir, name: &str) -> String {
+        std::fs::read_to_string(dir.path().join(name)).unwrap()
+    }
+
+    #[tokio::test]
+    async fn apply_swaps_and_chains_names() {
+        let dir = TempDir::new();
+        for name in ["a", "b", "c"] {
+            std::fs::write(dir.path().join(name), name).unwrap();
+        }
+
+        // a和b互换名称，同时把c重命名为d
+        apply(&moves(&dir, &[("a", "b"), ("b", "a"), ("c", "d")])).await.unwrap();
+        assert_eq!(read(&dir, "a"), "b");
+        assert_eq!(read(&dir, "b"), "a");
+        assert_eq!(read(&dir, "d"), "c");
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 3);
+    }
+
+    #[tokio::test]
+    async fn failed_apply_rolls_back() {
+        let dir = TempDir::new();
+        for name in ["a", "b", "taken"] {
+            std::fs::write(dir.path().join(name), name).unwrap();
+        }
+
+        // 第二个目标在计划之后被占用，已完成的步骤全部撤销
+        let result = apply(&moves(&dir, &[("a", "x"), ("b", "taken")])).await;
+        assert!(matches!(result, Err(AppError::Conflict(_))));
+        assert_eq!(read(&dir, "a"), "a");
+        assert_eq!(read(&dir, "b"), "b");
+        assert_eq!(read(&dir, "taken"), "taken");
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 3);
+
+        // 直接回滚部分完成的重命名
+        let moves = moves(&dir, &[("a", "y")]);
+        std::fs::rename(&moves[0].source, &moves[0].staged).unwrap();
+        std::fs::rename(&moves[0].staged, &moves[0].target).unwrap();
+        rollback(&moves, 1).await;
+        assert_eq!(read(&dir, "a"), "a");
+        assert!(!dir.path().join("y").exists());
+    }
+}
@@ -0,0 +1,500 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::{HeaderMap, HeaderName, HeaderValue},
+    Json,
+};
+use base64::{engine::general_purpose::STANDARD, Engine};
+use md5::Md5;
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::io::Read;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::SystemTime;
+use tokio::fs;
+use tokio::sync::Semaphore;
+
+use crate::{
+    acl::Permission,
+    auth::AuthUser,
+    errors::AppError,
+    models::*,
+    utils::*,
+    AppState,
+};
+
+const ALL_ALGORITHMS: &[HashAlgorithm] = &[
+    HashAlgorithm::Md5,
+    HashAlgorithm::Sha1,
+    HashAlgorithm::Sha256,
+    HashAlgorithm::Blake3,
+];
+
+const READ_BUFFER_SIZE: usize = 256 * 1024;
+
+const WANT_REPR_DIGEST: HeaderName = HeaderName::from_static("want-repr-digest");
+const WANT_DIGEST: HeaderName = HeaderName::from_static("want-digest");
+const REPR_DIGEST: HeaderName = HeaderName::from_static("repr-digest");
+const DIGEST: HeaderName = HeaderName::from_static("digest");
+
+impl HashAlgorithm {
+    fn parse(name: &str) -> Option<Self> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "md5" => Some(HashAlgorithm::Md5),
+            "sha1" | "sha-1" => Some(HashAlgorithm::Sha1),
+            "sha256" | "sha-256" => Some(HashAlgorithm::Sha256),
+            "blake3" => Some(HashAlgorithm::Blake3),
+            _ => None,
+        }
+    }
+}
+
+// 解析逗号分隔的算法列表，未设置时返回全部算法
+pub fn parse_algorithms(value: Option<&str>) -> Result<Vec<HashAlgorithm>, AppError> {
+    let Some(value) = value.filter(|v| !v.trim().is_empty()) else {
+        return Ok(ALL_ALGORITHMS.to_vec());
+    };
+    let mut algorithms = Vec::new();
+    for name in value.split(',').filter(|name| !name.trim().is_empty()) {
+        let algorithm = HashAlgorithm::parse(name)
+            .ok_or_else(|| AppError::InvalidRequest(format!("Unsupported checksum algorithm: {}", name.trim())))?;
+        if !algorithms.contains(&algorithm) {
+            algorithms.push(algorithm);
+        }
+    }
+    Ok(algorithms)
+}
+
+enum Hasher {
+    Md5(Md5),
+    Sha1(Sha1),
+    Sha256(Sha256),
+    Blake3(Box<blake3::Hasher>),
+}
+
+impl Hasher {
+    fn new(algorithm: HashAlgorithm) -> Self {
+        match algorithm {
+            HashAlgorithm::Md5 => Hasher::Md5(Md5::new()),
+            HashAlgorithm::Sha1 => Hasher::Sha1(Sha1::new()),
+            HashAlgorithm::Sha256 => Hasher::Sha256(Sha256::new()),
+            HashAlgorithm::Blake3 => Hasher::Blake3(Box::new(blake3::Hasher::new())),
+        }
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        match self {
+            Hasher::Md5(h) => h.update(data),
+            Hasher::Sha1(h) => h.update(data),
+            Hasher::Sha256(h) => h.update(data),
+            Hasher::Blake3(h) => {
+                h.update(data);
+            }
+        }
+    }
+
+    fn finalize(self) -> String {
+        match self {
+            Hasher::Md5(h) => hex::encode(h.finalize()),
+            Hasher::Sha1(h) => hex::encode(h.finalize()),
+            Hasher::Sha256(h) => hex::encode(h.finalize()),
+            Hasher::Blake3(h) => h.finalize().to_hex().to_string(),
+        }
+    }
+}
+
+// 读取一遍文件同时计算多个摘要
+fn hash_file(path: &std::path::Path, algorithms: &[HashAlgorithm]) -> std::io::Result<Vec<(HashAlgorithm, String)>> {
+    let mut file = std::fs::File::open(path)?;
+    let mut hashers: Vec<(HashAlgorithm, Hasher)> = algorithms.iter().map(|&a| (a, Hasher::new(a))).collect();
+    let mut buf = vec![0u8; READ_BUFFER_SIZE];
+    loop {
+        let n = file.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        for (_, hasher) in hashers.iter_mut() {
+            hasher.update(&buf[..n]);
+        }
+    }
+    Ok(hashers.into_iter().map(|(a, h)| (a, h.finalize())).collect())
+}
+
+// 上传时边写入边计算摘要：始终计算SHA-256，客户端提供了其他算法的预期值时一并计算
+pub struct UploadDigests {
+    expected: Vec<(HashAlgorithm, String)>,
+    hashers: Vec<(HashAlgorithm, Hasher)>,
+}
+
+impl UploadDigests {
+    pub fn new(expected: Vec<(HashAlgorithm, String)>) -> Self {
+        let mut algorithms = vec![HashAlgorithm::Sha256];
+        for (algorithm, _) in &expected {
+            if !algorithms.contains(algorithm) {
+                algorithms.push(*algorithm);
+            }
+        }
+        UploadDigests {
+            expected,
+            hashers: algorithms.into_iter().map(|a| (a, Hasher::new(a))).collect(),
+        }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        for (_, hasher) in self.hashers.iter_mut() {
+            hasher.update(data);
+        }
+    }
+
+    // 返回计算出的摘要，与预期值不一致时返回错误说明
+    pub fn finish(self) -> Result<BTreeMap<HashAlgorithm, String>, String> {
+        let digests: BTreeMap<HashAlgorithm, String> =
+            self.hashers.into_iter().map(|(a, h)| (a, h.finalize())).collect();
+        for (algorithm, expected) in &self.expected {
+            let actual = &digests[algorithm];
+            if actual != expected {
+                return Err(format!(
+                    "Checksum mismatch: expected {} {}, got {}",
+                    algorithm_name(*algorithm),
+                    expected,
+                    actual
+                ));
+            }
+        }
+        Ok(digests)
+    }
+}
+
+fn algorithm_name(algorithm: HashAlgorithm) -> &'static str {
+    match algorithm {
+        HashAlgorithm::Md5 => "md5",
+        HashAlgorithm::Sha1 => "sha1",
+        HashAlgorithm::Sha256 => "sha256",
+        HashAlgorithm::Blake3 => "blake3",
+    }
+}
+
+// 解析上传的预期摘要，支持两种格式：
+// 表单字段 "sha256:<hex>"，以及Content-Digest头 "sha-256=:<base64>:"（可用逗号分隔多个）；
+// 只接受SHA-256和BLAKE3，返回小写十六进制
+pub fn parse_expected_digests(value: &str) -> Result<Vec<(HashAlgorithm, String)>, AppError> {
+    let invalid = || AppError::InvalidRequest(format!("Invalid checksum: {}", value.trim()));
+    let mut expected = Vec::new();
+    for item in value.split(',').map(str::trim).filter(|item| !item.is_empty()) {
+        let (name, digest) = if let Some((name, encoded)) = item.split_once('=') {
+            let encoded = encoded.trim().trim_matches(':');
+            let bytes = STANDARD.decode(encoded).map_err(|_| invalid())?;
+            (name, hex::encode(bytes))
+        } else {
+            let (name, digest) = item.split_once(':').ok_or_else(invalid)?;
+            let bytes = hex::decode(digest.trim()).map_err(|_| invalid())?;
+            (name, hex::encode(bytes))
+        };
+        let algorithm = match HashAlgorithm::parse(name) {
+            Some(a @ (HashAlgorithm::Sha256 | HashAlgorithm::Blake3)) => a,
+            _ => {
+                return Err(AppError::InvalidRequest(format!(
+                    "Unsupported upload checksum algorithm: {}",
+                    name.trim()
+                )))
+            }
+        };
+        // SHA-256和BLAKE3的摘要都是32字节
+        if digest.len() != 64 {
+            return Err(invalid());
+        }
+        expected.push((algorithm, digest));
+    }
+    if expected.is_empty() {
+        return Err(invalid());
+    }
+    Ok(expected)
+}
+
+struct CachedChecksums {
+    size: u64,
+    modified: SystemTime,
+    digests: BTreeMap<HashAlgorithm, String>,
+}
+
+// 文件摘要的内存缓存，按路径保存并以大小和修改时间判断是否仍然有效
+pub struct ChecksumStore {
+    max_entries: usize,
+    // 限制同时计算摘要的文件数，计算需要读取整个文件
+    permits: Semaphore,
+    entries: Mutex<HashMap<String, CachedChecksums>>,
+}
+
+impl ChecksumStore {
+    pub fn new(config: &serde_json::Value) -> Self {
+        let max_concurrent = config["max_concurrent"]
+            .as_u64()
+            .map(|n| n.max(1) as usize)
+            .unwrap_or_else(|| {
+                std::thread::available_parallelism()
+                    .map(|n| n.get())
+                    .unwrap_or(1)
+                    .min(4)
+            });
+
+        ChecksumStore {
+            max_entries: config["max_cached_files"].as_u64().unwrap_or(10000).max(1) as usize,
+            permits: Semaphore::new(max_concurrent),
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<String, CachedChecksums>> {
+        self.entries.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn cached(&self, rel_path: &str, metadata: &std::fs::Metadata) -> BTreeMap<HashAlgorithm, String> {
+        self.lock()
+            .get(rel_path)
+            .filter(|cached| cached.size == metadata.len() && cached.modified == modified_time(metadata))
+            .map(|cached| cached.digests.clone())
+            .unwrap_or_default()
+    }
+
+    fn store(&self, rel_path: &str, metadata: &std::fs::Metadata, digests: &BTreeMap<HashAlgorithm, String>) {
+        let mut entries = self.lock();
+        let size = metadata.len();
+        let modified = modified_time(metadata);
+        match entries.get_mut(rel_path) {
+            Some(cached) if cached.size == size && cached.modified == modified => {
+                cached.digests.extend(digests.iter().map(|(a, d)| (*a, d.clone())));
+            }
+            _ => {
+                if entries.len() >= self.max_entries {
+                    entries.clear();
+                }
+                entries.insert(
+                    rel_path.to_string(),
+                    CachedChecksums {
+                        size,
+                        modified,
+                        digests: digests.clone(),
+                    },
+                );
+            }
+        }
+    }
+
+    // 上传时已经计算出的摘要直接写入缓存
+    pub fn remember(&self, rel_path: &str, metadata: &std::fs::Metadata, digests: &BTreeMap<HashAlgorithm, String>) {
+        self.store(rel_path, metadata, digests);
+    }
+
+    // 文件或目录变化后删除它及其下所有文件的缓存
+    pub fn invalidate(&self, rel_path: &str) {
+        let prefix = format!("{}/", rel_path);
+        self.lock()
+            .retain(|path, _| !rel_path.is_empty() && path != rel_path && !path.starts_with(&prefix));
+    }
+}
+
+// 文件的摘要：先查缓存和索引，compute为true时读取文件计算缺少的算法
+pub async fn file_digests(
+    state: &AppState,
+    full_path: &std::path::Path,
+    rel_path: &str,
+    metadata: &std::fs::Metadata,
+    algorithms: &[HashAlgorithm],
+    compute: bool,
+) -> Result<BTreeMap<HashAlgorithm, String>, AppError> {
+    let store = &state.checksums;
+    let mut digests = store.cached(rel_path, metadata);
+
+    // 启用索引的hash选项时，SHA-256可以直接使用索引中的结果
+    if algorithms.contains(&HashAlgorithm::Sha256) && !digests.contains_key(&HashAlgorithm::Sha256) {
+        if let Some((size, indexed_modified, hash)) = state.index.file_hash(rel_path).await {
+            if size == metadata.len() && indexed_modified == modified_nanos(metadata) {
+                digests.insert(HashAlgorithm::Sha256, hash);
+            }
+        }
+    }
+
+    let missing: Vec<HashAlgorithm> = algorithms.iter().copied().filter(|a| !digests.contains_key(a)).collect();
+    if compute && !missing.is_empty() {
+        let _permit = store
+            .permits
+            .acquire()
+            .await
+            .map_err(|e| AppError::IoError(e.to_string()))?;
+        let path = full_path.to_path_buf();
+        let computed = tokio::task::spawn_blocking(move || hash_file(&path, &missing))
+            .await
+            .map_err(|e| AppError::IoError(e.to_string()))??;
+        digests.extend(computed);
+
+        // 计算期间文件被修改时结果不可靠，不写入缓存
+        let unchanged = fs::metadata(full_path)
+            .await
+            .is_ok_and(|m| m.len() == metadata.len() && modified_time(&m) == modified_time(metadata));
+        if !unchanged {
+            return Err(AppError::Conflict("File changed while computing checksum".to_string()));
+        }
+    }
+
+    digests.retain(|a, _| algorithms.contains(a));
+    if !digests.is_empty() {
+        store.store(rel_path, metadata, &digests);
+    }
+    Ok(digests)
+}
+
+// 下载响应的摘要头：客户端通过Want-Repr-Digest或Want-Digest请求时计算SHA-256，
+// 否则只在已有缓存结果时附带，避免每次下载都读取两遍文件
+pub async fn add_digest_headers(
+    state: &AppState,
+    full_path: &std::path::Path,
+    rel_path: &str,
+    request_headers: &HeaderMap,
+    response_headers: &mut HeaderMap,
+) -> Result<(), AppError> {
+    let metadata = fs::metadata(full_path).await?;
+    let wanted = request_headers.contains_key(WANT_REPR_DIGEST) || request_headers.contains_key(WANT_DIGEST);
+    let digests = file_digests(state, full_path, rel_path, &metadata, &[HashAlgorithm::Sha256], wanted).await?;
+
+    let Some(sha256) = digests.get(&HashAlgorithm::Sha256).and_then(|d| hex::decode(d).ok()) else {
+        return Ok(());
+    };
+    let encoded = STANDARD.encode(sha256);
+    if let Ok(value) = HeaderValue::from_str(&format!("sha-256=:{}:", encoded)) {
+        response_headers.insert(REPR_DIGEST, value);
+    }
+    if let Ok(value) = HeaderValue::from_str(&format!("sha-256={}", encoded)) {
+        response_headers.insert(DIGEST, value);
+    }
+    Ok(())
+}
+
+// 计算文件摘要
+pub async fn checksum(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Path(path): Path<String>,
+    Query(query): Query<ChecksumQuery>,
+) -> Result<Json<ApiResponse<ChecksumResponse>>, AppError> {
+    let algorithms = parse_algorithms(query.algorithms.as_deref())?;
+    let safe_path = sanitize_path(&path);
+    let full_path = state.base_dir.join(&safe_path);
+
+    if !is_safe_path(&state.base_dir, &full_path) {
+        return Err(AppError::PermissionDenied("Access denied".to_string()));
+    }
+
+    state.acl.check(&user, &safe_path, Permission::Read)?;
+
+    let metadata = fs::metadata(&full_path)
+        .await
+        .map_err(|_| AppError::NotFound("File not found".to_string()))?;
+    if metadata.is_dir() {
+        return Err(AppError::InvalidPath("Cannot compute checksum of a directory".to_string()));
+    }
+
+    let checksums = file_digests(&state, &full_path, &safe_path, &metadata, &algorithms, true).await?;
+
+    Ok(Json(ApiResponse::success(ChecksumResponse {
+        path: safe_path,
+        size: metadata.len(),
+        modified: format_system_time(modified_time(&metadata)),
+        checksums,
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::test_state;
+    use std::time::Duration;
+
+    // "abc" 的摘要
+    const SHA256_ABC: &str = "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad";
+    const SHA256_ABC_B64: &str = "ungWv48Bz+pBQUDeXa4iI7ADYaOWF3qctBD/YfIAFa0=";
+
+    #[test]
+    fn form_field_digest() {
+        let expected = parse_expected_digests(&format!("sha256:{}", SHA256_ABC.to_uppercase())).unwrap();
+        assert_eq!(expected, vec![(HashAlgorithm::Sha256, SHA256_ABC.to_string())]);
+        assert_eq!(parse_expected_digests(&format!("SHA-256:{}", SHA256_ABC)).unwrap(), expected);
+    }
+
+    #[test]
+    fn content_digest_header() {
+        let expected = parse_expected_digests(&format!("sha-256=:{}:", SHA256_ABC_B64)).unwrap();
+        assert_eq!(expected, vec![(HashAlgorithm::Sha256, SHA256_ABC.to_string())]);
+
+        let blake3 = blake3::hash(b"abc");
+        let both = parse_expected_digests(&format!(
+            "sha-256=:{}:, blake3=:{}:",
+            SHA256_ABC_B64,
+            STANDARD.encode(blake3.as_bytes())
+        ))
+        .unwrap();
+        assert_eq!(both[1], (HashAlgorithm::Blake3, blake3.to_hex().to_string()));
+    }
+
+    #[test]
+    fn invalid_digests_are_rejected() {
+        for value in [
+            "",
+            " , ",
+            "nonsense",
+            "sha256:xyz",
+            "sha256:abcd",
+            "sha-256=:not base64:",
+            "sha-256=:YWJj:",
+            "md5:900150983cd24fb0d6963f7d28e17f72",
+            "sha1=:qZk+NkcGgWq6PiVxeFDCbJzQ2J0=:",
+        ] {
+            assert!(parse_expected_digests(value).is_err(), "{:?}", value);
+        }
+    }
+
+    #[test]
+    fn upload_digests_verify_expected_value() {
+        let mut digests = UploadDigests::new(parse_expected_digests(&format!("sha256:{}", SHA256_ABC)).unwrap());
+        digests.update(b"ab");
+        digests.update(b"c");
+        let computed = digests.finish().unwrap();
+        assert_eq!(computed[&HashAlgorithm::Sha256], SHA256_ABC);
+
+        let mut digests = UploadDigests::new(parse_expected_digests(&format!("sha256:{}", SHA256_ABC)).unwrap());
+        digests.update(b"abd");
+        assert!(digests.finish().unwrap_err().starts_with("Checksum mismatch"));
+
+        // 未提供预期值时也计算SHA-256
+        let mut digests = UploadDigests::new(Vec::new());
+        digests.update(b"abc");
+        assert_eq!(digests.finish().unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn indexed_hash_requires_exact_modified_time() {
+        let config = serde_json::json!({ "index": { "enabled": true, "hash": true } });
+        let (state, _dir) = test_state(config).await;
+        let full_path = state.base_dir.join("a.txt");
+        std::fs::write(&full_path, "abc").unwrap();
+        state.index.sync_all(true).await.unwrap();
+
+        let metadata = std::fs::metadata(&full_path).unwrap();
+        let digests = file_digests(&state, &full_path, "a.txt", &metadata, &[HashAlgorithm::Sha256], false)
+            .await
+            .unwrap();
+        assert_eq!(digests[&HashAlgorithm::Sha256], SHA256_ABC);
+
+        // 同一秒内改写为同样大小的内容，索引中的摘要已过期，不能再使用
+        let modified = metadata.modified().unwrap();
+        let nanos = modified.duration_since(SystemTime::UNIX_EPOCH).unwrap().subsec_nanos();
+        let changed = if nanos > 0 { modified - Duration::from_nanos(1) } else { modified + Duration::from_nanos(1) };
+        std::fs::write(&full_path, "abd").unwrap();
+        std::fs::File::options().write(true).open(&full_path).unwrap().set_modified(changed).unwrap();
+
+        let metadata = std::fs::metadata(&full_path).unwrap();
+        let digests = file_digests(&state, &full_path, "a.txt", &metadata, &[HashAlgorithm::Sha256], false)
+            .await
+            .unwrap();
+        assert!(digests.is_empty());
+    }
+}
@@ -10,8 +10,37 @@ pub enum AppError {
     NotFound(String),
     InvalidPath(String),
     PermissionDenied(String),
+    Unauthorized(String),
+    TooManyRequests(String),
     IoError(String),
     InvalidRequest(String),
+    Conflict(String),
+    PreconditionFailed(String),
+    PayloadTooLarge(String),
+    MethodNotAllowed(String),
+    UnsupportedMediaType(String),
+    Locked(String),
+}
+
+impl std::fmt::Display for AppError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let msg = match self {
+            AppError::NotFound(msg)
+            | AppError::InvalidPath(msg)
+            | AppError::PermissionDenied(msg)
+            | AppError::Unauthorized(msg)
+            | AppError::TooManyRequests(msg)
+            | AppError::IoError(msg)
+            | AppError::InvalidRequest(msg)
+            | AppError::Conflict(msg)
+            | AppError::PreconditionFailed(msg)
+            | AppError::PayloadTooLarge(msg)
+            | AppError::MethodNotAllowed(msg)
+            | AppError::UnsupportedMediaType(msg)
+            | AppError::Locked(msg) => msg,
+        };
+        write!(f, "{}", msg)
+    }
 }
 
 impl IntoResponse for AppError {
@@ -20,8 +49,16 @@ impl IntoResponse for AppError {
             AppError::NotFound(msg) => (StatusCode::NOT_FOUND, msg),
             AppError::InvalidPath(msg) => (StatusCode::BAD_REQUEST, msg),
             AppError::PermissionDenied(msg) => (StatusCode::FORBIDDEN, msg),
+            AppError::Unauthorized(msg) => (StatusCode::UNAUTHORIZED, msg),
+            AppError::TooManyRequests(msg) => (StatusCode::TOO_MANY_REQUESTS, msg),
             AppError::IoError(msg) => (StatusCode::INTERNAL_SERVER_ERROR, msg),
             AppError::InvalidRequest(msg) => (StatusCode::BAD_REQUEST, msg),
+            AppError::Conflict(msg) => (StatusCode::CONFLICT, msg),
+            AppError::PreconditionFailed(msg) => (StatusCode::PRECONDITION_FAILED, msg),
+            AppError::PayloadTooLarge(msg) => (StatusCode::PAYLOAD_TOO_LARGE, msg),
+            AppError::MethodNotAllowed(msg) => (StatusCode::METHOD_NOT_ALLOWED, msg),
+            AppError::UnsupportedMediaType(msg) => (StatusCode::UNSUPPORTED_MEDIA_TYPE, msg),
+            AppError::Locked(msg) => (StatusCode::LOCKED, msg),
         };
 
         let response: ApiResponse<()> = ApiResponse::error(status.as_u16() as i32, message);
@@ -35,6 +72,12 @@ impl From<std::io::Error> for AppError {
     }
 }
 
+impl From<rusqlite::Error> for AppError {
+    fn from(err: rusqlite::Error) -> Self {
+        AppError::IoError(err.to_string())
+    }
+}
+
 impl From<anyhow::Error> for AppError {
     fn from(err: anyhow::Error) -> Self {
         AppError::IoError(err.to_string())
@@ -0,0 +1,381 @@
+use axum::{
+    extract::{Query, State},
+    response::sse::{Event, KeepAlive, Sse},
+};
+use futures_util::Stream;
+use notify::{
+    event::{ModifyKind, RenameMode},
+    EventKind, RecommendedWatcher, RecursiveMode, Watcher,
+};
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::{broadcast, mpsc};
+
+use crate::{
+    acl::Permission,
+    auth::AuthUser,
+    errors::AppError,
+    models::*,
+    utils::*,
+    AppState,
+};
+
+// 每个订阅者最多缓存的未发送通知数，超过后通知客户端重新加载
+const CHANNEL_CAPACITY: usize = 256;
+
+// 监听器收到的事件延迟处理，让服务器自身的通知先记录下来以便去重
+const WATCH_DELAY: Duration = Duration::from_millis(300);
+
+// 同一路径在这段时间内已经通知过时，不再发送监听器收到的重复事件
+const SUPPRESS_WINDOW: Duration = Duration::from_secs(2);
+
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(15);
+
+// 最近通知过的路径：通知时间以及是否由服务器自身的操作产生
+struct RecentChange {
+    at: Instant,
+    local: bool,
+}
+
+// 监听器收到、等待去重的变化
+struct WatchedChange {
+    received: Instant,
+    kind: ChangeKind,
+    path: String,
+    from: Option<String>,
+    // 单独的移出或移入事件所属重命名的标识
+    half_rename: Option<usize>,
+}
+
+// 文件变化通知的分发中心
+pub struct EventHub {
+    enabled: bool,
+    watch: bool,
+    tx: broadcast::Sender<ChangeEvent>,
+    recent: Mutex<HashMap<String, RecentChange>>,
+    // 监听器收到的成对重命名事件的标识，同一标识的单独移出和移入不再通知
+    renamed: Mutex<HashMap<usize, Instant>>,
+    watcher: Mutex<Option<RecommendedWatcher>>,
+}
+
+impl EventHub {
+    pub fn new(config: &serde_json::Value) -> Self {
+        let enabled = config["enabled"].as_bool().unwrap_or(true);
+        let watch = config["watch"].as_bool().unwrap_or(true);
+        let (tx, _) = broadcast::channel(CHANNEL_CAPACITY);
+
+        EventHub {
+            enabled,
+            watch,
+            tx,
+            recent: Mutex::new(HashMap::new()),
+            renamed: Mutex::new(HashMap::new()),
+            watcher: Mutex::new(None),
+        }
+    }
+
+    fn watch_enabled(&self) -> bool {
+        self.enabled && self.watch
+    }
+
+    fn remember(&self, paths: &[&str], local: bool) {
+        let mut recent = self.recent.lock().unwrap_or_else(|e| e.into_inner());
+        let now = Instant::now();
+        recent.retain(|_, change| now.duration_since(change.at) < SUPPRESS_WINDOW);
+        for path in paths {
+            recent.insert(path.to_string(), RecentChange { at: now, local });
+        }
+    }
+
+    fn remember_rename(&self, tracker: usize) {
+        let mut renamed = self.renamed.lock().unwrap_or_else(|e| e.into_inner());
+        let now = Instant::now();
+        renamed.retain(|_, at| now.duration_since(*at) < SUPPRESS_WINDOW);
+        renamed.insert(tracker, now);
+    }
+
+    // 监听器收到的事件是否重复：服务器自身刚通知过同一路径、同一路径的连续修改，
+    // 或者已经包含在重命名事件中的单独移出和移入
+    fn is_duplicate(&self, change: &WatchedChange) -> bool {
+        if let Some(tracker) = change.half_rename {
+            let renamed = self.renamed.lock().unwrap_or_else(|e| e.into_inner());
+            if renamed.contains_key(&tracker) {
+                return true;
+            }
+        }
+
+        let recent = self.recent.lock().unwrap_or_else(|e| e.into_inner());
+        recent.get(&change.path).is_some_and(|recent| {
+            recent.at.elapsed() < SUPPRESS_WINDOW && (recent.local || change.kind == ChangeKind::Modified)
+        })
+    }
+
+    fn publish(&self, kind: ChangeKind, path: String, from: Option<String>, user: Option<&str>) {
+        if !self.enabled {
+            return;
+        }
+
+        let mut paths = vec![path.as_str()];
+        paths.extend(from.as_deref());
+        self.remember(&paths, user.is_some());
+
+        // 没有订阅者时发送失败，忽略即可
+        let _ = self.tx.send(ChangeEvent {
+            kind,
+            path,
+            from,
+            user: user.map(str::to_string),
+            time: format_system_time(std::time::SystemTime::now()),
+        });
+    }
+}
+
+// 服务器自身修改文件后调用：更新索引、清理缩略图并通知订阅者
+pub async fn file_changed(state: &AppState, kind: ChangeKind, rel_path: &str, user: &str) {
+    state.index.refresh(rel_path).await;
+    state.usage.invalidate(rel_path);
+    state.checksums.invalidate(rel_path);
+    if kind == ChangeKind::Deleted {
+        state.thumbnails.invalidate(rel_path).await;
+    }
+    state.events.publish(kind, rel_path.to_string(), None, Some(user));
+}
+
+pub async fn file_renamed(state: &AppState, from: &str, to: &str, user: &str) {
+    state.index.refresh(from).await;
+    state.index.refresh(to).await;
+    state.usage.invalidate(from);
+    state.usage.invalidate(to);
+    state.checksums.invalidate(from);
+    state.thumbnails.invalidate(from).await;
+    state
+        .events
+        .publish(ChangeKind::Renamed, to.to_string(), Some(from.to_string()), Some(user));
+}
+
+// 监听base_dir的变化，供元数据索引和变化通知使用
+pub fn spawn_watcher(state: Arc<AppState>) {
+    if !state.events.watch_enabled() && !state.index.wants_watch() {
+        return;
+    }
+
+    let base_dir = match std::fs::canonicalize(&state.base_dir) {
+        Ok(base_dir) => base_dir,
+        Err(e) => {
+            tracing::warn!("Failed to start filesystem watcher: {}", e);
+            return;
+        }
+    };
+
+    let (tx, mut rx) = mpsc::unbounded_channel::<WatchedChange>();
+    let handler_state = state.clone();
+    let handler_base = base_dir.clone();
+    let watcher = notify::recommended_watcher(move |res: notify::Result<notify::Event>| match res {
+        Ok(event) => {
+            // 索引只需要知道哪些路径变了；事件队列溢出时重新同步整个根目录
+            if event.need_rescan() {
+                handler_state.index.path_changed(handler_base.clone());
+            }
+            for path in &event.paths {
+                handler_state.index.path_changed(path.clone());
+            }
+
+            if handler_state.events.watch_enabled() {
+                if let Some((kind, path, from)) = classify_event(&handler_base, &event) {
+                    // inotify在成对的重命名事件之前还会分别发出移出和移入事件，三者的标识相同
+                    let half_rename = match event.kind {
+                        EventKind::Modify(ModifyKind::Name(RenameMode::Both)) => {
+                            if let Some(tracker) = event.tracker() {
+                                handler_state.events.remember_rename(tracker);
+                            }
+                            None
+                        }
+                        EventKind::Modify(ModifyKind::Name(RenameMode::From | RenameMode::To)) => event.tracker(),
+                        _ => None,
+                    };
+                    let _ = tx.send(WatchedChange {
+                        received: Instant::now(),
+                        kind,
+                        path,
+                        from,
+                        half_rename,
+                    });
+                }
+            }
+        }
+        Err(e) => tracing::warn!("Filesystem watcher error: {}", e),
+    });
+
+    let mut watcher = match watcher {
+        Ok(watcher) => watcher,
+        Err(e) => {
+            tracing::warn!("Failed to start filesystem watcher: {}", e);
+            return;
+        }
+    };
+    if let Err(e) = watcher.watch(&base_dir, RecursiveMode::Recursive) {
+        tracing::warn!("Failed to watch {:?}: {}", base_dir, e);
+        return;
+    }
+    *state.events.watcher.lock().unwrap_or_else(|e| e.into_inner()) = Some(watcher);
+
+    tokio::spawn(async move {
+        while let Some(change) = rx.recv().await {
+            tokio::time::sleep_until((change.received + WATCH_DELAY).into()).await;
+            state.usage.invalidate(&change.path);
+            state.checksums.invalidate(&change.path);
+            if let Some(from) = &change.from {
+                state.usage.invalidate(from);
+                state.checksums.invalidate(from);
+            }
+            match (change.kind, &change.from) {
+                (ChangeKind::Deleted, _) => state.thumbnails.invalidate(&change.path).await,
+                (ChangeKind::Renamed, Some(from)) => state.thumbnails.invalidate(from).await,
+                _ => {}
+            }
+            if state.events.is_duplicate(&change) {
+                continue;
+            }
+            state.events.publish(change.kind, change.path, change.from, None);
+        }
+    });
+}
+
+// 把监听器事件转换为变化通知，忽略回收站、临时文件和访问事件
+fn classify_event(base_dir: &Path, event: &notify::Event) -> Option<(ChangeKind, String, Option<String>)> {
+    let rel = |path: &PathBuf| -> Option<String> {
+        if !is_safe_path(base_dir, path) || is_temp_path(path) {
+            return None;
+        }
+        let rel = path.strip_prefix(base_dir).ok()?.to_string_lossy().replace('\\', "/");
+        (!rel.is_empty()).then_some(rel)
+    };
+    let first = || event.paths.first().and_then(rel);
+
+    match event.kind {
+        EventKind::Create(_) => first().map(|p| (ChangeKind::Created, p, None)),
+        EventKind::Remove(_) => first().map(|p| (ChangeKind::Deleted, p, None)),
+        EventKind::Modify(ModifyKind::Name(RenameMode::Both)) if event.paths.len() == 2 => {
+            match (rel(&event.paths[0]), rel(&event.paths[1])) {
+                (Some(from), Some(to)) => Some((ChangeKind::Renamed, to, Some(from))),
+                // 临时文件重命名为目标文件，或从根目录外移入
+                (None, Some(to)) => Some((ChangeKind::Created, to, None)),
+                // 移入回收站或移出根目录
+                (Some(from), None) => Some((ChangeKind::Deleted, from, None)),
+                (None, None) => None,
+            }
+        }
+        EventKind::Modify(ModifyKind::Name(RenameMode::From)) => first().map(|p| (ChangeKind::Deleted, p, None)),
+        EventKind::Modify(ModifyKind::Name(RenameMode::To)) => first().map(|p| (ChangeKind::Created, p, None)),
+        EventKind::Modify(ModifyKind::Name(_)) => {
+            let path = event.paths.first()?;
+            let kind = if path.exists() { ChangeKind::Created } else { ChangeKind::Deleted };
+            rel(path).map(|p| (kind, p, None))
+        }
+        EventKind::Modify(_) => first().map(|p| (ChangeKind::Modified, p, None)),
+        _ => None,
+    }
+}
+
+// 路径是否属于订阅的目录：目录本身、直接子项，递归订阅时包括所有子路径
+fn in_scope(dir: &str, path: &str, recursive: bool) -> bool {
+    if path == dir {
+        return true;
+    }
+    if recursive {
+        return dir.is_empty() || path.starts_with(&format!("{}/", dir));
+    }
+    path.rsplit_once('/').map(|(parent, _)| parent).unwrap_or("") == dir
+}
+
+// 订阅者能看到的通知；重命名只有一端可见时，移出改为删除原路径、移入改为新建，
+// 不暴露另一端的路径
+fn visible_event(
+    state: &AppState,
+    user: &AuthUser,
+    dir: &str,
+    recursive: bool,
+    event: ChangeEvent,
+) -> Option<ChangeEvent> {
+    let visible = |path: &str| in_scope(dir, path, recursive) && state.acl.allows(user, path, Permission::Read);
+    let to_visible = visible(&event.path);
+    match event.from.as_deref().map(visible) {
+        None | Some(true) if to_visible => Some(event),
+        Some(true) => Some(ChangeEvent {
+            kind: ChangeKind::Deleted,
+            path: event.from.unwrap_or_default(),
+            from: None,
+            ..event
+        }),
+        Some(false) if to_visible => Some(ChangeEvent {
+            kind: ChangeKind::Created,
+            from: None,
+            ..event
+        }),
+        _ => None,
+    }
+}
+
+// 订阅目录的变化通知（Server-Sent Events），事件名为变化类型，数据为ChangeEvent
+pub async fn subscribe(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Query(query): Query<EventsQuery>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, AppError> {
+    if !state.events.enabled {
+        return Err(AppError::NotFound("Change notifications are disabled".to_string()));
+    }
+
+    let safe_path = sanitize_path(&query.path);
+    let full_path = if safe_path.is_empty() {
+        state.base_dir.clone()
+    } else {
+        state.base_dir.join(&safe_path)
+    };
+
+    if !is_safe_path(&state.base_dir, &full_path) {
+        return Err(AppError::PermissionDenied("Access denied".to_string()));
+    }
+
+    state.acl.check(&user, &safe_path, Permission::Read)?;
+
+    if !full_path.is_dir() {
+        return Err(AppError::NotFound("Directory not found".to_string()));
+    }
+
+    tracing::debug!("{} subscribed to changes in /{}", user, safe_path);
+
+    let rx = state.events.tx.subscribe();
+    let stream = futures_util::stream::unfold(
+        (rx, state, user, safe_path, query.recursive),
+        |(mut rx, state, user, dir, recursive)| async move {
+            loop {
+                let event = match rx.recv().await {
+                    Ok(change) => {
+                        let Some(change) = visible_event(&state, &user, &dir, recursive, change) else {
+                            continue;
+                        };
+                        match Event::default().event(change.kind.as_str()).json_data(&change) {
+                            Ok(event) => event,
+                            Err(e) => {
+                                tracing::error!("Failed to serialize change event: {}", e);
+                                continue;
+                            }
+                        }
+                    }
+                    // 客户端处理太慢丢失了部分通知，让它重新加载目录
+                    Err(broadcast::error::RecvError::Lagged(missed)) => {
+                        Event::default().event("resync").data(missed.to_string())
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return None,
+                };
+                return Some((Ok(event), (rx, state, user, dir, recursive)));
+            }
+        },
+    );
+
+    Ok(Sse::new(stream).keep_alive(KeepAlive::new().interval(KEEP_ALIVE_INTERVAL)))
+}
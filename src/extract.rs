@@ -0,0 +1,412 @@
+use axum::{extract::State, Json};
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+
+use crate::{
+    acl::Permission,
+    auth::AuthUser,
+    errors::AppError,
+    events,
+    models::*,
+    utils::*,
+    AppState,
+};
+
+// 单个归档最多解压的条目数和解压后的总字节数，防止解压炸弹耗尽磁盘
+const MAX_ENTRIES: usize = 100_000;
+const MAX_TOTAL_SIZE: u64 = 16 * 1024 * 1024 * 1024;
+
+#[derive(Debug, Clone, Copy)]
+enum ArchiveKind {
+    Zip,
+    Tar,
+    TarGz,
+    TarZst,
+}
+
+impl ArchiveKind {
+    // 按文件扩展名识别归档格式
+    fn detect(path: &Path) -> Option<Self> {
+        let name = path.file_name()?.to_str()?.to_lowercase();
+        if name.ends_with(".zip") {
+            Some(ArchiveKind::Zip)
+        } else if name.ends_with(".tar.gz") || name.ends_with(".tgz") {
+            Some(ArchiveKind::TarGz)
+        } else if name.ends_with(".tar.zst") || name.ends_with(".tzst") {
+            Some(ArchiveKind::TarZst)
+        } else if name.ends_with(".tar") {
+            Some(ArchiveKind::Tar)
+        } else {
+            None
+        }
+    }
+}
+
+enum EntryKind {
+    Dir,
+    File,
+    // 符号链接、硬链接、设备文件等，一律不解压
+    Unsupported(&'static str),
+}
+
+// 把归档条目名转换为安全的相对路径：拒绝绝对路径和任何 ".." 组件（zip-slip）
+fn entry_relative_path(name: &str) -> Option<PathBuf> {
+    let name = name.replace('\\', "/");
+    let mut relative = PathBuf::new();
+    for component in Path::new(&name).components() {
+        match component {
+            Component::Normal(part) => relative.push(part),
+            Component::CurDir => {}
+            Component::ParentDir | Component::RootDir | Component::Prefix(_) => return None,
+        }
+    }
+    if relative.as_os_str().is_empty() {
+        None
+    } else {
+        Some(relative)
+    }
+}
+
+struct ExtractJob {
+    state: Arc<AppState>,
+    user: AuthUser,
+    dest_dir: PathBuf,
+    dest_rel: String,
+    canonical_base: PathBuf,
+    policy: ConflictPolicy,
+    extracted: Vec<String>,
+    skipped: Vec<String>,
+    failed: Vec<(String, String)>,
+    entries: usize,
+    written: u64,
+}
+
+impl ExtractJob {
+    // 创建目录后用规范化路径确认没有经由已存在的符号链接逃出根目录
+    fn ensure_dir(&self, dir: &Path) -> io::Result<()> {
+        std::fs::create_dir_all(dir)?;
+        let canonical = std::fs::canonicalize(dir)?;
+        if !canonical.starts_with(&self.canonical_base) {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "Path escapes base directory through a symlink",
+            ));
+        }
+        Ok(())
+    }
+
+    // 超过条目数上限时中止整个解压
+    fn count_entry(&mut self) -> io::Result<()> {
+        self.entries += 1;
+        if self.entries > MAX_ENTRIES {
+            return Err(io::Error::other(format!("Archive has more than {} entries", MAX_ENTRIES)));
+        }
+        Ok(())
+    }
+
+    fn extract_entry(&mut self, name: &str, kind: EntryKind, reader: &mut dyn Read) -> io::Result<()> {
+        let relative = match entry_relative_path(name) {
+            Some(relative) => relative,
+            None => {
+                self.failed.push((name.to_string(), "Unsafe entry path".to_string()));
+                return Ok(());
+            }
+        };
+
+        let target = self.dest_dir.join(&relative);
+        if !is_safe_path(&self.state.base_dir, &target) {
+            self.failed.push((name.to_string(), "Access denied".to_string()));
+            return Ok(());
+        }
+
+        let rel_path = join_relative_path(
+            &self.dest_rel,
+            &relative.to_string_lossy().replace('\\', "/"),
+        );
+        if !self.state.acl.allows(&self.user, &rel_path, Permission::Write) {
+            self.failed.push((name.to_string(), "Permission denied".to_string()));
+            return Ok(());
+        }
+
+        let result = match kind {
+            EntryKind::Unsupported(kind) => {
+                self.failed
+                    .push((name.to_string(), format!("Unsupported entry type: {}", kind)));
+                return Ok(());
+            }
+            EntryKind::Dir => self.ensure_dir(&target).map(|_| None),
+            EntryKind::File => self.extract_file(&target, reader),
+        };
+
+        match result {
+            Ok(Some(path)) => {
+                let rel = self.rel_path(&path);
+                self.extracted.push(rel);
+            }
+            Ok(None) => {}
+            // 超出总大小上限时中止整个解压，其余错误只影响当前条目
+            Err(e) if e.kind() == io::ErrorKind::FileTooLarge => return Err(e),
+            Err(e) => self.failed.push((name.to_string(), e.to_string())),
+        }
+        Ok(())
+    }
+
+    // 写入单个文件，返回实际写入的路径；按冲突策略跳过时返回None并记录
+    fn extract_file(&mut self, target: &Path, reader: &mut dyn Read) -> io::Result<Option<PathBuf>> {
+        if let Some(parent) = target.parent() {
+            self.ensure_dir(parent)?;
+        }
+
+        let mut target = target.to_path_buf();
+        let mut overwrite = false;
+        if let Ok(existing) = std::fs::symlink_metadata(&target) {
+            match self.policy {
+                ConflictPolicy::Skip => {
+                    let rel = self.rel_path(&target);
+                    self.skipped.push(rel);
+                    return Ok(None);
+                }
+                ConflictPolicy::Overwrite => {
+                    if existing.is_dir() {
+                        return Err(io::Error::other("A directory with the same name exists"));
+                    }
+                    // 覆盖等同于删除原文件，与移动、恢复时的覆盖一样需要删除权限
+                    if !self.state.acl.allows(&self.user, &self.rel_path(&target), Permission::Delete) {
+                        return Err(io::Error::new(io::ErrorKind::PermissionDenied, "Permission denied"));
+                    }
+                    overwrite = true;
+                }
+                ConflictPolicy::Rename => target = unique_path(&target),
+            }
+        }
+
+        // 先写临时文件再重命名，覆盖已有的符号链接本身而不是其指向的文件
+        let guard = TempFileGuard::new(temp_path_for(&target));
+        let mut file = File::create(guard.path())?;
+        // 按实际解压出的字节数计算，不信任归档头中声明的大小
+        let remaining = MAX_TOTAL_SIZE - self.written;
+        let copied = io::copy(&mut reader.take(remaining + 1), &mut file)?;
+        if copied > remaining {
+            return Err(io::Error::new(
+                io::ErrorKind::FileTooLarge,
+                format!("Archive expands to more than {} bytes", MAX_TOTAL_SIZE),
+            ));
+        }
+        self.written += copied;
+        file.sync_all()?;
+        drop(file);
+
+        if overwrite {
+            let rel_path = self.rel_path(&target);
+            tokio::runtime::Handle::current().block_on(self.state.versions.preserve(
+                &target,
+                &rel_path,
+                &self.user.username,
+            ))?;
+        }
+        guard.commit_blocking(&target)?;
+
+        Ok(Some(target))
+    }
+
+    fn rel_path(&self, path: &Path) -> String {
+        path.strip_prefix(&self.state.base_dir)
+            .unwrap_or(path)
+            .to_string_lossy()
+            .replace('\\', "/")
+    }
+
+    fn extract_zip(&mut self, file: File) -> io::Result<()> {
+        let mut archive = zip::ZipArchive::new(BufReader::new(file)).map_err(io::Error::other)?;
+        if archive.len() > MAX_ENTRIES {
+            return Err(io::Error::other(format!("Archive has more than {} entries", MAX_ENTRIES)));
+        }
+        for index in 0..archive.len() {
+            self.count_entry()?;
+            let mut entry = match archive.by_index(index) {
+                Ok(entry) => entry,
+                Err(e) => {
+                    self.failed.push((format!("entry #{}", index), e.to_string()));
+                    continue;
+                }
+            };
+            let name = entry.name().to_string();
+            let kind = if entry.is_dir() {
+                EntryKind::Dir
+            } else if entry.is_symlink() {
+                EntryKind::Unsupported("symlink")
+            } else {
+                EntryKind::File
+            };
+            self.extract_entry(&name, kind, &mut entry)?;
+        }
+        Ok(())
+    }
+
+    fn extract_tar<R: Read>(&mut self, reader: R) -> io::Result<()> {
+        let mut archive = tar::Archive::new(reader);
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+            let name = String::from_utf8_lossy(&entry.path_bytes()).to_string();
+            let kind = match entry.header().entry_type() {
+                tar::EntryType::Directory => EntryKind::Dir,
+                tar::EntryType::Regular | tar::EntryType::Continuous => EntryKind::File,
+                tar::EntryType::Symlink => EntryKind::Unsupported("symlink"),
+                tar::EntryType::Link => EntryKind::Unsupported("hard link"),
+                tar::EntryType::XGlobalHeader | tar::EntryType::XHeader => continue,
+                _ => EntryKind::Unsupported("special file"),
+            };
+            self.count_entry()?;
+            self.extract_entry(&name, kind, &mut entry)?;
+        }
+        Ok(())
+    }
+
+    fn run(&mut self, archive_path: &Path, kind: ArchiveKind) -> io::Result<()> {
+        let file = File::open(archive_path)?;
+        match kind {
+            ArchiveKind::Zip => self.extract_zip(file),
+            ArchiveKind::Tar => self.extract_tar(BufReader::new(file)),
+            ArchiveKind::TarGz => self.extract_tar(flate2::read::GzDecoder::new(BufReader::new(file))),
+            ArchiveKind::TarZst => self.extract_tar(zstd::stream::read::Decoder::new(file)?),
+        }
+    }
+}
+
+// 解压服务端归档文件到指定目录
+pub async fn extract_archive(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Json(req): Json<ExtractRequest>,
+) -> Result<Json<ApiResponse<serde_json::Value>>, AppError> {
+    let safe_path = sanitize_path(&req.path);
+    let archive_path = state.base_dir.join(&safe_path);
+
+    if !is_safe_path(&state.base_dir, &archive_path) {
+        return Err(AppError::PermissionDenied("Access denied".to_string()));
+    }
+
+    state.acl.check(&user, &safe_path, Permission::Read)?;
+
+    if !archive_path.is_file() {
+        return Err(AppError::NotFound("Archive not found".to_string()));
+    }
+
+    let kind = ArchiveKind::detect(&archive_path).ok_or_else(|| {
+        AppError::InvalidRequest("Unsupported archive format (zip, tar, tar.gz, tar.zst)".to_string())
+    })?;
+
+    let dest_safe = sanitize_path(&req.destination);
+    let dest_dir = state.base_dir.join(&dest_safe);
+
+    if !is_safe_path(&state.base_dir, &dest_dir) {
+        return Err(AppError::PermissionDenied("Access denied".to_string()));
+    }
+
+    state.acl.check(&user, &dest_safe, Permission::Write)?;
+
+    let canonical_base = tokio::fs::canonicalize(&state.base_dir).await?;
+    let mut job = ExtractJob {
+        state: state.clone(),
+        user: user.clone(),
+        dest_dir,
+        dest_rel: dest_safe,
+        canonical_base,
+        policy: req.on_conflict,
+        extracted: Vec::new(),
+        skipped: Vec::new(),
+        failed: Vec::new(),
+        entries: 0,
+        written: 0,
+    };
+
+    let job = tokio::task::spawn_blocking(move || {
+        let dest_dir = job.dest_dir.clone();
+        let result = job
+            .ensure_dir(&dest_dir)
+            .and_then(|_| job.run(&archive_path, kind));
+        (job, result)
+    })
+    .await
+    .map_err(|e| AppError::IoError(e.to_string()))?;
+
+    let (mut job, result) = job;
+    if let Err(e) = result {
+        // 归档损坏或超出上限时保留已解压的条目，并把错误记录在failed中
+        tracing::error!("Failed to extract {}: {}", safe_path, e);
+        if job.extracted.is_empty() && job.skipped.is_empty() && job.failed.is_empty() {
+            return Err(AppError::InvalidRequest(format!("Failed to read archive: {}", e)));
+        }
+        job.failed.push((safe_path.clone(), e.to_string()));
+    }
+
+    // 按解压目标下的顶层条目更新索引并通知订阅者，每棵子树只同步一次
+    let top_level: std::collections::BTreeSet<String> = job
+        .extracted
+        .iter()
+        .filter_map(|rel| {
+            let relative = match job.dest_rel.as_str() {
+                "" => rel.as_str(),
+                dest => rel.strip_prefix(dest)?.strip_prefix('/')?,
+            };
+            let first = relative.split('/').next()?;
+            Some(join_relative_path(&job.dest_rel, first))
+        })
+        .collect();
+    for rel in &top_level {
+        events::file_changed(&state, ChangeKind::Created, rel, &user.username).await;
+    }
+
+    tracing::info!(
+        "{} extracted {} entries from {} to {}",
+        user,
+        job.extracted.len(),
+        safe_path,
+        job.dest_dir.display()
+    );
+    Ok(Json(ApiResponse::success(serde_json::json!({
+        "extracted": job.extracted,
+        "skipped": job.skipped,
+        "failed": job.failed
+    }))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn entry_paths_stay_relative() {
+        assert_eq!(entry_relative_path("a/b.txt"), Some(PathBuf::from("a/b.txt")));
+        assert_eq!(entry_relative_path("./a/./b/"), Some(PathBuf::from("a/b")));
+        assert_eq!(entry_relative_path("a\\b.txt"), Some(PathBuf::from("a/b.txt")));
+    }
+
+    #[test]
+    fn zip_slip_entries_are_rejected() {
+        for name in [
+            "../evil",
+            "a/../../evil",
+            "a/../b",
+            "/etc/passwd",
+            "\\etc\\passwd",
+            "..\\..\\evil",
+            "",
+            ".",
+            "./",
+        ] {
+            assert_eq!(entry_relative_path(name), None, "{:?}", name);
+        }
+    }
+
+    #[test]
+    fn archive_kind_by_extension() {
+        assert!(matches!(ArchiveKind::detect(Path::new("a/B.ZIP")), Some(ArchiveKind::Zip)));
+        assert!(matches!(ArchiveKind::detect(Path::new("x.tgz")), Some(ArchiveKind::TarGz)));
+        assert!(matches!(ArchiveKind::detect(Path::new("x.tar.zst")), Some(ArchiveKind::TarZst)));
+        assert!(matches!(ArchiveKind::detect(Path::new("x.tar")), Some(ArchiveKind::Tar)));
+        assert!(ArchiveKind::detect(Path::new("x.rar")).is_none());
+    }
+}
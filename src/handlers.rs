@@ -1,42 +1,54 @@
 use axum::{
-    extract::{Path, Multipart, State},
+    extract::{Path, Multipart, Query, State, multipart::Field},
     http::{StatusCode, header},
     response::{IntoResponse, Response},
     Json,
 };
 use std::sync::Arc;
 use tokio::fs;
-use tokio::io::AsyncReadExt;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
 
 use crate::{
+    acl::Permission,
+    auth::AuthUser,
+    checksum,
+    events,
+    listing,
     models::*,
     utils::*,
     errors::AppError,
     AppState,
 };
 
-use tracing;
-
 // 列表文件（根目录）
 pub async fn list_files_root(
     State(state): State<Arc<AppState>>,
-) -> Result<Json<ApiResponse<FileListResponse>>, AppError> {
-    list_files_impl(state, "").await
+    user: AuthUser,
+    Query(query): Query<ListQuery>,
+    headers: axum::http::HeaderMap,
+) -> Result<Response, AppError> {
+    list_files_impl(state, user, "", &query, &headers).await
 }
 
 // 列表文件（带路径）
 pub async fn list_files(
     State(state): State<Arc<AppState>>,
+    user: AuthUser,
     Path(path): Path<String>,
-) -> Result<Json<ApiResponse<FileListResponse>>, AppError> {
-    list_files_impl(state, &path).await
+    Query(query): Query<ListQuery>,
+    headers: axum::http::HeaderMap,
+) -> Result<Response, AppError> {
+    list_files_impl(state, user, &path, &query, &headers).await
 }
 
 // 列表文件实现
 async fn list_files_impl(
     state: Arc<AppState>,
+    user: AuthUser,
     path: &str,
-) -> Result<Json<ApiResponse<FileListResponse>>, AppError> {
+    query: &ListQuery,
+    headers: &axum::http::HeaderMap,
+) -> Result<Response, AppError> {
     let safe_path = sanitize_path(path);
     
     let full_path = if safe_path.is_empty() {
@@ -49,6 +61,8 @@ async fn list_files_impl(
         return Err(AppError::PermissionDenied("Access denied".to_string()));
     }
 
+    state.acl.check(&user, &safe_path, Permission::Read)?;
+
     if !full_path.exists() {
         return Err(AppError::NotFound("Path not found".to_string()));
     }
@@ -57,34 +71,50 @@ async fn list_files_impl(
         return Err(AppError::InvalidPath("Not a directory".to_string()));
     }
 
+    if listing::wants_ndjson(headers) {
+        return listing::stream(state, user, full_path, safe_path, query);
+    }
+    if query.recursive {
+        return Err(AppError::InvalidRequest(
+            "Recursive listing requires Accept: application/x-ndjson".to_string(),
+        ));
+    }
+
+    // 启用索引时直接从索引读取，避免逐项读取元数据
+    let entries = match state.index.list_dir(&safe_path).await {
+        Some(indexed) => indexed.iter().map(|e| e.to_file_entry()).collect(),
+        None => list_directory(&full_path, &state.base_dir).await?,
+    };
+
+    Ok(Json(ApiResponse::success(listing::paginate(entries, query)?)).into_response())
+}
+
+// 读取目录内容（未排序），条目路径相对于root计算（分享链接以分享目录为根）
+pub(crate) async fn list_directory(
+    full_path: &std::path::Path,
+    root: &std::path::Path,
+) -> Result<Vec<FileEntry>, AppError> {
     let mut entries = Vec::new();
-    let mut read_dir = fs::read_dir(&full_path).await?;
+    let mut read_dir = fs::read_dir(full_path).await?;
 
     while let Some(entry) = read_dir.next_entry().await? {
         let entry_path = entry.path();
-        if let Ok(file_entry) = create_file_entry(&entry_path, &state.base_dir).await {
+        // 跳过回收站目录
+        if !is_safe_path(root, &entry_path) {
+            continue;
+        }
+        if let Ok(file_entry) = create_file_entry(&entry_path, root).await {
             entries.push(file_entry);
         }
     }
 
-    // 排序：文件夹在前，按名称排序
-    entries.sort_by(|a, b| {
-        if a.is_dir != b.is_dir {
-            b.is_dir.cmp(&a.is_dir)
-        } else {
-            a.name.cmp(&b.name)
-        }
-    });
-
-    let total = entries.len();
-    let response = FileListResponse { items: entries, total };
-    
-    Ok(Json(ApiResponse::success(response)))
+    Ok(entries)
 }
 
 // 文件预览
 pub async fn preview_file(
     State(state): State<Arc<AppState>>,
+    user: AuthUser,
     Path(path): Path<String>,
 ) -> Result<impl IntoResponse, AppError> {
     let safe_path = sanitize_path(&path);
@@ -94,6 +124,8 @@ pub async fn preview_file(
         return Err(AppError::PermissionDenied("Access denied".to_string()));
     }
 
+    state.acl.check(&user, &safe_path, Permission::Read)?;
+
     if !full_path.exists() {
         return Err(AppError::NotFound("File not found".to_string()));
     }
@@ -109,25 +141,29 @@ pub async fn preview_file(
 // 文件上传（根目录）
 pub async fn upload_file_root(
     State(state): State<Arc<AppState>>,
+    user: AuthUser,
     multipart: Multipart,
 ) -> Result<Json<ApiResponse<serde_json::Value>>, AppError> {
-    upload_file_impl(state, "", multipart).await
+    upload_file_impl(state, &user, "", multipart, true).await
 }
 
 // 文件上传（带路径）
 pub async fn upload_file(
     State(state): State<Arc<AppState>>,
+    user: AuthUser,
     Path(path): Path<String>,
     multipart: Multipart,
 ) -> Result<Json<ApiResponse<serde_json::Value>>, AppError> {
-    upload_file_impl(state, &path, multipart).await
+    upload_file_impl(state, &user, &path, multipart, true).await
 }
 
-// 文件上传实现
-async fn upload_file_impl(
+// 文件上传实现；overwrite为false时同名文件不会被覆盖，而是改用带序号的新名称
+pub(crate) async fn upload_file_impl(
     state: Arc<AppState>,
+    user: &AuthUser,
     path: &str,
     mut multipart: Multipart,
+    overwrite: bool,
 ) -> Result<Json<ApiResponse<serde_json::Value>>, AppError> {
     let safe_path = sanitize_path(path);
     
@@ -141,10 +177,15 @@ async fn upload_file_impl(
         return Err(AppError::PermissionDenied("Access denied".to_string()));
     }
 
+    state.acl.check(user, &safe_path, Permission::Write)?;
+
     fs::create_dir_all(&target_dir).await?;
 
     let mut uploaded_files = Vec::new();
-    let mut error_count = 0;
+    let mut checksums = serde_json::Map::new();
+    let mut failed = Vec::new();
+    // 名为checksum的表单字段指定紧随其后的文件的预期摘要
+    let mut pending_checksum: Option<String> = None;
 
     while let Some(field) = multipart.next_field().await.map_err(|e| {
         AppError::InvalidRequest(format!("Multipart error: {}", e))
@@ -152,44 +193,139 @@ async fn upload_file_impl(
         // 跳过没有文件名的字段
         let file_name = match field.file_name() {
             Some(name) => name.to_string(),
-            None => continue,
+            None => {
+                if field.name() == Some("checksum") {
+                    let value = field.text().await.map_err(|e| {
+                        AppError::InvalidRequest(format!("Multipart error: {}", e))
+                    })?;
+                    pending_checksum = Some(value);
+                }
+                continue;
+            }
         };
+        let field_checksum = pending_checksum.take();
 
-        match field.bytes().await {
-            Ok(data) => {
-                let file_path = target_dir.join(&file_name);
-                match fs::write(&file_path, data).await {
-                    Ok(_) => {
-                        uploaded_files.push(file_name);
-                    }
-                    Err(e) => {
-                        tracing::error!("Failed to write file {}: {}", file_name, e);
-                        error_count += 1;
-                    }
-                }
+        // 文件名来自客户端，不能包含路径分隔符或指向上级目录
+        if !is_valid_file_name(&file_name) || !is_safe_path(&state.base_dir, &target_dir.join(&file_name)) {
+            tracing::error!("Failed to upload file {}: invalid file name", file_name);
+            failed.push(serde_json::json!({ "name": file_name, "error": "Invalid file name" }));
+            continue;
+        }
+
+        let file_name = if overwrite {
+            file_name
+        } else {
+            unique_name(&file_name, false, |candidate| {
+                std::fs::symlink_metadata(target_dir.join(candidate)).is_ok()
+            })
+        };
+        let file_path = target_dir.join(&file_name);
+        let rel_path = join_relative_path(&safe_path, &file_name);
+
+        if let Err(e) = state.acl.check(user, &rel_path, Permission::Write) {
+            tracing::error!("Failed to upload file {}: {}", file_name, e);
+            failed.push(serde_json::json!({ "name": file_name, "error": e.to_string() }));
+            continue;
+        }
+
+        // 预期摘要可以来自表单字段，也可以来自该字段的Content-Digest头，同时提供时都要匹配
+        let header_checksum = field
+            .headers()
+            .get("content-digest")
+            .and_then(|v| v.to_str().ok())
+            .map(str::to_string);
+        let expected = [field_checksum, header_checksum]
+            .into_iter()
+            .flatten()
+            .map(|value| checksum::parse_expected_digests(&value))
+            .collect::<Result<Vec<_>, _>>()
+            .map(|lists| lists.into_iter().flatten().collect::<Vec<_>>());
+        let expected = match expected {
+            Ok(expected) => expected,
+            Err(e) => {
+                tracing::error!("Failed to upload file {}: {}", file_name, e);
+                failed.push(serde_json::json!({ "name": file_name, "error": e.to_string() }));
+                continue;
+            }
+        };
+
+        match save_field_streaming(&state, user, field, &file_path, &rel_path, overwrite, expected).await {
+            Ok((size, digests)) => {
+                tracing::info!("{} uploaded {} ({} bytes)", user, file_path.display(), size);
+                checksums.insert(file_name.clone(), serde_json::json!(digests));
+                uploaded_files.push(file_name);
             }
             Err(e) => {
-                tracing::error!("Failed to read field {}: {}", file_name, e);
-                error_count += 1;
+                tracing::error!("Failed to upload file {}: {}", file_name, e);
+                failed.push(serde_json::json!({ "name": file_name, "error": e.to_string() }));
             }
         }
     }
 
-    if uploaded_files.is_empty() && error_count > 0 {
-        return Err(AppError::InvalidRequest(format!(
-            "Failed to upload {} files",
-            error_count
-        )));
+    if uploaded_files.is_empty() && !failed.is_empty() {
+        // 只有一个文件时直接返回它的错误，便于脚本判断失败原因
+        return Err(AppError::InvalidRequest(match failed.as_slice() {
+            [only] => only["error"].as_str().unwrap_or_default().to_string(),
+            _ => format!("Failed to upload {} files", failed.len()),
+        }));
     }
 
-    Ok(Json(ApiResponse::success(serde_json::json!({
-        "uploaded": uploaded_files
-    }))))
+    let mut response = serde_json::json!({
+        "uploaded": uploaded_files,
+        "checksums": checksums,
+    });
+    if !failed.is_empty() {
+        response["failed"] = serde_json::Value::Array(failed);
+    }
+    Ok(Json(ApiResponse::success(response)))
+}
+
+// 将multipart字段逐块写入临时文件并同时计算摘要，摘要与预期不符时丢弃临时文件；
+// 完成后保存被覆盖文件的历史版本并原子重命名到目标位置；
+// overwrite为false时目标在此期间被创建也不会覆盖，而是返回错误
+async fn save_field_streaming(
+    state: &AppState,
+    user: &AuthUser,
+    mut field: Field<'_>,
+    file_path: &std::path::Path,
+    rel_path: &str,
+    overwrite: bool,
+    expected: Vec<(HashAlgorithm, String)>,
+) -> anyhow::Result<(u64, std::collections::BTreeMap<HashAlgorithm, String>)> {
+    let guard = TempFileGuard::new(temp_path_for(file_path));
+    let mut file = fs::File::create(guard.path()).await?;
+    let mut digests = checksum::UploadDigests::new(expected);
+    let mut written: u64 = 0;
+
+    while let Some(chunk) = field.chunk().await? {
+        file.write_all(&chunk).await?;
+        digests.update(&chunk);
+        written += chunk.len() as u64;
+    }
+
+    file.flush().await?;
+    file.sync_all().await?;
+    drop(file);
+    let digests = digests.finish().map_err(anyhow::Error::msg)?;
+
+    let existed = overwrite && file_path.exists();
+    if overwrite {
+        state.versions.preserve(file_path, rel_path, &user.username).await?;
+        guard.commit(file_path).await?;
+    } else {
+        guard.commit_new(file_path).await?;
+    }
+    events::file_changed(state, ChangeKind::written(existed), rel_path, &user.username).await;
+    if let Ok(metadata) = fs::metadata(file_path).await {
+        state.checksums.remember(rel_path, &metadata, &digests);
+    }
+    Ok((written, digests))
 }
 
 // 删除文件
 pub async fn delete_file(
     State(state): State<Arc<AppState>>,
+    user: AuthUser,
     Path(path): Path<String>,
 ) -> Result<impl IntoResponse, AppError> {
     let safe_path = sanitize_path(&path);
@@ -199,6 +335,8 @@ pub async fn delete_file(
         return Err(AppError::PermissionDenied("Access denied".to_string()));
     }
 
+    state.acl.check(&user, &safe_path, Permission::Delete)?;
+
     if !full_path.exists() {
         return Err(AppError::NotFound("File not found".to_string()));
     }
@@ -207,13 +345,16 @@ pub async fn delete_file(
         return Err(AppError::InvalidPath("Use delete-dir for directories".to_string()));
     }
 
-    fs::remove_file(&full_path).await?;
+    state.trash.remove(&full_path, &safe_path, &user).await?;
+    events::file_changed(&state, ChangeKind::Deleted, &safe_path, &user.username).await;
+    tracing::info!("{} deleted file {}", user, full_path.display());
     Ok(Json(ApiResponse::<()>::success(())))
 }
 
 // 删除文件夹
 pub async fn delete_directory(
     State(state): State<Arc<AppState>>,
+    user: AuthUser,
     Path(path): Path<String>,
 ) -> Result<impl IntoResponse, AppError> {
     let safe_path = sanitize_path(&path);
@@ -223,6 +364,8 @@ pub async fn delete_directory(
         return Err(AppError::PermissionDenied("Access denied".to_string()));
     }
 
+    state.acl.check_tree(&user, &safe_path, Permission::Delete)?;
+
     if !full_path.exists() {
         return Err(AppError::NotFound("Directory not found".to_string()));
     }
@@ -231,13 +374,16 @@ pub async fn delete_directory(
         return Err(AppError::InvalidPath("Not a directory".to_string()));
     }
 
-    fs::remove_dir_all(&full_path).await?;
+    state.trash.remove(&full_path, &safe_path, &user).await?;
+    events::file_changed(&state, ChangeKind::Deleted, &safe_path, &user.username).await;
+    tracing::info!("{} deleted directory {}", user, full_path.display());
     Ok(Json(ApiResponse::<()>::success(())))
 }
 
 // 创建文件夹
 pub async fn create_directory(
     State(state): State<Arc<AppState>>,
+    user: AuthUser,
     Json(req): Json<CreateDirRequest>,
 ) -> Result<impl IntoResponse, AppError> {
     let safe_path = sanitize_path(&req.path);
@@ -257,18 +403,25 @@ pub async fn create_directory(
         return Err(AppError::PermissionDenied("Access denied".to_string()));
     }
 
+    let new_rel = join_relative_path(&safe_path, &req.name);
+    state.acl.check(&user, &new_rel, Permission::Write)?;
+
     if new_dir.exists() {
         return Err(AppError::InvalidRequest("Directory already exists".to_string()));
     }
 
     fs::create_dir_all(&new_dir).await?;
+    events::file_changed(&state, ChangeKind::Created, &new_rel, &user.username).await;
+    tracing::info!("{} created directory {}", user, new_dir.display());
     Ok(Json(ApiResponse::<()>::success(())))
 }
 
 // 获取文件信息
 pub async fn get_file_info(
     State(state): State<Arc<AppState>>,
+    user: AuthUser,
     Path(path): Path<String>,
+    Query(query): Query<FileInfoQuery>,
 ) -> Result<impl IntoResponse, AppError> {
     let safe_path = sanitize_path(&path);
     let full_path = state.base_dir.join(&safe_path);
@@ -277,11 +430,22 @@ pub async fn get_file_info(
         return Err(AppError::PermissionDenied("Access denied".to_string()));
     }
 
+    state.acl.check(&user, &safe_path, Permission::Read)?;
+
     if !full_path.exists() {
         return Err(AppError::NotFound("File not found".to_string()));
     }
 
     let metadata = fs::metadata(&full_path).await?;
+
+    // 目录没有摘要，忽略checksum参数
+    let checksums = match query.checksum.as_deref() {
+        Some(algorithms) if !metadata.is_dir() => {
+            let algorithms = checksum::parse_algorithms(Some(algorithms))?;
+            Some(checksum::file_digests(&state, &full_path, &safe_path, &metadata, &algorithms, true).await?)
+        }
+        _ => None,
+    };
     let name = full_path
         .file_name()
         .and_then(|n| n.to_str())
@@ -306,6 +470,7 @@ pub async fn get_file_info(
         modified,
         created,
         mime_type,
+        checksums,
     };
 
     Ok(Json(ApiResponse::success(info)))
@@ -314,6 +479,7 @@ pub async fn get_file_info(
 // 批量删除
 pub async fn batch_delete(
     State(state): State<Arc<AppState>>,
+    user: AuthUser,
     Json(req): Json<BatchDeleteRequest>,
 ) -> Result<impl IntoResponse, AppError> {
     let mut deleted = Vec::new();
@@ -328,21 +494,26 @@ pub async fn batch_delete(
             continue;
         }
 
+        if let Err(e) = state.acl.check_tree(&user, &safe_path, Permission::Delete) {
+            failed.push((path, e.to_string()));
+            continue;
+        }
+
         if !full_path.exists() {
             failed.push((path, "Not found".to_string()));
             continue;
         }
 
-        match if full_path.is_dir() {
-            fs::remove_dir_all(&full_path).await
-        } else {
-            fs::remove_file(&full_path).await
-        } {
-            Ok(_) => deleted.push(path),
+        match state.trash.remove(&full_path, &safe_path, &user).await {
+            Ok(_) => {
+                events::file_changed(&state, ChangeKind::Deleted, &safe_path, &user.username).await;
+                deleted.push(path);
+            }
             Err(e) => failed.push((path, e.to_string())),
         }
     }
 
+    tracing::info!("{} batch deleted {} items", user, deleted.len());
     Ok(Json(ApiResponse::success(serde_json::json!({
         "deleted": deleted,
         "failed": failed
@@ -352,106 +523,360 @@ pub async fn batch_delete(
 // 批量移动
 pub async fn batch_move(
     State(state): State<Arc<AppState>>,
+    user: AuthUser,
     Json(req): Json<BatchMoveRequest>,
 ) -> Result<impl IntoResponse, AppError> {
-    let dest_safe = sanitize_path(&req.destination);
+    let (dest_safe, dest_dir) = prepare_destination(&state, &user, &req.destination).await?;
+
+    let mut items = Vec::new();
+    for path in req.paths {
+        items.push(transfer_entry(&state, &user, path, &dest_safe, req.on_conflict, true).await);
+    }
+
+    let moved = paths_with_outcome(&items, TransferOutcome::Moved);
+    tracing::info!("{} batch moved {} items to {}", user, moved.len(), dest_dir.display());
+    Ok(Json(ApiResponse::success(serde_json::json!({
+        "moved": moved,
+        "skipped": paths_with_outcome(&items, TransferOutcome::Skipped),
+        "failed": failed_items(&items),
+        "items": items
+    }))))
+}
+
+// 批量复制
+pub async fn batch_copy(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Json(req): Json<BatchCopyRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let (dest_safe, dest_dir) = prepare_destination(&state, &user, &req.destination).await?;
+
+    let mut items = Vec::new();
+    for path in req.paths {
+        items.push(transfer_entry(&state, &user, path, &dest_safe, req.on_conflict, false).await);
+    }
+
+    let copied = paths_with_outcome(&items, TransferOutcome::Copied);
+    tracing::info!("{} batch copied {} items to {}", user, copied.len(), dest_dir.display());
+    Ok(Json(ApiResponse::success(serde_json::json!({
+        "copied": copied,
+        "skipped": paths_with_outcome(&items, TransferOutcome::Skipped),
+        "failed": failed_items(&items),
+        "items": items
+    }))))
+}
+
+async fn prepare_destination(
+    state: &AppState,
+    user: &AuthUser,
+    destination: &str,
+) -> Result<(String, std::path::PathBuf), AppError> {
+    let dest_safe = sanitize_path(destination);
     let dest_dir = state.base_dir.join(&dest_safe);
 
     if !is_safe_path(&state.base_dir, &dest_dir) {
         return Err(AppError::PermissionDenied("Access denied".to_string()));
     }
 
+    state.acl.check(user, &dest_safe, Permission::Write)?;
+
     fs::create_dir_all(&dest_dir).await?;
+    Ok((dest_safe, dest_dir))
+}
 
-    let mut moved = Vec::new();
-    let mut failed = Vec::new();
+fn paths_with_outcome(items: &[TransferItem], outcome: TransferOutcome) -> Vec<&str> {
+    items
+        .iter()
+        .filter(|item| item.outcome == outcome)
+        .map(|item| item.path.as_str())
+        .collect()
+}
 
-    for path in req.paths {
-        let safe_path = sanitize_path(&path);
-        let full_path = state.base_dir.join(&safe_path);
+fn failed_items(items: &[TransferItem]) -> Vec<(&str, &str)> {
+    items
+        .iter()
+        .filter_map(|item| Some((item.path.as_str(), item.error.as_deref()?)))
+        .collect()
+}
 
-        if !is_safe_path(&state.base_dir, &full_path) {
-            failed.push((path, "Access denied".to_string()));
-            continue;
-        }
+// 把一个条目移动或复制到目标目录，按policy处理同名冲突
+async fn transfer_entry(
+    state: &AppState,
+    user: &AuthUser,
+    path: String,
+    dest_safe: &str,
+    policy: TransferConflict,
+    is_move: bool,
+) -> TransferItem {
+    let mut applied = None;
+    let result = transfer_entry_impl(state, user, &path, dest_safe, policy, is_move, &mut applied).await;
+    let (target, outcome, error) = match result {
+        Ok(Some(target)) if is_move => (Some(target), TransferOutcome::Moved, None),
+        Ok(Some(target)) => (Some(target), TransferOutcome::Copied, None),
+        Ok(None) => (None, TransferOutcome::Skipped, None),
+        Err(e) => (None, TransferOutcome::Failed, Some(e.to_string())),
+    };
+    TransferItem {
+        path,
+        target,
+        outcome,
+        policy: applied,
+        error,
+    }
+}
 
-        if !full_path.exists() {
-            failed.push((path, "Not found".to_string()));
-            continue;
+// 返回实际写入的相对路径，跳过时返回None；目标已存在时把采用的处理方式记入applied
+async fn transfer_entry_impl(
+    state: &AppState,
+    user: &AuthUser,
+    path: &str,
+    dest_safe: &str,
+    policy: TransferConflict,
+    is_move: bool,
+    applied: &mut Option<TransferConflict>,
+) -> Result<Option<String>, AppError> {
+    let safe_path = sanitize_path(path);
+    let full_path = state.base_dir.join(&safe_path);
+
+    if !is_safe_path(&state.base_dir, &full_path) {
+        return Err(AppError::PermissionDenied("Access denied".to_string()));
+    }
+    if safe_path.is_empty() {
+        return Err(AppError::InvalidPath("Cannot move or copy root directory".to_string()));
+    }
+
+    let source_permission = if is_move { Permission::Delete } else { Permission::Read };
+    state.acl.check_tree(user, &safe_path, source_permission)?;
+
+    let metadata = fs::metadata(&full_path)
+        .await
+        .map_err(|_| AppError::NotFound("Not found".to_string()))?;
+
+    let file_name = full_path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or("unknown")
+        .to_string();
+    let mut target = state.base_dir.join(dest_safe).join(&file_name);
+    let mut target_rel = join_relative_path(dest_safe, &file_name);
+
+    state.acl.check_tree(user, &target_rel, Permission::Write)?;
+
+    if metadata.is_dir() && target != full_path && target.starts_with(&full_path) {
+        return Err(AppError::InvalidPath("Cannot move or copy a directory into itself".to_string()));
+    }
+
+    let mut merge = false;
+    if let Ok(existing) = fs::symlink_metadata(&target).await {
+        *applied = Some(policy);
+        // 源和目标是同一个条目时只能跳过或另起名称
+        if target == full_path && matches!(policy, TransferConflict::Overwrite | TransferConflict::Merge) {
+            return Err(AppError::Conflict("Source and destination are the same".to_string()));
         }
 
-        let file_name = full_path
-            .file_name()
-            .and_then(|n| n.to_str())
-            .unwrap_or("unknown");
-        let new_path = dest_dir.join(file_name);
+        match policy {
+            TransferConflict::Fail => {
+                return Err(AppError::Conflict(format!("{} already exists", target_rel)));
+            }
+            TransferConflict::Skip => return Ok(None),
+            TransferConflict::Rename => {
+                target = unique_path(&target);
+                let new_name = target.file_name().and_then(|n| n.to_str()).unwrap_or("unknown");
+                target_rel = join_relative_path(dest_safe, new_name);
+                state.acl.check_tree(user, &target_rel, Permission::Write)?;
+            }
+            TransferConflict::Merge if existing.is_dir() && metadata.is_dir() => {
+                state.acl.check_tree(user, &target_rel, Permission::Delete)?;
+                merge = true;
+            }
+            TransferConflict::Overwrite | TransferConflict::Merge => {
+                state.acl.check_tree(user, &target_rel, Permission::Delete)?;
+                state.trash.remove(&target, &target_rel, user).await?;
+                events::file_changed(state, ChangeKind::Deleted, &target_rel, &user.username).await;
+            }
+        }
+    }
 
-        match fs::rename(&full_path, &new_path).await {
-            Ok(_) => moved.push(path),
-            Err(e) => failed.push((path, e.to_string())),
+    if merge {
+        merge_dir(state, user, full_path, target, target_rel.clone(), is_move).await?;
+        if is_move {
+            events::file_changed(state, ChangeKind::Deleted, &safe_path, &user.username).await;
+        }
+        events::file_changed(state, ChangeKind::Modified, &target_rel, &user.username).await;
+    } else {
+        transfer_path(&full_path, &target, metadata.is_dir(), is_move).await?;
+        if is_move {
+            events::file_renamed(state, &safe_path, &target_rel, &user.username).await;
+        } else {
+            events::file_changed(state, ChangeKind::Created, &target_rel, &user.username).await;
         }
     }
 
+    Ok(Some(target_rel))
+}
+
+async fn transfer_path(
+    src: &std::path::Path,
+    dst: &std::path::Path,
+    is_dir: bool,
+    is_move: bool,
+) -> std::io::Result<()> {
+    match (is_move, is_dir) {
+        (true, true) => move_dir(src, dst).await,
+        (true, false) => move_file(src, dst).await,
+        (false, true) => copy_dir_recursive(src.to_path_buf(), dst.to_path_buf()).await,
+        (false, false) => fs::copy(src, dst).await.map(|_| ()),
+    }
+}
+
+// 把src目录的内容合并进dst目录：子目录递归合并，同名文件覆盖（原文件移入回收站），移动时最后删除src
+fn merge_dir<'a>(
+    state: &'a AppState,
+    user: &'a AuthUser,
+    src: std::path::PathBuf,
+    dst: std::path::PathBuf,
+    dst_rel: String,
+    is_move: bool,
+) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<(), AppError>> + Send + 'a>> {
+    Box::pin(async move {
+        let mut entries = fs::read_dir(&src).await?;
+
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            let file_name = entry.file_name();
+            let new_path = dst.join(&file_name);
+            let new_rel = join_relative_path(&dst_rel, &file_name.to_string_lossy());
+            let is_dir = path.is_dir();
+
+            match fs::symlink_metadata(&new_path).await {
+                Ok(existing) if is_dir && existing.is_dir() => {
+                    merge_dir(state, user, path, new_path, new_rel, is_move).await?;
+                    continue;
+                }
+                Ok(_) => state.trash.remove(&new_path, &new_rel, user).await?,
+                Err(_) => {}
+            }
+            transfer_path(&path, &new_path, is_dir, is_move).await?;
+        }
+
+        if is_move {
+            fs::remove_dir(&src).await?;
+        }
+        Ok(())
+    })
+}
+
+// 重命名
+pub async fn rename(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Json(req): Json<RenameRequest>,
+) -> Result<impl IntoResponse, AppError> {
+    let new_rel = rename_entry(&state, &user, &req.path, &req.new_name, req.on_conflict).await?;
     Ok(Json(ApiResponse::success(serde_json::json!({
-        "moved": moved,
-        "failed": failed
+        "path": new_rel
     }))))
 }
 
-// 批量复制
-pub async fn batch_copy(
+// 批量重命名
+pub async fn batch_rename(
     State(state): State<Arc<AppState>>,
-    Json(req): Json<BatchCopyRequest>,
+    user: AuthUser,
+    Json(req): Json<BatchRenameRequest>,
 ) -> Result<impl IntoResponse, AppError> {
-    let dest_safe = sanitize_path(&req.destination);
-    let dest_dir = state.base_dir.join(&dest_safe);
+    let mut renamed = Vec::new();
+    let mut failed = Vec::new();
 
-    if !is_safe_path(&state.base_dir, &dest_dir) {
+    for item in req.items {
+        match rename_entry(&state, &user, &item.path, &item.new_name, req.on_conflict).await {
+            Ok(_) => renamed.push(item.path),
+            Err(e) => failed.push((item.path, e.to_string())),
+        }
+    }
+
+    tracing::info!("{} batch renamed {} items", user, renamed.len());
+    Ok(Json(ApiResponse::success(serde_json::json!({
+        "renamed": renamed,
+        "failed": failed
+    }))))
+}
+
+// 在原目录中重命名条目，返回新的相对路径；被覆盖的目标移入回收站
+async fn rename_entry(
+    state: &AppState,
+    user: &AuthUser,
+    path: &str,
+    new_name: &str,
+    policy: RenameConflict,
+) -> Result<String, AppError> {
+    if !is_valid_file_name(new_name) {
+        return Err(AppError::InvalidPath("Invalid file name".to_string()));
+    }
+
+    let safe_path = sanitize_path(path);
+    if safe_path.is_empty() {
+        return Err(AppError::InvalidPath("Cannot rename root directory".to_string()));
+    }
+
+    let full_path = state.base_dir.join(&safe_path);
+    let parent_rel = safe_path.rsplit_once('/').map(|(parent, _)| parent).unwrap_or("");
+    let new_rel = join_relative_path(parent_rel, new_name);
+    let new_path = state.base_dir.join(&new_rel);
+
+    if !is_safe_path(&state.base_dir, &full_path) || !is_safe_path(&state.base_dir, &new_path) {
         return Err(AppError::PermissionDenied("Access denied".to_string()));
     }
 
-    fs::create_dir_all(&dest_dir).await?;
+    state.acl.check_tree(user, &safe_path, Permission::Delete)?;
+    state.acl.check_tree(user, &new_rel, Permission::Write)?;
 
-    let mut copied = Vec::new();
-    let mut failed = Vec::new();
+    if fs::symlink_metadata(&full_path).await.is_err() {
+        return Err(AppError::NotFound("Not found".to_string()));
+    }
 
-    for path in req.paths {
-        let safe_path = sanitize_path(&path);
-        let full_path = state.base_dir.join(&safe_path);
+    if new_rel == safe_path {
+        return Ok(new_rel);
+    }
 
-        if !is_safe_path(&state.base_dir, &full_path) {
-            failed.push((path, "Access denied".to_string()));
-            continue;
+    let old_name = safe_path.rsplit('/').next().unwrap_or(&safe_path);
+    if name_taken(&state.base_dir.join(parent_rel), new_name, old_name).await? {
+        match policy {
+            RenameConflict::Fail => {
+                return Err(AppError::Conflict(format!("{} already exists", new_rel)));
+            }
+            RenameConflict::Overwrite => {
+                state.acl.check_tree(user, &new_rel, Permission::Delete)?;
+                state.trash.remove(&new_path, &new_rel, user).await?;
+                events::file_changed(state, ChangeKind::Deleted, &new_rel, &user.username).await;
+            }
         }
+    }
 
-        if !full_path.exists() {
-            failed.push((path, "Not found".to_string()));
-            continue;
-        }
+    fs::rename(&full_path, &new_path).await?;
+    events::file_renamed(state, &safe_path, &new_rel, &user.username).await;
+    tracing::info!("{} renamed {} to {}", user, safe_path, new_rel);
+    Ok(new_rel)
+}
 
-        let file_name = full_path
-            .file_name()
-            .and_then(|n| n.to_str())
-            .unwrap_or("unknown");
-        let new_path = dest_dir.join(file_name);
+// 目录中是否已有名为name的条目；大小写不敏感的文件系统上只改变大小写的重命名不算冲突
+pub(crate) async fn name_taken(dir: &std::path::Path, name: &str, old_name: &str) -> std::io::Result<bool> {
+    if fs::symlink_metadata(dir.join(name)).await.is_err() {
+        return Ok(false);
+    }
+    if name.to_lowercase() != old_name.to_lowercase() {
+        return Ok(true);
+    }
 
-        match if full_path.is_dir() {
-            copy_dir_recursive(full_path.clone(), new_path).await
-        } else {
-            fs::copy(&full_path, &new_path).await.map(|_| ())
-        } {
-            Ok(_) => copied.push(path),
-            Err(e) => failed.push((path, e.to_string())),
+    let mut entries = fs::read_dir(dir).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        if entry.file_name() == name {
+            return Ok(true);
         }
     }
-
-    Ok(Json(ApiResponse::success(serde_json::json!({
-        "copied": copied,
-        "failed": failed
-    }))))
+    Ok(false)
 }
 
-fn copy_dir_recursive(
+pub(crate) fn copy_dir_recursive(
     src: std::path::PathBuf,
     dst: std::path::PathBuf,
 ) -> std::pin::Pin<Box<dyn std::future::Future<Output = std::io::Result<()>> + Send>> {
@@ -478,6 +903,7 @@ fn copy_dir_recursive(
 // 文件下载（支持Range请求用于seek）
 pub async fn download_file(
     State(state): State<Arc<AppState>>,
+    user: AuthUser,
     Path(path): Path<String>,
     headers: axum::http::HeaderMap,
 ) -> Result<Response, AppError> {
@@ -488,6 +914,8 @@ pub async fn download_file(
         return Err(AppError::PermissionDenied("Access denied".to_string()));
     }
 
+    state.acl.check(&user, &safe_path, Permission::Read)?;
+
     if !full_path.exists() {
         return Err(AppError::NotFound("File not found".to_string()));
     }
@@ -496,13 +924,32 @@ pub async fn download_file(
         return Err(AppError::InvalidPath("Cannot download directory".to_string()));
     }
 
+    let mut response = send_file(&full_path, &headers).await?;
+    checksum::add_digest_headers(&state, &full_path, &safe_path, &headers, response.headers_mut()).await?;
+    Ok(response)
+}
+
+// 发送文件内容，支持Range请求
+pub(crate) async fn send_file(
+    full_path: &std::path::Path,
+    headers: &axum::http::HeaderMap,
+) -> Result<Response, AppError> {
     let file_name = full_path
         .file_name()
         .and_then(|n| n.to_str())
         .unwrap_or("download");
 
-    let file_size = fs::metadata(&full_path).await?.len();
-    let mime_type = get_mime_type(&full_path)
+    send_file_as(full_path, file_name, headers).await
+}
+
+// 以指定文件名发送文件内容（用于历史版本等存储名与原文件名不同的文件）
+pub(crate) async fn send_file_as(
+    full_path: &std::path::Path,
+    file_name: &str,
+    headers: &axum::http::HeaderMap,
+) -> Result<Response, AppError> {
+    let file_size = fs::metadata(full_path).await?.len();
+    let mime_type = get_mime_type(std::path::Path::new(file_name))
         .unwrap_or_else(|| "application/octet-stream".to_string());
 
     // 检查Range请求头
@@ -512,7 +959,7 @@ pub async fn download_file(
                 let (start, end) = range;
                 let content_length = end - start + 1;
                 
-                let file = tokio::fs::File::open(&full_path).await?;
+                let file = tokio::fs::File::open(full_path).await?;
                 let mut reader = tokio::io::BufReader::new(file);
                 
                 // 跳转到指定位置
@@ -542,7 +989,7 @@ pub async fn download_file(
     }
 
     // 普通请求，返回完整文件
-    let content = fs::read(&full_path).await?;
+    let content = fs::read(full_path).await?;
 
     Ok((
         [
@@ -560,7 +1007,7 @@ pub async fn download_file(
 }
 
 // 解析Range请求头
-fn parse_range_header(range_str: &str, file_size: u64) -> Option<(u64, u64)> {
+pub(crate) fn parse_range_header(range_str: &str, file_size: u64) -> Option<(u64, u64)> {
     if !range_str.starts_with("bytes=") {
         return None;
     }
@@ -571,11 +1018,21 @@ fn parse_range_header(range_str: &str, file_size: u64) -> Option<(u64, u64)> {
         let start_str = &range_part[..dash_pos];
         let end_str = &range_part[dash_pos + 1..];
 
+        // bytes=-N 表示最后N个字节
+        if start_str.is_empty() {
+            let suffix = end_str.parse::<u64>().ok()?.min(file_size);
+            if suffix == 0 {
+                return None;
+            }
+            return Some((file_size - suffix, file_size - 1));
+        }
+
         let start = start_str.parse::<u64>().ok()?;
+        // 结束位置超出文件大小时截断到文件末尾
         let end = if end_str.is_empty() {
-            file_size - 1
+            file_size.checked_sub(1)?
         } else {
-            end_str.parse::<u64>().ok()?
+            end_str.parse::<u64>().ok()?.min(file_size.checked_sub(1)?)
         };
 
         if start <= end && end < file_size {
@@ -607,3 +1064,136 @@ pub async fn app_js() -> ([(header::HeaderName, &'static str); 1], &'static str)
         include_str!("../static/app.js"),
     )
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{test_state, TempDir};
+
+    #[test]
+    fn range_header() {
+        assert_eq!(parse_range_header("bytes=0-9", 100), Some((0, 9)));
+        assert_eq!(parse_range_header("bytes=90-", 100), Some((90, 99)));
+        // 结束位置超出文件大小时截断
+        assert_eq!(parse_range_header("bytes=10-1000", 100), Some((10, 99)));
+        assert_eq!(parse_range_header("bytes=-10", 100), Some((90, 99)));
+        assert_eq!(parse_range_header("bytes=-1000", 100), Some((0, 99)));
+    }
+
+    #[test]
+    fn unsatisfiable_or_malformed_range() {
+        for range in [
+            "bytes=-0",
+            "bytes=50-10",
+            "bytes=100-",
+            "bytes=abc-",
+            "bytes=0-x",
+            "bytes=",
+            "bytes=5",
+            "items=0-9",
+            "x",
+            // 不支持多段Range
+            "bytes=0-1,5-6",
+        ] {
+            assert_eq!(parse_range_header(range, 100), None, "{:?}", range);
+        }
+        assert_eq!(parse_range_header("bytes=0-", 0), None);
+        assert_eq!(parse_range_header("bytes=-5", 0), None);
+    }
+
+    #[tokio::test]
+    async fn name_taken_ignores_case_only_renames() {
+        let dir = TempDir::new();
+        std::fs::write(dir.path().join("a.txt"), "").unwrap();
+        std::fs::write(dir.path().join("b.txt"), "").unwrap();
+
+        assert!(name_taken(dir.path(), "b.txt", "a.txt").await.unwrap());
+        assert!(!name_taken(dir.path(), "c.txt", "a.txt").await.unwrap());
+        // 只改变大小写时，只有目录中确实存在同名（大小写完全一致）的条目才算冲突
+        assert!(!name_taken(dir.path(), "A.txt", "a.txt").await.unwrap());
+        if !dir.path().join("A.TXT").exists() {
+            std::fs::write(dir.path().join("A.txt"), "").unwrap();
+            assert!(name_taken(dir.path(), "A.txt", "a.txt").await.unwrap());
+        }
+    }
+
+    #[tokio::test]
+    async fn rename_conflict_policy() {
+        let (state, _dir) = test_state(serde_json::json!({ "acl": { "default": ["read", "write", "delete"] } })).await;
+        let user = AuthUser::anonymous();
+        std::fs::create_dir(state.base_dir.join("d")).unwrap();
+        std::fs::write(state.base_dir.join("d/a.txt"), "a").unwrap();
+        std::fs::write(state.base_dir.join("d/b.txt"), "b").unwrap();
+
+        let result = rename_entry(&state, &user, "d/a.txt", "b.txt", RenameConflict::Fail).await;
+        assert!(matches!(result, Err(AppError::Conflict(_))));
+        assert_eq!(std::fs::read_to_string(state.base_dir.join("d/b.txt")).unwrap(), "b");
+
+        // 覆盖时原有的目标移入回收站
+        let new_rel = rename_entry(&state, &user, "d/a.txt", "b.txt", RenameConflict::Overwrite)
+            .await
+            .unwrap();
+        assert_eq!(new_rel, "d/b.txt");
+        assert!(!state.base_dir.join("d/a.txt").exists());
+        assert_eq!(std::fs::read_to_string(state.base_dir.join("d/b.txt")).unwrap(), "a");
+        let trashed = std::fs::read_dir(state.base_dir.join(TRASH_DIR)).unwrap().count();
+        assert_eq!(trashed, 2);
+
+        let result = rename_entry(&state, &user, "d/b.txt", "../x", RenameConflict::Fail).await;
+        assert!(matches!(result, Err(AppError::InvalidPath(_))));
+    }
+
+    #[tokio::test]
+    async fn transfer_merges_directories() {
+        let (state, _dir) = test_state(serde_json::json!({ "acl": { "default": ["read", "write", "delete"] } })).await;
+        let user = AuthUser::anonymous();
+        let base = &state.base_dir;
+        std::fs::create_dir_all(base.join("src/d/sub")).unwrap();
+        std::fs::write(base.join("src/d/x.txt"), "new").unwrap();
+        std::fs::write(base.join("src/d/sub/y.txt"), "y").unwrap();
+        std::fs::create_dir_all(base.join("dst/d")).unwrap();
+        std::fs::write(base.join("dst/d/x.txt"), "old").unwrap();
+        std::fs::write(base.join("dst/d/z.txt"), "z").unwrap();
+
+        let item = transfer_entry(&state, &user, "src/d".to_string(), "dst", TransferConflict::Merge, true).await;
+        assert_eq!(item.outcome, TransferOutcome::Moved, "{:?}", item.error);
+        assert_eq!(item.target.as_deref(), Some("dst/d"));
+        assert_eq!(item.policy, Some(TransferConflict::Merge));
+
+        // 同名文件被覆盖，目标中原有的其他条目保留，源目录被删除
+        assert_eq!(std::fs::read_to_string(base.join("dst/d/x.txt")).unwrap(), "new");
+        assert_eq!(std::fs::read_to_string(base.join("dst/d/z.txt")).unwrap(), "z");
+        assert_eq!(std::fs::read_to_string(base.join("dst/d/sub/y.txt")).unwrap(), "y");
+        assert!(!base.join("src/d").exists());
+        // 被覆盖的文件移入回收站
+        let trashed = std::fs::read_dir(base.join(TRASH_DIR)).unwrap().count();
+        assert!(trashed >= 1);
+    }
+
+    #[tokio::test]
+    async fn transfer_rename_picks_unique_names() {
+        let (state, _dir) = test_state(serde_json::json!({ "acl": { "default": ["read", "write", "delete"] } })).await;
+        let user = AuthUser::anonymous();
+        let base = &state.base_dir;
+        std::fs::create_dir_all(base.join("src/d")).unwrap();
+        std::fs::write(base.join("src/a.txt"), "a").unwrap();
+        std::fs::create_dir_all(base.join("dst/d")).unwrap();
+        std::fs::write(base.join("dst/a.txt"), "old").unwrap();
+
+        let item = transfer_entry(&state, &user, "src/a.txt".to_string(), "dst", TransferConflict::Rename, false).await;
+        assert_eq!(item.outcome, TransferOutcome::Copied, "{:?}", item.error);
+        assert_eq!(item.target.as_deref(), Some("dst/a (1).txt"));
+        let item = transfer_entry(&state, &user, "src/a.txt".to_string(), "dst", TransferConflict::Rename, true).await;
+        assert_eq!(item.outcome, TransferOutcome::Moved, "{:?}", item.error);
+        assert_eq!(item.target.as_deref(), Some("dst/a (2).txt"));
+        assert_eq!(std::fs::read_to_string(base.join("dst/a.txt")).unwrap(), "old");
+        assert_eq!(std::fs::read_to_string(base.join("dst/a (2).txt")).unwrap(), "a");
+        assert!(!base.join("src/a.txt").exists());
+
+        // 目录名不拆分扩展名
+        let item = transfer_entry(&state, &user, "src/d".to_string(), "dst", TransferConflict::Rename, true).await;
+        assert_eq!(item.target.as_deref(), Some("dst/d (1)"));
+        assert!(base.join("dst/d (1)").is_dir());
+        assert!(!base.join("src/d").exists());
+    }
+}
@@ -0,0 +1,854 @@
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeSet, HashMap};
+use std::fs::Metadata;
+use std::io::Read;
+use std::ops::ControlFlow;
+use std::path::{Path as FsPath, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+
+use crate::{
+    acl::Permission,
+    auth::AuthUser,
+    errors::AppError,
+    models::*,
+    tokens::require_admin,
+    usage,
+    utils::*,
+    AppState,
+};
+
+// 文件系统事件合并处理的等待时间
+const WATCH_DEBOUNCE: Duration = Duration::from_millis(500);
+
+// 检查报告中每类最多列出的路径数
+const REPORT_SAMPLE: usize = 100;
+
+// 同步时每个事务写入的条目数，批次之间释放数据库锁，重建大目录时不阻塞其他查询
+const SYNC_BATCH: usize = 1000;
+
+// 搜索时每批从索引读取的条目数，同样在批次之间释放数据库锁
+const QUERY_BATCH: usize = 1000;
+
+// 索引格式版本，与数据库的user_version不同时删除旧数据重新构建；
+// 版本2起modified以纳秒保存
+const SCHEMA_VERSION: i64 = 2;
+
+const NANOS_PER_SEC: i64 = 1_000_000_000;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS entries (
+    path TEXT PRIMARY KEY,
+    parent TEXT NOT NULL,
+    name TEXT NOT NULL,
+    is_dir INTEGER NOT NULL,
+    is_link INTEGER NOT NULL,
+    size INTEGER NOT NULL,
+    modified INTEGER NOT NULL,
+    created INTEGER NOT NULL,
+    mime TEXT,
+    hash TEXT
+);
+CREATE INDEX IF NOT EXISTS entries_parent ON entries(parent);
+";
+
+const ENTRY_COLUMNS: &str = "path, name, is_dir, is_link, size, modified, created, mime, hash";
+
+// 索引中的一个条目，path为相对base_dir的路径
+#[derive(Debug, Clone)]
+pub struct IndexedEntry {
+    pub path: String,
+    pub name: String,
+    pub is_dir: bool,
+    pub is_link: bool,
+    pub size: u64,
+    pub modified: i64,
+    pub created: i64,
+    pub mime: Option<String>,
+    pub hash: Option<String>,
+}
+
+impl IndexedEntry {
+    fn from_row(row: &rusqlite::Row) -> rusqlite::Result<Self> {
+        Ok(IndexedEntry {
+            path: row.get(0)?,
+            name: row.get(1)?,
+            is_dir: row.get(2)?,
+            is_link: row.get(3)?,
+            size: row.get::<_, i64>(4)? as u64,
+            modified: row.get::<_, i64>(5)?.div_euclid(NANOS_PER_SEC),
+            created: row.get(6)?,
+            mime: row.get(7)?,
+            hash: row.get(8)?,
+        })
+    }
+
+    pub fn to_file_entry(&self) -> FileEntry {
+        FileEntry {
+            name: self.name.clone(),
+            path: self.path.clone(),
+            is_dir: self.is_dir,
+            size: self.size,
+            modified: format_timestamp(self.modified),
+            created: format_timestamp(self.created),
+        }
+    }
+}
+
+// 待写入索引的一行，哈希等耗时的部分在持有数据库锁之前算好
+struct EntryRow {
+    path: String,
+    is_dir: bool,
+    is_link: bool,
+    size: u64,
+    modified: i64,
+    created: i64,
+    mime: Option<String>,
+    hash: Option<String>,
+}
+
+// 与磁盘比较时使用的已索引状态
+struct IndexedState {
+    is_dir: bool,
+    size: u64,
+    modified: i64,
+    has_hash: bool,
+}
+
+fn unix_secs(time: std::io::Result<std::time::SystemTime>) -> i64 {
+    time.ok()
+        .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+        .map(|d| d.as_secs() as i64)
+        .unwrap_or(0)
+}
+
+fn parent_of(rel_path: &str) -> &str {
+    rel_path.rsplit_once('/').map(|(parent, _)| parent).unwrap_or("")
+}
+
+// rel_path下所有子路径的查询条件：'a/' <= path < 'a0'（'0'是'/'的下一个字符），根目录匹配所有条目
+fn subtree_filter(rel_path: &str, include_self: bool) -> (&'static str, Vec<String>) {
+    if rel_path.is_empty() {
+        return ("1", Vec::new());
+    }
+    let bounds = vec![format!("{}/", rel_path), format!("{}0", rel_path)];
+    if include_self {
+        let mut params = bounds;
+        params.push(rel_path.to_string());
+        ("(path >= ?1 AND path < ?2) OR path = ?3", params)
+    } else {
+        ("path >= ?1 AND path < ?2", bounds)
+    }
+}
+
+// 索引数据库及其访问的目录
+struct IndexCore {
+    base_dir: PathBuf,
+    hash: bool,
+    conn: Mutex<Connection>,
+}
+
+impl IndexCore {
+    fn conn(&self) -> std::sync::MutexGuard<'_, Connection> {
+        self.conn.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn hash_file(&self, path: &FsPath) -> Option<String> {
+        let mut file = std::fs::File::open(path).ok()?;
+        let mut hasher = Sha256::new();
+        let mut buf = vec![0u8; 64 * 1024];
+        loop {
+            let n = file.read(&mut buf).ok()?;
+            if n == 0 {
+                break;
+            }
+            hasher.update(&buf[..n]);
+        }
+        Some(hex::encode(hasher.finalize()))
+    }
+
+    fn entry_row(&self, rel_path: &str, full_path: &FsPath, metadata: &Metadata, is_link: bool) -> EntryRow {
+        let is_dir = metadata.is_dir();
+        EntryRow {
+            path: rel_path.to_string(),
+            is_dir,
+            is_link,
+            size: metadata.len(),
+            modified: modified_nanos(metadata),
+            // 文件系统不支持创建时间时使用修改时间
+            created: unix_secs(metadata.created().or_else(|_| metadata.modified())),
+            mime: if is_dir { None } else { get_mime_type(full_path) },
+            hash: if self.hash && !is_dir { self.hash_file(full_path) } else { None },
+        }
+    }
+
+    fn insert_row(conn: &Connection, row: &EntryRow) -> rusqlite::Result<()> {
+        let name = row.path.rsplit('/').next().unwrap_or(&row.path);
+        conn.execute(
+            "INSERT OR REPLACE INTO entries (path, parent, name, is_dir, is_link, size, modified, created, mime, hash)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+            params![
+                row.path,
+                parent_of(&row.path),
+                name,
+                row.is_dir,
+                row.is_link,
+                row.size as i64,
+                row.modified,
+                row.created,
+                row.mime,
+                row.hash,
+            ],
+        )?;
+        Ok(())
+    }
+
+    // 在一个事务中写入一批条目；扫描之后又被修改或删除的条目跳过，留给之后的刷新处理，
+    // 避免覆盖并发刷新写入的较新状态
+    fn write_rows(&self, rows: &mut Vec<EntryRow>) -> rusqlite::Result<()> {
+        if rows.is_empty() {
+            return Ok(());
+        }
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+        for row in rows.drain(..) {
+            let unchanged = std::fs::metadata(self.base_dir.join(&row.path)).is_ok_and(|m| {
+                m.is_dir() == row.is_dir && m.len() == row.size && modified_nanos(&m) == row.modified
+            });
+            if unchanged {
+                Self::insert_row(&tx, &row)?;
+            }
+        }
+        tx.commit()
+    }
+
+    // 读取rel_path（为空时表示整个根目录）及其子路径的已索引状态
+    fn indexed_states(&self, conn: &Connection, rel_path: &str) -> rusqlite::Result<HashMap<String, IndexedState>> {
+        let (condition, params) = subtree_filter(rel_path, true);
+        let mut stmt = conn.prepare(&format!(
+            "SELECT path, is_dir, size, modified, hash IS NOT NULL FROM entries WHERE {}",
+            condition
+        ))?;
+        let rows = stmt.query_map(params_from_iter(params), |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                IndexedState {
+                    is_dir: row.get(1)?,
+                    size: row.get::<_, i64>(2)? as u64,
+                    modified: row.get(3)?,
+                    has_hash: row.get(4)?,
+                },
+            ))
+        })?;
+        rows.collect()
+    }
+
+    // 遍历磁盘上rel_path下的条目：不进入符号链接目录，跳过回收站，符号链接记录目标的元数据
+    fn scan_disk(&self, rel_path: &str, visit: &mut dyn FnMut(&str, &FsPath, &Metadata, bool)) {
+        let full_path = if rel_path.is_empty() {
+            self.base_dir.clone()
+        } else {
+            self.base_dir.join(rel_path)
+        };
+
+        if !rel_path.is_empty() {
+            let Ok(link_meta) = std::fs::symlink_metadata(&full_path) else {
+                return;
+            };
+            let is_link = link_meta.file_type().is_symlink();
+            let Ok(metadata) = std::fs::metadata(&full_path) else {
+                return;
+            };
+            visit(rel_path, &full_path, &metadata, is_link);
+            if is_link || !metadata.is_dir() {
+                return;
+            }
+        }
+        self.scan_dir(&full_path, rel_path, visit);
+    }
+
+    fn scan_dir(&self, dir: &FsPath, rel_path: &str, visit: &mut dyn FnMut(&str, &FsPath, &Metadata, bool)) {
+        let Ok(read_dir) = std::fs::read_dir(dir) else {
+            return;
+        };
+
+        for entry in read_dir.filter_map(|e| e.ok()) {
+            let full_path = entry.path();
+            if !is_safe_path(&self.base_dir, &full_path) {
+                continue;
+            }
+            let child_rel = join_relative_path(rel_path, &entry.file_name().to_string_lossy());
+            let Ok(file_type) = entry.file_type() else {
+                continue;
+            };
+            let is_link = file_type.is_symlink();
+            let Ok(metadata) = std::fs::metadata(&full_path) else {
+                continue;
+            };
+
+            visit(&child_rel, &full_path, &metadata, is_link);
+            if metadata.is_dir() && !is_link {
+                self.scan_dir(&full_path, &child_rel, visit);
+            }
+        }
+    }
+
+    // 比较rel_path下磁盘与索引的差异，apply为true时同时修正索引；
+    // 遍历磁盘和计算哈希时不持有数据库锁，修正分批提交
+    fn sync(&self, rel_path: &str, apply: bool) -> rusqlite::Result<IndexReport> {
+        let started = Instant::now();
+        let mut indexed = self.indexed_states(&self.conn(), rel_path)?;
+        let mut report = IndexReport::default();
+        let mut pending = Vec::new();
+        let mut error = None;
+
+        self.scan_disk(rel_path, &mut |path, full_path, metadata, is_link| {
+            if error.is_some() {
+                return;
+            }
+            report.entries += 1;
+
+            let changed = match indexed.remove(path) {
+                None => {
+                    report.missing_count += 1;
+                    if report.missing.len() < REPORT_SAMPLE {
+                        report.missing.push(path.to_string());
+                    }
+                    true
+                }
+                Some(old)
+                    if old.is_dir != metadata.is_dir()
+                        || old.size != metadata.len()
+                        || old.modified != modified_nanos(metadata) =>
+                {
+                    report.stale_count += 1;
+                    if report.stale.len() < REPORT_SAMPLE {
+                        report.stale.push(path.to_string());
+                    }
+                    true
+                }
+                // 开启哈希后补算旧条目的哈希，不计入差异
+                Some(old) => self.hash && !old.is_dir && !old.has_hash,
+            };
+
+            if apply && changed {
+                pending.push(self.entry_row(path, full_path, metadata, is_link));
+                if pending.len() >= SYNC_BATCH {
+                    if let Err(e) = self.write_rows(&mut pending) {
+                        error = Some(e);
+                    }
+                }
+            }
+        });
+        if let Some(e) = error {
+            return Err(e);
+        }
+        if apply {
+            self.write_rows(&mut pending)?;
+        }
+
+        let mut extra: Vec<String> = indexed.into_keys().collect();
+        extra.sort();
+        report.extra_count = extra.len();
+        if apply {
+            for batch in extra.chunks(SYNC_BATCH) {
+                let mut conn = self.conn();
+                let tx = conn.transaction()?;
+                {
+                    let mut stmt = tx.prepare("DELETE FROM entries WHERE path = ?1")?;
+                    for path in batch {
+                        // 扫描之后重新出现的路径留给之后的刷新处理
+                        if std::fs::symlink_metadata(self.base_dir.join(path)).is_err() {
+                            stmt.execute(params![path])?;
+                        }
+                    }
+                }
+                tx.commit()?;
+            }
+        }
+        extra.truncate(REPORT_SAMPLE);
+        report.extra = extra;
+
+        report.repaired = apply;
+        report.duration_ms = started.elapsed().as_millis() as u64;
+        Ok(report)
+    }
+
+    // 只更新单个条目（不含子路径），用于子项变化后刷新上级目录的修改时间
+    fn touch(&self, rel_path: &str) -> rusqlite::Result<()> {
+        let full_path = self.base_dir.join(rel_path);
+        match (std::fs::symlink_metadata(&full_path), std::fs::metadata(&full_path)) {
+            (Ok(link_meta), Ok(metadata)) => {
+                let row = self.entry_row(rel_path, &full_path, &metadata, link_meta.file_type().is_symlink());
+                Self::insert_row(&self.conn(), &row)
+            }
+            _ => self
+                .conn()
+                .execute("DELETE FROM entries WHERE path = ?1", params![rel_path])
+                .map(|_| ()),
+        }
+    }
+
+    // 目录本身是否已完整索引：根目录，或索引中不是符号链接的目录
+    fn is_indexed_dir(&self, conn: &Connection, rel_path: &str) -> rusqlite::Result<bool> {
+        if rel_path.is_empty() {
+            return Ok(true);
+        }
+        let row: Option<(bool, bool)> = conn
+            .query_row(
+                "SELECT is_dir, is_link FROM entries WHERE path = ?1",
+                params![rel_path],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .optional()?;
+        Ok(matches!(row, Some((true, false))))
+    }
+
+    fn list_dir(&self, rel_path: &str) -> rusqlite::Result<Option<Vec<IndexedEntry>>> {
+        let conn = self.conn();
+        if !self.is_indexed_dir(&conn, rel_path)? {
+            return Ok(None);
+        }
+        let mut stmt = conn.prepare(&format!("SELECT {} FROM entries WHERE parent = ?1", ENTRY_COLUMNS))?;
+        let rows = stmt.query_map(params![rel_path], IndexedEntry::from_row)?;
+        rows.collect::<rusqlite::Result<Vec<_>>>().map(Some)
+    }
+
+    // 目录下路径大于after的下一批子路径（按路径排序），只在读取这一批时持有连接
+    fn entries_under(&self, rel_path: &str, after: &str, batch: usize) -> rusqlite::Result<Option<Vec<IndexedEntry>>> {
+        let conn = self.conn();
+        if !self.is_indexed_dir(&conn, rel_path)? {
+            return Ok(None);
+        }
+        let (condition, mut params) = subtree_filter(rel_path, false);
+        params.push(after.to_string());
+        let mut stmt = conn.prepare(&format!(
+            "SELECT {} FROM entries WHERE ({}) AND path > ?{} ORDER BY path LIMIT {}",
+            ENTRY_COLUMNS,
+            condition,
+            params.len(),
+            batch
+        ))?;
+        let rows = stmt.query_map(params_from_iter(params), IndexedEntry::from_row)?;
+        rows.collect::<rusqlite::Result<Vec<_>>>().map(Some)
+    }
+
+    // 已索引文件的大小、修改时间（纳秒）和SHA-256摘要
+    fn file_hash(&self, rel_path: &str) -> rusqlite::Result<Option<(u64, i64, String)>> {
+        let row: Option<(i64, i64, Option<String>)> = self
+            .conn()
+            .query_row(
+                "SELECT size, modified, hash FROM entries WHERE path = ?1 AND is_dir = 0",
+                params![rel_path],
+                |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+            )
+            .optional()?;
+        Ok(row.and_then(|(size, modified, hash)| Some((size as u64, modified, hash?))))
+    }
+
+    // 目录下的文件总大小、文件数和子目录数（不含符号链接）
+    fn dir_usage(&self, rel_path: &str) -> rusqlite::Result<Option<(u64, u64, u64)>> {
+        let conn = self.conn();
+        if !self.is_indexed_dir(&conn, rel_path)? {
+            return Ok(None);
+        }
+        let (condition, params) = subtree_filter(rel_path, false);
+        let (size, files, dirs): (i64, i64, i64) = conn.query_row(
+            &format!(
+                "SELECT COALESCE(SUM(CASE WHEN is_dir = 0 THEN size ELSE 0 END), 0),
+                        COALESCE(SUM(is_dir = 0), 0),
+                        COALESCE(SUM(is_dir = 1), 0)
+                 FROM entries WHERE is_link = 0 AND ({})",
+                condition
+            ),
+            params_from_iter(params),
+            |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+        )?;
+        Ok(Some((size as u64, files as u64, dirs as u64)))
+    }
+}
+
+// 可选的元数据索引（SQLite），加速大目录的列表、搜索和目录大小统计
+pub struct IndexStore {
+    core: Option<Arc<IndexCore>>,
+    // 启动时的首次构建完成前不使用索引
+    ready: AtomicBool,
+    // 文件系统监听到的变化路径，首次构建完成后再处理
+    changes_tx: Option<mpsc::UnboundedSender<PathBuf>>,
+    changes_rx: Mutex<Option<mpsc::UnboundedReceiver<PathBuf>>>,
+}
+
+impl IndexStore {
+    pub fn new(db_path: PathBuf, base_dir: &FsPath, config: &serde_json::Value) -> anyhow::Result<Self> {
+        let enabled = config["enabled"].as_bool().unwrap_or(false);
+        let watch = config["watch"].as_bool().unwrap_or(true);
+        let hash = config["hash"].as_bool().unwrap_or(false);
+
+        let core = if enabled {
+            let conn = Connection::open(&db_path)?;
+            conn.execute_batch("PRAGMA journal_mode = WAL; PRAGMA synchronous = NORMAL;")?;
+            let version: i64 = conn.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+            if version != SCHEMA_VERSION {
+                // 旧格式的数据直接丢弃，启动时的首次构建会重新索引
+                conn.execute_batch("DROP TABLE IF EXISTS entries;")?;
+            }
+            conn.execute_batch(SCHEMA)?;
+            conn.pragma_update(None, "user_version", SCHEMA_VERSION)?;
+            Some(Arc::new(IndexCore {
+                base_dir: std::fs::canonicalize(base_dir)?,
+                hash,
+                conn: Mutex::new(conn),
+            }))
+        } else {
+            None
+        };
+
+        let (changes_tx, changes_rx) = if core.is_some() && watch {
+            let (tx, rx) = mpsc::unbounded_channel();
+            (Some(tx), Some(rx))
+        } else {
+            (None, None)
+        };
+
+        Ok(IndexStore {
+            core,
+            ready: AtomicBool::new(false),
+            changes_tx,
+            changes_rx: Mutex::new(changes_rx),
+        })
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.core.is_some()
+    }
+
+    // 是否需要文件系统监听器
+    pub fn wants_watch(&self) -> bool {
+        self.changes_tx.is_some()
+    }
+
+    // 监听器发现路径变化时调用，path为规范化后的绝对路径
+    pub fn path_changed(&self, path: PathBuf) {
+        if let Some(tx) = &self.changes_tx {
+            let _ = tx.send(path);
+        }
+    }
+
+    fn ready_core(&self) -> Option<Arc<IndexCore>> {
+        if self.ready.load(Ordering::Acquire) {
+            self.core.clone()
+        } else {
+            None
+        }
+    }
+
+    // 在阻塞线程中访问索引，索引未启用或未就绪时返回None
+    async fn query<T, F>(&self, f: F) -> Option<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&IndexCore) -> rusqlite::Result<Option<T>> + Send + 'static,
+    {
+        let core = self.ready_core()?;
+        match tokio::task::spawn_blocking(move || f(&core)).await {
+            Ok(Ok(result)) => result,
+            Ok(Err(e)) => {
+                tracing::error!("Index query failed: {}", e);
+                None
+            }
+            Err(e) => {
+                tracing::error!("Index query task failed: {}", e);
+                None
+            }
+        }
+    }
+
+    // 目录的直接子项，目录未被索引时返回None，调用方应回退到读取磁盘
+    pub async fn list_dir(&self, rel_path: &str) -> Option<Vec<IndexedEntry>> {
+        let rel_path = rel_path.to_string();
+        self.query(move |core| core.list_dir(&rel_path)).await
+    }
+
+    // 按路径顺序逐个处理目录下的所有子路径，供搜索在阻塞线程中使用；
+    // 分批读取，f返回Break时停止。目录未被索引时返回None
+    pub fn for_each_under_blocking(
+        &self,
+        rel_path: &str,
+        mut f: impl FnMut(&IndexedEntry) -> ControlFlow<()>,
+    ) -> Option<ControlFlow<()>> {
+        let core = self.ready_core()?;
+        let mut after = String::new();
+        loop {
+            let entries = match core.entries_under(rel_path, &after, QUERY_BATCH) {
+                Ok(Some(entries)) => entries,
+                result => {
+                    if let Err(e) = result {
+                        tracing::error!("Index query failed: {}", e);
+                    }
+                    // 第一批就失败时由调用方改为遍历文件系统；之后失败（如目录已被删除）按已处理的部分结束
+                    return (!after.is_empty()).then_some(ControlFlow::Continue(()));
+                }
+            };
+            for entry in &entries {
+                if f(entry).is_break() {
+                    return Some(ControlFlow::Break(()));
+                }
+            }
+            match entries.last() {
+                Some(last) if entries.len() == QUERY_BATCH => after = last.path.clone(),
+                _ => return Some(ControlFlow::Continue(())),
+            }
+        }
+    }
+
+    pub fn is_ready(&self) -> bool {
+        self.ready_core().is_some()
+    }
+
+    pub async fn dir_usage(&self, rel_path: &str) -> Option<(u64, u64, u64)> {
+        let rel_path = rel_path.to_string();
+        self.query(move |core| core.dir_usage(&rel_path)).await
+    }
+
+    // 启用索引的hash选项时已计算的文件SHA-256，返回(大小, 修改时间（纳秒）, 摘要)
+    pub async fn file_hash(&self, rel_path: &str) -> Option<(u64, i64, String)> {
+        let rel_path = rel_path.to_string();
+        self.query(move |core| core.file_hash(&rel_path)).await
+    }
+
+    // 文件或目录被修改后重新同步它及其子路径，并刷新各级上级目录
+    pub async fn refresh(&self, rel_path: &str) {
+        let Some(core) = self.core.clone() else {
+            return;
+        };
+        let rel_path = rel_path.to_string();
+        let result = tokio::task::spawn_blocking(move || {
+            core.sync(&rel_path, true)?;
+            // 上级目录可能是刚创建的，逐级补上
+            let mut parent = parent_of(&rel_path);
+            while !parent.is_empty() {
+                core.touch(parent)?;
+                parent = parent_of(parent);
+            }
+            Ok::<_, rusqlite::Error>(())
+        })
+        .await;
+
+        match result {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => tracing::error!("Failed to update index: {}", e),
+            Err(e) => tracing::error!("Index update task failed: {}", e),
+        }
+    }
+
+    // 检查整个索引与磁盘的差异，apply为true时修正
+    pub async fn sync_all(&self, apply: bool) -> Result<IndexReport, AppError> {
+        let core = self
+            .core
+            .clone()
+            .ok_or_else(|| AppError::InvalidRequest("Index is not enabled".to_string()))?;
+        let report = tokio::task::spawn_blocking(move || core.sync("", apply))
+            .await
+            .map_err(|e| AppError::IoError(e.to_string()))??;
+        if apply {
+            self.ready.store(true, Ordering::Release);
+        }
+        Ok(report)
+    }
+}
+
+// 启动时在后台构建索引，完成后处理文件系统监听到的变化
+pub fn spawn_index_task(state: Arc<AppState>) {
+    if !state.index.enabled() {
+        return;
+    }
+
+    tokio::spawn(async move {
+        tracing::info!("Building metadata index");
+        match state.index.sync_all(true).await {
+            Ok(report) => tracing::info!(
+                "Metadata index ready: {} entries, {} added, {} updated, {} removed in {} ms",
+                report.entries,
+                report.missing_count,
+                report.stale_count,
+                report.extra_count,
+                report.duration_ms
+            ),
+            Err(e) => {
+                tracing::error!("Failed to build metadata index: {}", e);
+                return;
+            }
+        }
+
+        let Some(core) = state.index.core.clone() else {
+            return;
+        };
+        let Some(mut rx) = state.index.changes_rx.lock().unwrap_or_else(|e| e.into_inner()).take() else {
+            return;
+        };
+
+        while let Some(first) = rx.recv().await {
+            // 合并一段时间内的事件，同一目录树只同步一次
+            tokio::time::sleep(WATCH_DEBOUNCE).await;
+            let mut paths = vec![first];
+            while let Ok(path) = rx.try_recv() {
+                paths.push(path);
+            }
+
+            let mut changed = BTreeSet::new();
+            for path in paths {
+                if !is_safe_path(&core.base_dir, &path) || is_temp_path(&path) {
+                    continue;
+                }
+                if let Ok(rel) = path.strip_prefix(&core.base_dir) {
+                    changed.insert(rel.to_string_lossy().replace('\\', "/"));
+                }
+            }
+
+            let mut synced: Vec<String> = Vec::new();
+            for rel in changed {
+                let covered = synced
+                    .iter()
+                    .any(|done| done.is_empty() || rel == *done || rel.starts_with(&format!("{}/", done)));
+                if !covered {
+                    state.index.refresh(&rel).await;
+                    synced.push(rel);
+                }
+            }
+        }
+    });
+}
+
+// 重新构建索引
+pub async fn rebuild_index(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+) -> Result<Json<ApiResponse<IndexReport>>, AppError> {
+    require_admin(&user)?;
+
+    let report = state.index.sync_all(true).await?;
+    tracing::info!(
+        "{} rebuilt metadata index: {} entries in {} ms",
+        user,
+        report.entries,
+        report.duration_ms
+    );
+    Ok(Json(ApiResponse::success(report)))
+}
+
+// 检查索引与磁盘是否一致，repair=true时同时修正
+pub async fn check_index(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Query(query): Query<IndexCheckQuery>,
+) -> Result<Json<ApiResponse<IndexReport>>, AppError> {
+    require_admin(&user)?;
+
+    let report = state.index.sync_all(query.repair).await?;
+    if query.repair {
+        tracing::info!(
+            "{} repaired metadata index: {} missing, {} stale, {} extra",
+            user,
+            report.missing_count,
+            report.stale_count,
+            report.extra_count
+        );
+    }
+    Ok(Json(ApiResponse::success(report)))
+}
+
+pub async fn dir_size_root(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+) -> Result<Json<ApiResponse<DirSizeResponse>>, AppError> {
+    dir_size_impl(state, &user, "").await
+}
+
+pub async fn dir_size(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Path(path): Path<String>,
+) -> Result<Json<ApiResponse<DirSizeResponse>>, AppError> {
+    dir_size_impl(state, &user, &path).await
+}
+
+// 统计目录下文件的总大小，启用索引时直接查询索引，否则遍历磁盘并缓存结果
+async fn dir_size_impl(
+    state: Arc<AppState>,
+    user: &AuthUser,
+    path: &str,
+) -> Result<Json<ApiResponse<DirSizeResponse>>, AppError> {
+    let safe_path = sanitize_path(path);
+    let full_path = if safe_path.is_empty() {
+        state.base_dir.clone()
+    } else {
+        state.base_dir.join(&safe_path)
+    };
+
+    if !is_safe_path(&state.base_dir, &full_path) {
+        return Err(AppError::PermissionDenied("Access denied".to_string()));
+    }
+
+    state.acl.check(user, &safe_path, Permission::Read)?;
+
+    if !full_path.exists() {
+        return Err(AppError::NotFound("Path not found".to_string()));
+    }
+
+    if !full_path.is_dir() {
+        return Err(AppError::InvalidPath("Not a directory".to_string()));
+    }
+
+    let (usage, indexed) = usage::visible_usage(&state, user, &full_path, &safe_path).await?;
+
+    Ok(Json(ApiResponse::success(DirSizeResponse {
+        path: safe_path,
+        size: usage.size,
+        files: usage.files,
+        dirs: usage.dirs,
+        indexed,
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::test_state;
+
+    #[tokio::test]
+    async fn subtree_is_read_in_batches() {
+        let (state, _dir) = test_state(serde_json::json!({ "index": { "enabled": true } })).await;
+        for path in ["a/1", "a/2", "a/3/x", "a/4", "a0", "ab/5"] {
+            let full_path = state.base_dir.join(path);
+            std::fs::create_dir_all(full_path.parent().unwrap()).unwrap();
+            std::fs::write(full_path, "").unwrap();
+        }
+        state.index.sync_all(true).await.unwrap();
+
+        // 按路径分页读取，兄弟目录"a0"、"ab"不属于"a"的子树
+        let core = state.index.ready_core().unwrap();
+        let mut paths = Vec::new();
+        let mut after = String::new();
+        while let Some(batch) = core.entries_under("a", &after, 2).unwrap().filter(|b| !b.is_empty()) {
+            after = batch.last().unwrap().path.clone();
+            paths.extend(batch.into_iter().map(|e| e.path));
+        }
+        assert_eq!(paths, ["a/1", "a/2", "a/3", "a/3/x", "a/4"]);
+
+        // 回调返回Break时不再读取后续条目
+        let mut seen = 0;
+        let flow = state.index.for_each_under_blocking("", |_| {
+            seen += 1;
+            if seen == 3 { ControlFlow::Break(()) } else { ControlFlow::Continue(()) }
+        });
+        assert_eq!(flow, Some(ControlFlow::Break(())));
+        assert_eq!(seen, 3);
+        assert!(state.index.for_each_under_blocking("missing", |_| ControlFlow::Continue(())).is_none());
+    }
+}
@@ -0,0 +1,237 @@
+use axum::{
+    body::Bytes,
+    http::{header, HeaderMap},
+    response::Response,
+};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::io;
+use std::ops::ControlFlow;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+
+use crate::{
+    auth::AuthUser,
+    errors::AppError,
+    models::*,
+    search::{file_extension, ndjson_response, parse_extensions, send_line, TreeWalker},
+    utils::{file_entry_from_metadata, natural_cmp},
+    AppState,
+};
+
+const NDJSON: &str = "application/x-ndjson";
+
+// 单页最多返回的条目数
+const MAX_LIMIT: usize = 10000;
+
+// 分页游标记录上一页最后一项的排序字段，下一页从排在它之后的条目开始，
+// 翻页期间目录有增删也不会重复或遗漏未变化的条目
+#[derive(Serialize, Deserialize)]
+struct Cursor {
+    sort: ListSort,
+    order: SortOrder,
+    name: String,
+    is_dir: bool,
+    size: u64,
+    modified: String,
+    created: String,
+}
+
+impl Cursor {
+    fn new(entry: &FileEntry, query: &ListQuery) -> Self {
+        Cursor {
+            sort: query.sort,
+            order: query.order,
+            name: entry.name.clone(),
+            is_dir: entry.is_dir,
+            size: entry.size,
+            modified: entry.modified.clone(),
+            created: entry.created.clone(),
+        }
+    }
+
+    fn encode(&self) -> String {
+        URL_SAFE_NO_PAD.encode(serde_json::to_vec(self).unwrap_or_default())
+    }
+
+    fn decode(value: &str, query: &ListQuery) -> Result<FileEntry, AppError> {
+        let cursor: Cursor = URL_SAFE_NO_PAD
+            .decode(value)
+            .ok()
+            .and_then(|data| serde_json::from_slice(&data).ok())
+            .ok_or_else(|| AppError::InvalidRequest("Invalid cursor".to_string()))?;
+        if cursor.sort != query.sort || cursor.order != query.order {
+            return Err(AppError::InvalidRequest("Cursor does not match the sort order".to_string()));
+        }
+        Ok(FileEntry {
+            name: cursor.name,
+            path: String::new(),
+            is_dir: cursor.is_dir,
+            size: cursor.size,
+            modified: cursor.modified,
+            created: cursor.created,
+        })
+    }
+}
+
+// 文件夹始终排在文件前面；排序字段相同时按名称排序，保证顺序唯一
+fn compare(a: &FileEntry, b: &FileEntry, sort: ListSort, order: SortOrder) -> Ordering {
+    if a.is_dir != b.is_dir {
+        return b.is_dir.cmp(&a.is_dir);
+    }
+
+    let ordering = match sort {
+        ListSort::Name => Ordering::Equal,
+        ListSort::Size => a.size.cmp(&b.size),
+        ListSort::Modified => a.modified.cmp(&b.modified),
+        ListSort::Created => a.created.cmp(&b.created),
+        ListSort::Type => file_extension(&a.name).cmp(&file_extension(&b.name)),
+    }
+    .then_with(|| natural_cmp(&a.name, &b.name));
+
+    match order {
+        SortOrder::Asc => ordering,
+        SortOrder::Desc => ordering.reverse(),
+    }
+}
+
+// 按查询参数过滤、排序目录条目并取出一页
+pub fn paginate(mut entries: Vec<FileEntry>, query: &ListQuery) -> Result<FileListResponse, AppError> {
+    if query.cursor.is_some() && query.offset.is_some() {
+        return Err(AppError::InvalidRequest("offset and cursor cannot be used together".to_string()));
+    }
+    let after = query.cursor.as_deref().map(|c| Cursor::decode(c, query)).transpose()?;
+
+    let extensions = parse_extensions(query.ext.as_deref());
+    entries.retain(|entry| {
+        (query.hidden || !entry.name.starts_with('.'))
+            && (entry.is_dir || extensions.is_empty() || extensions.contains(&file_extension(&entry.name)))
+    });
+    entries.sort_by(|a, b| compare(a, b, query.sort, query.order));
+
+    let total = entries.len();
+    let offset = match &after {
+        Some(after) => entries.partition_point(|entry| compare(entry, after, query.sort, query.order).is_le()),
+        None => query.offset.unwrap_or(0).min(total),
+    };
+    let limit = query.limit.map(|limit| limit.clamp(1, MAX_LIMIT));
+    let end = limit.map_or(total, |limit| (offset + limit).min(total));
+
+    let items: Vec<FileEntry> = entries.drain(offset..end).collect();
+    let has_more = end < total;
+    let next_cursor = match items.last() {
+        Some(last) if has_more => Some(Cursor::new(last, query).encode()),
+        _ => None,
+    };
+
+    Ok(FileListResponse {
+        items,
+        total,
+        offset,
+        limit,
+        has_more,
+        next_cursor,
+    })
+}
+
+// 客户端通过Accept请求头选择NDJSON流式列表
+pub fn wants_ndjson(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::ACCEPT)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.split(',').any(|t| t.split(';').next().unwrap_or("").trim() == NDJSON))
+}
+
+// 按read_dir返回的顺序逐行输出条目，不排序也不分页，服务器不在内存中保存列表；
+// 每行都是FileEntry，列出的条目与JSON列表相同，递归时只进入用户可读的目录
+pub fn stream(
+    state: Arc<AppState>,
+    user: AuthUser,
+    full_path: PathBuf,
+    rel_path: String,
+    query: &ListQuery,
+) -> Result<Response, AppError> {
+    if query.sort != ListSort::default()
+        || query.order != SortOrder::default()
+        || query.offset.is_some()
+        || query.limit.is_some()
+        || query.cursor.is_some()
+    {
+        return Err(AppError::InvalidRequest(
+            "Streaming listing does not support sorting or pagination".to_string(),
+        ));
+    }
+
+    let max_depth = if query.recursive { query.depth.map(|d| d.max(1)) } else { Some(1) };
+    let mut walker = TreeWalker::new(&state, user, max_depth)?.unsorted().all_entries();
+    if !query.hidden {
+        walker = walker.skip_hidden();
+    }
+    let extensions = parse_extensions(query.ext.as_deref());
+
+    let (tx, rx) = mpsc::channel::<io::Result<Bytes>>(32);
+    tokio::task::spawn_blocking(move || {
+        let _ = walker.walk(&full_path, &rel_path, &mut |path, _rel, metadata| {
+            let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+            if !metadata.is_dir() && !extensions.is_empty() && !extensions.contains(&file_extension(name)) {
+                return ControlFlow::Continue(());
+            }
+            let Ok(entry) = file_entry_from_metadata(path, &state.base_dir, metadata) else {
+                return ControlFlow::Continue(());
+            };
+            if send_line(&tx, &entry) {
+                ControlFlow::Continue(())
+            } else {
+                ControlFlow::Break(())
+            }
+        });
+    });
+
+    Ok(ndjson_response(rx))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{handlers::list_directory, test_support::test_state};
+
+    async fn stream_paths(state: &Arc<AppState>, query: serde_json::Value) -> Vec<String> {
+        let query: ListQuery = serde_json::from_value(query).unwrap();
+        let full_path = state.base_dir.join("docs");
+        let response = stream(state.clone(), AuthUser::anonymous(), full_path, "docs".to_string(), &query).unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let mut paths: Vec<String> = body
+            .split(|b| *b == b'\n')
+            .filter(|line| !line.is_empty())
+            .map(|line| serde_json::from_slice::<FileEntry>(line).unwrap().path)
+            .collect();
+        paths.sort();
+        paths
+    }
+
+    #[tokio::test]
+    async fn stream_lists_the_same_entries_as_json() {
+        let config = serde_json::json!({
+            "acl": { "default": ["read"], "rules": [{ "path": "docs/secret", "permissions": [] }] }
+        });
+        let (state, _dir) = test_state(config).await;
+        for path in ["docs/a.txt", "docs/.hidden", "docs/secret/s.txt", "docs/sub/b.txt"] {
+            let full_path = state.base_dir.join(path);
+            std::fs::create_dir_all(full_path.parent().unwrap()).unwrap();
+            std::fs::write(full_path, "").unwrap();
+        }
+
+        let entries = list_directory(&state.base_dir.join("docs"), &state.base_dir).await.unwrap();
+        let listed = paginate(entries, &ListQuery::default()).unwrap();
+        let mut json_paths: Vec<String> = listed.items.into_iter().map(|e| e.path).collect();
+        json_paths.sort();
+        assert_eq!(json_paths, ["docs/.hidden", "docs/a.txt", "docs/secret", "docs/sub"]);
+        assert_eq!(stream_paths(&state, serde_json::json!({})).await, json_paths);
+
+        // 递归时不进入没有读权限的目录
+        let paths = stream_paths(&state, serde_json::json!({ "recursive": true, "hidden": false })).await;
+        assert_eq!(paths, ["docs/a.txt", "docs/secret", "docs/sub", "docs/sub/b.txt"]);
+    }
+}
@@ -1,7 +1,8 @@
 use axum::{
-    routing::{get, post, delete},
+    routing::{any, get, post, delete, head},
     Router,
     extract::DefaultBodyLimit,
+    middleware,
 };
 use std::sync::Arc;
 use std::path::PathBuf;
@@ -12,76 +13,214 @@ mod handlers;
 mod models;
 mod utils;
 mod errors;
+mod tus;
+mod auth;
+mod acl;
+mod tokens;
+mod shares;
+mod archive;
+mod extract;
+mod trash;
+mod versions;
+mod webdav;
+mod xml;
+mod s3;
+mod sigv4;
+mod search;
+mod index;
+mod events;
+mod thumbnail;
+mod bulk_rename;
+mod listing;
+mod usage;
+mod checksum;
+#[cfg(test)]
+mod test_support;
 
 use handlers::*;
+use tus::TusStore;
+use auth::AuthStore;
+use acl::Acl;
+use tokens::TokenStore;
+use shares::ShareStore;
+use trash::TrashStore;
+use versions::VersionStore;
+use webdav::WebDavStore;
+use s3::S3Store;
+use search::SearchLimits;
+use index::IndexStore;
+use events::EventHub;
+use thumbnail::ThumbnailStore;
+use usage::UsageCache;
+use checksum::ChecksumStore;
 
 #[derive(Clone)]
 pub struct AppState {
     pub base_dir: PathBuf,
+    pub data_dir: PathBuf,
+    pub tus: Arc<TusStore>,
+    pub auth: Arc<AuthStore>,
+    pub acl: Arc<Acl>,
+    pub tokens: Arc<TokenStore>,
+    pub shares: Arc<ShareStore>,
+    pub trash: Arc<TrashStore>,
+    pub versions: Arc<VersionStore>,
+    pub webdav: Arc<WebDavStore>,
+    pub s3: Arc<S3Store>,
+    pub search: SearchLimits,
+    pub index: Arc<IndexStore>,
+    pub events: Arc<EventHub>,
+    pub thumbnails: Arc<ThumbnailStore>,
+    pub usage: Arc<UsageCache>,
+    pub checksums: Arc<ChecksumStore>,
+}
+
+// 根据配置创建基础目录、数据目录和各组件
+async fn build_state(config: &serde_json::Value) -> anyhow::Result<Arc<AppState>> {
+    let base_dir = config["dir"].as_str().unwrap_or("./public").to_string();
+    let data_dir = config["data_dir"].as_str().unwrap_or("./data").to_string();
+
+    // 创建基础目录和数据目录
+    fs::create_dir_all(&base_dir).await?;
+    fs::create_dir_all(&data_dir).await?;
+
+    let data_dir = PathBuf::from(data_dir);
+    let tus = TusStore::new(data_dir.join("tus"), &config["tus"]).await?;
+    let auth = AuthStore::new(&config["auth"])?;
+    if !auth.enabled() {
+        tracing::warn!("Authentication is disabled, all API routes are open");
+    }
+    let acl = Acl::new(&config["acl"])?;
+    let tokens = TokenStore::new(data_dir.join("tokens.json")).await?;
+    let shares = ShareStore::new(data_dir.join("shares.json")).await?;
+    let base_dir = PathBuf::from(base_dir);
+    let trash = TrashStore::new(&base_dir, &config["trash"]).await?;
+    let versions = VersionStore::new(data_dir.join("versions"), &config["versions"]).await?;
+    let webdav = WebDavStore::new(data_dir.join("webdav_props.json")).await?;
+    let s3 = S3Store::new(data_dir.join("s3-multipart"), data_dir.join("s3-etags"), &config["s3"]).await?;
+    let search = SearchLimits::new(&config["search"]);
+    let index = IndexStore::new(data_dir.join("index.db"), &base_dir, &config["index"])?;
+    let events = EventHub::new(&config["events"]);
+    let thumbnails = ThumbnailStore::new(data_dir.join("thumbnails"), &config["thumbnails"]).await?;
+    let usage = UsageCache::new(&config["usage"]);
+    let checksums = ChecksumStore::new(&config["checksum"]);
+
+    Ok(Arc::new(AppState {
+        base_dir,
+        data_dir,
+        tus: Arc::new(tus),
+        auth: Arc::new(auth),
+        acl: Arc::new(acl),
+        tokens: Arc::new(tokens),
+        shares: Arc::new(shares),
+        trash: Arc::new(trash),
+        versions: Arc::new(versions),
+        webdav: Arc::new(webdav),
+        s3: Arc::new(s3),
+        search,
+        index: Arc::new(index),
+        events: Arc::new(events),
+        thumbnails: Arc::new(thumbnails),
+        usage: Arc::new(usage),
+        checksums: Arc::new(checksums),
+    }))
 }
 
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     tracing_subscriber::fmt::init();
 
+    // 命令行工具：生成密码哈希，用于配置用户
+    let args: Vec<String> = std::env::args().collect();
+    if args.len() == 3 && args[1] == "hash-password" {
+        println!("{}", auth::hash_password(&args[2])?);
+        return Ok(());
+    }
+
     // 读取配置，如果不存在则使用默认值
-    let (base_dir, host, port) = match std::fs::read_to_string("config.json") {
-        Ok(config_str) => {
-            match serde_json::from_str::<serde_json::Value>(&config_str) {
-                Ok(config) => {
-                    let dir = config["dir"]
-                        .as_str()
-                        .unwrap_or("./public")
-                        .to_string();
-                    let h = config["host"]
-                        .as_str()
-                        .unwrap_or("127.0.0.1")
-                        .to_string();
-                    let p = config["port"]
-                        .as_str()
-                        .unwrap_or("8080")
-                        .to_string();
-                    (dir, h, p)
-                }
-                Err(_) => {
-                    eprintln!("警告: config.json格式错误，使用默认配置");
-                    ("./public".to_string(), "127.0.0.1".to_string(), "8080".to_string())
-                }
+    let config = match std::fs::read_to_string("config.json") {
+        Ok(config_str) => match serde_json::from_str::<serde_json::Value>(&config_str) {
+            Ok(config) => config,
+            Err(_) => {
+                eprintln!("警告: config.json格式错误，使用默认配置");
+                serde_json::Value::Null
             }
-        }
+        },
         Err(_) => {
             eprintln!("警告: 未找到config.json，使用默认配置");
-            ("./public".to_string(), "127.0.0.1".to_string(), "8080".to_string())
+            serde_json::Value::Null
         }
     };
 
-    // 创建基础目录
-    fs::create_dir_all(&base_dir).await?;
+    let host = config["host"].as_str().unwrap_or("127.0.0.1").to_string();
+    let port = config["port"].as_str().unwrap_or("8080").to_string();
 
-    let state = AppState {
-        base_dir: PathBuf::from(base_dir),
-    };
+    let webdav_enabled = config["webdav"]["enabled"].as_bool().unwrap_or(true);
+
+    let state = build_state(&config).await?;
+
+    // 定期清理过期的断点续传会话
+    tus::spawn_cleanup_task(state.clone());
+    // 定期清理超过保留时间的回收站条目
+    trash::spawn_sweep_task(state.clone());
+    // 定期清理过期的S3分片上传
+    if state.s3.enabled() {
+        s3::spawn_cleanup_task(state.clone());
+    }
+    // 后台构建元数据索引
+    index::spawn_index_task(state.clone());
+    // 监听文件变化，更新索引并通知订阅者
+    events::spawn_watcher(state.clone());
 
     // 构建路由
-    let app = Router::new()
-        // 前端页面
-        .route("/", get(handlers::index))
-        .route("/index.html", get(handlers::index))
-        .route("/style.css", get(handlers::style))
-        .route("/app.js", get(handlers::app_js))
-        // API路由 - 更具体的路由放在前面
+    // 需要认证的API路由 - 更具体的路由放在前面
+    let api = Router::new()
+        .route("/api/auth/logout", post(auth::logout))
+        .route("/api/auth/me", get(auth::current_user))
+        .route("/api/tokens", get(tokens::list_tokens).post(tokens::create_token))
+        .route("/api/tokens/{id}", delete(tokens::revoke_token))
+        .route("/api/shares", get(shares::list_shares).post(shares::create_share))
+        .route("/api/shares/{token}", delete(shares::revoke_share))
         .route("/api/batch-delete", post(batch_delete))
         .route("/api/batch-move", post(batch_move))
         .route("/api/batch-copy", post(batch_copy))
+        .route("/api/rename", post(rename))
+        .route("/api/batch-rename", post(batch_rename))
+        .route("/api/bulk-rename", post(bulk_rename::bulk_rename))
         .route("/api/mkdir", post(create_directory))
+        .route("/api/archive", get(archive::archive_root).post(archive::archive_selection))
+        .route("/api/extract", post(extract::extract_archive))
+        // 文件搜索
+        .route("/api/search", get(search::search_files))
+        .route("/api/search/content", get(search::search_content))
+        // 元数据索引
+        .route("/api/index/rebuild", post(index::rebuild_index))
+        .route("/api/index/check", get(index::check_index))
+        .route("/api/dir-size", get(index::dir_size_root))
+        .route("/api/dir-size/{*path}", get(index::dir_size))
+        .route("/api/du", get(usage::disk_usage_root))
+        .route("/api/du/{*path}", get(usage::disk_usage))
+        .route("/api/tree", get(usage::tree_root))
+        .route("/api/tree/{*path}", get(usage::tree))
+        // 文件变化通知
+        .route("/api/events", get(events::subscribe))
+        // 回收站
+        .route("/api/trash", get(trash::list_trash).delete(trash::empty_trash))
+        .route("/api/trash/restore", post(trash::restore_trash))
+        .route("/api/trash/{id}", delete(trash::purge_trash_entry))
+        // 历史版本
+        .route("/api/versions/{*path}", get(versions::get_versions).post(versions::restore_version))
         .route("/api/files", get(list_files_root))
         .route("/api/upload", post(upload_file_root))
         // 文件列表
         .route("/api/files/{*path}", get(list_files))
         // 文件预览
         .route("/api/preview/{*path}", get(preview_file))
+        .route("/api/thumbnail/{*path}", get(thumbnail::thumbnail))
         // 文件下载
         .route("/api/download/{*path}", get(handlers::download_file))
+        // 打包下载
+        .route("/api/archive/{*path}", get(archive::archive_path))
         // 文件上传
         .route("/api/upload/{*path}", post(upload_file))
         // 文件删除
@@ -90,6 +229,49 @@ async fn main() -> anyhow::Result<()> {
         .route("/api/delete-dir/{*path}", delete(delete_directory))
         // 文件信息
         .route("/api/info/{*path}", get(get_file_info))
+        .route("/api/checksum/{*path}", get(checksum::checksum))
+        // 断点续传（tus 1.0）
+        .route("/api/tus", post(tus::create_upload).options(tus::options))
+        .route(
+            "/api/tus/{id}",
+            head(tus::upload_offset)
+                .patch(tus::upload_chunk)
+                .delete(tus::terminate_upload)
+                .options(tus::options),
+        )
+        .route_layer(middleware::from_fn_with_state(state.clone(), auth::require_auth));
+
+    // WebDAV（挂载在/dav下，支持Basic认证）
+    let dav = if webdav_enabled {
+        Router::new()
+            .route("/dav", any(webdav::handle))
+            .route("/dav/", any(webdav::handle))
+            .route("/dav/{*path}", any(webdav::handle))
+            .route_layer(middleware::from_fn_with_state(state.clone(), webdav::require_dav_auth))
+    } else {
+        Router::new()
+    };
+
+    // S3兼容接口使用独立端口，路径风格寻址（/{bucket}/{key}）
+    let s3_app = Router::new()
+        .fallback(s3::handle)
+        .layer(middleware::from_fn_with_state(state.clone(), s3::require_signature))
+        .with_state(state.clone());
+    let s3_port = state.s3.port.clone();
+    let s3_enabled = state.s3.enabled();
+
+    let app = Router::new()
+        // 前端页面
+        .route("/", get(handlers::index))
+        .route("/index.html", get(handlers::index))
+        .route("/style.css", get(handlers::style))
+        .route("/app.js", get(handlers::app_js))
+        // 登录不需要认证
+        .route("/api/auth/login", post(auth::login))
+        // 分享链接（通过链接令牌和可选密码访问）
+        .route("/s/{token}", get(shares::share_root).post(shares::share_upload_root))
+        .route("/s/{token}/{*path}", get(shares::share_path).post(shares::share_upload_path))
+        .merge(api)
         // 增加body大小限制到1GB
         .layer(DefaultBodyLimit::max(1024 * 1024 * 1024))
         .layer(
@@ -98,7 +280,19 @@ async fn main() -> anyhow::Result<()> {
                 .allow_methods(Any)
                 .allow_headers(Any),
         )
-        .with_state(Arc::new(state));
+        // WebDAV客户端会发送OPTIONS请求，需放在CORS层之外以免被预检处理拦截
+        .merge(dav)
+        .with_state(state);
+
+    if s3_enabled {
+        let s3_listener = tokio::net::TcpListener::bind(format!("{}:{}", host, s3_port)).await?;
+        tracing::info!("S3 endpoint running on http://{}:{}", host, s3_port);
+        tokio::spawn(async move {
+            if let Err(e) = axum::serve(s3_listener, s3_app).await {
+                tracing::error!("S3 endpoint stopped: {}", e);
+            }
+        });
+    }
 
     let listener = tokio::net::TcpListener::bind(format!("{}:{}", host, port)).await?;
     tracing::info!("Server running on http://{}:{}", host, port);
@@ -1,4 +1,6 @@
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use crate::acl::Permission;
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct FileEntry {
@@ -13,7 +15,77 @@ pub struct FileEntry {
 #[derive(Debug, Serialize, Deserialize)]
 pub struct FileListResponse {
     pub items: Vec<FileEntry>,
+    // 过滤后的条目总数，不受分页影响
     pub total: usize,
+    // 本页第一项在完整列表中的位置
+    pub offset: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit: Option<usize>,
+    pub has_more: bool,
+    // 传给下一次请求的cursor参数以获取下一页
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
+}
+
+// 目录列表的排序字段
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ListSort {
+    #[default]
+    Name,
+    Size,
+    Modified,
+    Created,
+    // 按扩展名
+    Type,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SortOrder {
+    #[default]
+    Asc,
+    Desc,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ListQuery {
+    #[serde(default)]
+    pub sort: ListSort,
+    #[serde(default)]
+    pub order: SortOrder,
+    // 是否包含以点开头的隐藏文件
+    #[serde(default = "default_true")]
+    pub hidden: bool,
+    // 逗号分隔的扩展名列表，如 "jpg,png"，只筛选文件，目录始终保留
+    pub ext: Option<String>,
+    pub offset: Option<usize>,
+    pub limit: Option<usize>,
+    pub cursor: Option<String>,
+    // 以下两项只用于NDJSON流式列表：是否递归列出子目录，以及递归的最大深度
+    #[serde(default)]
+    pub recursive: bool,
+    pub depth: Option<usize>,
+}
+
+impl Default for ListQuery {
+    fn default() -> Self {
+        ListQuery {
+            sort: ListSort::default(),
+            order: SortOrder::default(),
+            hidden: true,
+            ext: None,
+            offset: None,
+            limit: None,
+            cursor: None,
+            recursive: false,
+            depth: None,
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -25,6 +97,39 @@ pub struct FileInfoResponse {
     pub modified: String,
     pub created: String,
     pub mime_type: Option<String>,
+    // 通过checksum参数请求时返回的文件摘要（十六进制）
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub checksums: Option<BTreeMap<HashAlgorithm, String>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FileInfoQuery {
+    // 逗号分隔的摘要算法，如 "sha256,md5"
+    pub checksum: Option<String>,
+}
+
+// 文件摘要算法
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum HashAlgorithm {
+    Md5,
+    Sha1,
+    Sha256,
+    Blake3,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChecksumQuery {
+    // 逗号分隔的摘要算法，不设置时计算全部算法
+    pub algorithms: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChecksumResponse {
+    pub path: String,
+    pub size: u64,
+    pub modified: String,
+    pub checksums: BTreeMap<HashAlgorithm, String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -42,12 +147,538 @@ pub struct BatchDeleteRequest {
 pub struct BatchMoveRequest {
     pub paths: Vec<String>,
     pub destination: String,
+    #[serde(default)]
+    pub on_conflict: TransferConflict,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct BatchCopyRequest {
     pub paths: Vec<String>,
     pub destination: String,
+    #[serde(default)]
+    pub on_conflict: TransferConflict,
+}
+
+// 批量移动/复制时目标位置已有同名条目的处理方式
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum TransferConflict {
+    #[default]
+    Fail,
+    Skip,
+    Overwrite,
+    // 在名称后追加序号，如 a (1).txt
+    Rename,
+    // 源和目标都是目录时合并内容，目录内同名的文件被覆盖；否则同overwrite
+    Merge,
+}
+
+// 批量移动/复制中单个条目的结果
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum TransferOutcome {
+    Moved,
+    Copied,
+    Skipped,
+    Failed,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TransferItem {
+    pub path: String,
+    // 实际写入的位置（相对路径），失败或跳过时为空
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target: Option<String>,
+    pub outcome: TransferOutcome,
+    // 目标已存在时应用的处理方式，无冲突时为空
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub policy: Option<TransferConflict>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+// 重命名时目标名称已被占用的处理方式
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum RenameConflict {
+    #[default]
+    Fail,
+    Overwrite,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RenameRequest {
+    pub path: String,
+    pub new_name: String,
+    #[serde(default)]
+    pub on_conflict: RenameConflict,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RenameItem {
+    pub path: String,
+    pub new_name: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BatchRenameRequest {
+    pub items: Vec<RenameItem>,
+    #[serde(default)]
+    pub on_conflict: RenameConflict,
+}
+
+// 批量重命名
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum CaseConversion {
+    #[default]
+    None,
+    Lower,
+    Upper,
+    // 主名中每个单词首字母大写
+    Title,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum BulkRenameSort {
+    Name,
+    Modified,
+}
+
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct BulkRenameRule {
+    // 匹配文件名的正则表达式，不匹配的条目保持不变
+    pub find: Option<String>,
+    // 新名称模板，可使用 $1 等捕获组和 {n}、{date} 等变量
+    pub replace: Option<String>,
+    #[serde(default)]
+    pub ignore_case: bool,
+    #[serde(default)]
+    pub case: CaseConversion,
+    // 序号的起始值和步长
+    pub start: Option<u64>,
+    pub step: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BulkRenameRequest {
+    #[serde(default)]
+    pub paths: Vec<String>,
+    // 重命名目录中的直接子项（与paths二选一）
+    pub directory: Option<String>,
+    #[serde(default)]
+    pub include_dirs: bool,
+    pub sort: Option<BulkRenameSort>,
+    #[serde(default)]
+    pub rule: BulkRenameRule,
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum BulkRenameStatus {
+    Rename,
+    Unchanged,
+    Conflict,
+    Invalid,
+    Denied,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BulkRenameItem {
+    pub path: String,
+    pub new_path: String,
+    pub status: BulkRenameStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BulkRenameResponse {
+    pub dry_run: bool,
+    pub items: Vec<BulkRenameItem>,
+    // 重命名（或计划重命名）的条目数
+    pub renamed: usize,
+    // 无法重命名的条目数
+    pub conflicts: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LoginRequest {
+    pub username: String,
+    pub password: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LoginResponse {
+    pub token: String,
+    pub username: String,
+    pub expires: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreateTokenRequest {
+    pub label: String,
+    #[serde(default)]
+    pub path: String,
+    pub permissions: Vec<Permission>,
+    pub user: Option<String>,
+    pub expires_at: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TokenInfo {
+    pub id: String,
+    pub label: String,
+    pub user: String,
+    pub path: String,
+    pub permissions: Vec<Permission>,
+    pub created_by: String,
+    pub created: String,
+    pub expires: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreatedToken {
+    pub token: String,
+    #[serde(flatten)]
+    pub info: TokenInfo,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ShareMode {
+    // 只读：下载文件或浏览目录
+    #[default]
+    Read,
+    // 收件箱：只允许上传
+    Upload,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CreateShareRequest {
+    pub path: String,
+    #[serde(default)]
+    pub mode: ShareMode,
+    pub password: Option<String>,
+    pub expires_at: Option<String>,
+    pub max_downloads: Option<u64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ShareInfo {
+    pub token: String,
+    pub url: String,
+    pub path: String,
+    pub is_dir: bool,
+    pub mode: ShareMode,
+    pub has_password: bool,
+    pub expires: Option<String>,
+    pub max_downloads: Option<u64>,
+    pub downloads: u64,
+    pub created_by: String,
+    pub created: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ArchiveFormat {
+    #[default]
+    #[serde(rename = "zip")]
+    Zip,
+    #[serde(rename = "tar.gz")]
+    TarGz,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ArchiveQuery {
+    #[serde(default)]
+    pub format: ArchiveFormat,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ArchiveRequest {
+    pub paths: Vec<String>,
+    #[serde(default)]
+    pub format: ArchiveFormat,
+    pub name: Option<String>,
+}
+
+// 目标已存在时的处理方式
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ConflictPolicy {
+    #[default]
+    Skip,
+    Overwrite,
+    Rename,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ExtractRequest {
+    pub path: String,
+    pub destination: String,
+    #[serde(default)]
+    pub on_conflict: ConflictPolicy,
+}
+
+// 回收站条目
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TrashEntry {
+    pub id: String,
+    pub name: String,
+    pub original_path: String,
+    pub deleted_at: String,
+    pub deleted_by: String,
+    pub is_dir: bool,
+    pub size: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RestoreRequest {
+    pub ids: Vec<String>,
+    #[serde(default)]
+    pub on_conflict: ConflictPolicy,
+}
+
+// 文件历史版本
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VersionInfo {
+    pub id: String,
+    pub size: u64,
+    pub modified: String,
+    pub archived: String,
+    pub archived_by: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VersionQuery {
+    pub version: Option<String>,
+}
+
+// 文件名匹配方式
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum MatchMode {
+    #[default]
+    Substring,
+    Glob,
+    Regex,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum EntryKind {
+    File,
+    Dir,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SearchQuery {
+    #[serde(default)]
+    pub path: String,
+    #[serde(default)]
+    pub q: String,
+    #[serde(default)]
+    pub mode: MatchMode,
+    #[serde(default)]
+    pub case_sensitive: bool,
+    #[serde(rename = "type")]
+    pub kind: Option<EntryKind>,
+    pub min_size: Option<u64>,
+    pub max_size: Option<u64>,
+    pub modified_after: Option<String>,
+    pub modified_before: Option<String>,
+    // 逗号分隔的扩展名列表，如 "jpg,png"
+    pub ext: Option<String>,
+    pub limit: Option<usize>,
+    pub depth: Option<usize>,
+}
+
+// 流式搜索结果的最后一行
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SearchSummary {
+    pub done: bool,
+    pub matched: usize,
+    pub truncated: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ContentSearchQuery {
+    #[serde(default)]
+    pub path: String,
+    pub q: String,
+    // 为true时q按正则表达式匹配，否则按字面文本匹配
+    #[serde(default)]
+    pub regex: bool,
+    #[serde(default)]
+    pub case_sensitive: bool,
+    pub ext: Option<String>,
+    pub limit: Option<usize>,
+    pub depth: Option<usize>,
+}
+
+// 内容搜索的一条匹配
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ContentMatch {
+    pub path: String,
+    pub line: usize,
+    pub snippet: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ContentSearchSummary {
+    pub done: bool,
+    pub matched: usize,
+    pub files_scanned: usize,
+    pub truncated: bool,
+    pub timed_out: bool,
+}
+
+// 索引一致性检查结果，各列表最多列出前100个路径
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct IndexReport {
+    // 磁盘上的条目数
+    pub entries: usize,
+    // 磁盘上存在但索引中缺失
+    pub missing: Vec<String>,
+    pub missing_count: usize,
+    // 大小、修改时间或类型与磁盘不一致
+    pub stale: Vec<String>,
+    pub stale_count: usize,
+    // 索引中存在但磁盘上已不存在
+    pub extra: Vec<String>,
+    pub extra_count: usize,
+    pub repaired: bool,
+    pub duration_ms: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct IndexCheckQuery {
+    #[serde(default)]
+    pub repair: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DirSizeResponse {
+    pub path: String,
+    pub size: u64,
+    pub files: u64,
+    pub dirs: u64,
+    // 结果是否来自索引
+    pub indexed: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DiskUsageEntry {
+    pub name: String,
+    pub path: String,
+    pub size: u64,
+    pub files: u64,
+    pub dirs: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DiskUsageResponse {
+    pub path: String,
+    pub size: u64,
+    pub files: u64,
+    pub dirs: u64,
+    pub indexed: bool,
+    // 各个子目录的占用，按大小降序排列
+    pub children: Vec<DiskUsageEntry>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TreeQuery {
+    pub depth: Option<usize>,
+    // 是否统计各目录的总大小、文件数和子目录数
+    #[serde(default)]
+    pub usage: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TreeNode {
+    pub name: String,
+    pub path: String,
+    pub is_dir: bool,
+    // 文件的大小；目录只在usage=true时为目录下所有文件的总大小，否则为0
+    pub size: u64,
+    pub modified: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub files: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dirs: Option<u64>,
+    // 超过深度限制的目录没有此字段
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub children: Option<Vec<TreeNode>>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TreeResponse {
+    pub tree: TreeNode,
+    pub nodes: usize,
+    // 节点数达到上限，部分条目未列出
+    pub truncated: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ThumbnailQuery {
+    // 缩略图的最大边长（像素）
+    pub size: Option<u32>,
+}
+
+// 文件变化通知
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeKind {
+    Created,
+    Modified,
+    Deleted,
+    Renamed,
+}
+
+impl ChangeKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ChangeKind::Created => "created",
+            ChangeKind::Modified => "modified",
+            ChangeKind::Deleted => "deleted",
+            ChangeKind::Renamed => "renamed",
+        }
+    }
+
+    // 写入文件后根据写入前是否已存在区分新建和修改
+    pub fn written(existed: bool) -> Self {
+        if existed {
+            ChangeKind::Modified
+        } else {
+            ChangeKind::Created
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ChangeEvent {
+    #[serde(rename = "type")]
+    pub kind: ChangeKind,
+    pub path: String,
+    // 重命名前的路径
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from: Option<String>,
+    // 通过本服务修改时为操作用户，外部修改时为空
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub user: Option<String>,
+    pub time: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EventsQuery {
+    #[serde(default)]
+    pub path: String,
+    #[serde(default)]
+    pub recursive: bool,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
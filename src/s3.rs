@@ -0,0 +1,1454 @@
+use axum::{
+    body::Body,
+    extract::{Request, State},
+    http::{header, HeaderMap, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use futures_util::StreamExt;
+use md5::Md5;
+use percent_encoding::percent_decode_str;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tokio::fs;
+use tokio::io::AsyncWriteExt;
+
+use crate::{
+    acl::{Acl, Permission},
+    auth::AuthUser,
+    errors::AppError,
+    events,
+    handlers::send_file,
+    models::ChangeKind,
+    sigv4::{self, ChunkedDecoder, PayloadCheck, SignedRequest},
+    utils::*,
+    xml::{self, XmlElement},
+    AppState,
+};
+
+const S3_NS: &str = "http://s3.amazonaws.com/doc/2006-03-01/";
+const MAX_KEYS: usize = 1000;
+const MAX_PART_NUMBER: u32 = 10000;
+// DeleteObjects、CompleteMultipartUpload等XML请求体的大小上限
+const MAX_XML_BODY: usize = 4 * 1024 * 1024;
+const EMPTY_MD5: &str = "d41d8cd98f00b204e9800998ecf8427e";
+
+// 未实现的子资源，直接返回NotImplemented而不是当作普通请求处理
+const UNSUPPORTED_SUBRESOURCES: &[&str] = &[
+    "acl", "policy", "versioning", "versions", "tagging", "lifecycle", "cors", "website",
+    "replication", "encryption", "object-lock", "retention", "legal-hold", "notification",
+    "logging", "requestPayment", "accelerate", "analytics", "inventory", "metrics",
+    "ownershipControls", "publicAccessBlock", "attributes", "torrent", "restore", "select",
+];
+
+// S3错误，以XML格式返回
+#[derive(Debug)]
+pub struct S3Error {
+    status: StatusCode,
+    pub(crate) code: &'static str,
+    message: String,
+}
+
+impl S3Error {
+    pub fn new(status: StatusCode, code: &'static str, message: impl Into<String>) -> Self {
+        S3Error {
+            status,
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+impl IntoResponse for S3Error {
+    fn into_response(self) -> Response {
+        let body = format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<Error><Code>{}</Code><Message>{}</Message></Error>",
+            self.code,
+            xml::escape(&self.message)
+        );
+        (self.status, [(header::CONTENT_TYPE, "application/xml")], body).into_response()
+    }
+}
+
+impl From<AppError> for S3Error {
+    fn from(err: AppError) -> Self {
+        let (status, code) = match &err {
+            AppError::NotFound(_) => (StatusCode::NOT_FOUND, "NoSuchKey"),
+            AppError::InvalidPath(_) | AppError::InvalidRequest(_) => {
+                (StatusCode::BAD_REQUEST, "InvalidArgument")
+            }
+            AppError::PermissionDenied(_) | AppError::Unauthorized(_) => {
+                (StatusCode::FORBIDDEN, "AccessDenied")
+            }
+            AppError::Conflict(_) | AppError::Locked(_) => (StatusCode::CONFLICT, "OperationAborted"),
+            AppError::PreconditionFailed(_) => (StatusCode::PRECONDITION_FAILED, "PreconditionFailed"),
+            AppError::PayloadTooLarge(_) => (StatusCode::BAD_REQUEST, "EntityTooLarge"),
+            AppError::MethodNotAllowed(_) => (StatusCode::METHOD_NOT_ALLOWED, "MethodNotAllowed"),
+            AppError::TooManyRequests(_) => (StatusCode::SERVICE_UNAVAILABLE, "SlowDown"),
+            AppError::UnsupportedMediaType(_) => (StatusCode::BAD_REQUEST, "InvalidRequest"),
+            AppError::IoError(_) => (StatusCode::INTERNAL_SERVER_ERROR, "InternalError"),
+        };
+        S3Error::new(status, code, err.to_string())
+    }
+}
+
+impl From<std::io::Error> for S3Error {
+    fn from(err: std::io::Error) -> Self {
+        AppError::from(err).into()
+    }
+}
+
+fn no_such_bucket() -> S3Error {
+    S3Error::new(StatusCode::NOT_FOUND, "NoSuchBucket", "The specified bucket does not exist")
+}
+
+fn no_such_key() -> S3Error {
+    S3Error::new(StatusCode::NOT_FOUND, "NoSuchKey", "The specified key does not exist")
+}
+
+fn no_such_upload() -> S3Error {
+    S3Error::new(StatusCode::NOT_FOUND, "NoSuchUpload", "The specified upload does not exist")
+}
+
+fn invalid_argument(message: impl Into<String>) -> S3Error {
+    S3Error::new(StatusCode::BAD_REQUEST, "InvalidArgument", message)
+}
+
+fn not_implemented() -> S3Error {
+    S3Error::new(
+        StatusCode::NOT_IMPLEMENTED,
+        "NotImplemented",
+        "A header or query parameter you provided implies functionality that is not implemented",
+    )
+}
+
+// Access Key配置，user为对应的用户名（用于访问控制）
+#[derive(Debug, Deserialize)]
+struct S3Credential {
+    access_key: String,
+    secret_key: String,
+    #[serde(default)]
+    user: Option<String>,
+}
+
+// 分片上传会话，保存在 {multipart_dir}/{id}/upload.json，分片为 {序号}.part
+#[derive(Debug, Serialize, Deserialize)]
+struct MultipartUpload {
+    id: String,
+    bucket: String,
+    key: String,
+    user: String,
+    initiated: i64,
+}
+
+pub struct S3Store {
+    enabled: bool,
+    pub port: String,
+    region: String,
+    credentials: HashMap<String, S3Credential>,
+    multipart_dir: PathBuf,
+    multipart_expiry_secs: i64,
+    // PutObject写入的对象的内容MD5，按路径哈希分文件保存
+    etag_dir: PathBuf,
+}
+
+// 保存的ETag只在文件大小和修改时间（纳秒）都未变化时有效，
+// 通过其他途径修改过的文件回退到file_etag
+#[derive(Serialize, Deserialize)]
+struct StoredEtag {
+    size: u64,
+    modified: i64,
+    etag: String,
+}
+
+impl S3Store {
+    pub async fn new(multipart_dir: PathBuf, etag_dir: PathBuf, config: &serde_json::Value) -> anyhow::Result<Self> {
+        let enabled = config["enabled"].as_bool().unwrap_or(false);
+        let credentials: Vec<S3Credential> = match config.get("credentials") {
+            Some(credentials) => serde_json::from_value(credentials.clone())?,
+            None => Vec::new(),
+        };
+        if enabled && credentials.is_empty() {
+            anyhow::bail!("s3.credentials must contain at least one access key");
+        }
+        if enabled {
+            fs::create_dir_all(&multipart_dir).await?;
+            fs::create_dir_all(&etag_dir).await?;
+        }
+
+        Ok(S3Store {
+            enabled,
+            port: config["port"].as_str().unwrap_or("9000").to_string(),
+            region: config["region"].as_str().unwrap_or("us-east-1").to_string(),
+            credentials: credentials
+                .into_iter()
+                .map(|c| (c.access_key.clone(), c))
+                .collect(),
+            multipart_dir,
+            multipart_expiry_secs: config["multipart_expiry_hours"].as_i64().unwrap_or(24) * 3600,
+            etag_dir,
+        })
+    }
+
+    fn etag_path(&self, rel_path: &str) -> PathBuf {
+        self.etag_dir.join(hex::encode(Sha256::digest(rel_path.as_bytes())))
+    }
+
+    async fn save_etag(&self, rel_path: &str, metadata: &std::fs::Metadata, etag: &str) {
+        let stored = StoredEtag {
+            size: metadata.len(),
+            modified: modified_nanos(metadata),
+            etag: etag.to_string(),
+        };
+        let content = serde_json::to_vec(&stored).unwrap_or_default();
+        if let Err(e) = write_file_atomic(&self.etag_path(rel_path), &content).await {
+            tracing::warn!("Failed to save ETag of {}: {}", rel_path, e);
+        }
+    }
+
+    async fn forget_etag(&self, rel_path: &str) {
+        let _ = fs::remove_file(self.etag_path(rel_path)).await;
+    }
+
+    // 对象的ETag：PutObject保存的MD5仍然有效时使用它，否则由修改时间和大小生成
+    async fn object_etag(&self, rel_path: &str, metadata: &std::fs::Metadata) -> String {
+        if metadata.is_file() {
+            if let Ok(content) = fs::read(self.etag_path(rel_path)).await {
+                if let Ok(stored) = serde_json::from_slice::<StoredEtag>(&content) {
+                    if stored.size == metadata.len() && stored.modified == modified_nanos(metadata) {
+                        return stored.etag;
+                    }
+                }
+            }
+        }
+        file_etag(metadata)
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    fn upload_dir(&self, id: &str) -> PathBuf {
+        self.multipart_dir.join(id)
+    }
+
+    async fn load_upload(&self, id: &str, bucket: &str, key: &str) -> Result<MultipartUpload, S3Error> {
+        if id.len() != 32 || !id.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(no_such_upload());
+        }
+        let content = fs::read_to_string(self.upload_dir(id).join("upload.json"))
+            .await
+            .map_err(|_| no_such_upload())?;
+        let upload: MultipartUpload = serde_json::from_str(&content).map_err(|_| no_such_upload())?;
+        if upload.bucket != bucket || upload.key != key {
+            return Err(no_such_upload());
+        }
+        Ok(upload)
+    }
+
+    async fn uploads(&self) -> Vec<MultipartUpload> {
+        let mut uploads = Vec::new();
+        let mut entries = match fs::read_dir(&self.multipart_dir).await {
+            Ok(entries) => entries,
+            Err(_) => return uploads,
+        };
+        while let Ok(Some(entry)) = entries.next_entry().await {
+            if let Ok(content) = fs::read_to_string(entry.path().join("upload.json")).await {
+                if let Ok(upload) = serde_json::from_str::<MultipartUpload>(&content) {
+                    uploads.push(upload);
+                }
+            }
+        }
+        uploads
+    }
+
+    // 删除超过有效期仍未完成的分片上传
+    pub async fn cleanup_expired(&self) -> usize {
+        let cutoff = Utc::now().timestamp() - self.multipart_expiry_secs;
+        let mut removed = 0;
+        for upload in self.uploads().await {
+            if upload.initiated < cutoff
+                && fs::remove_dir_all(self.upload_dir(&upload.id)).await.is_ok()
+            {
+                removed += 1;
+            }
+        }
+        removed
+    }
+}
+
+pub fn spawn_cleanup_task(state: Arc<AppState>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(3600));
+        loop {
+            interval.tick().await;
+            let removed = state.s3.cleanup_expired().await;
+            if removed > 0 {
+                tracing::info!("Removed {} expired S3 multipart uploads", removed);
+            }
+        }
+    });
+}
+
+// 认证中间件：校验SigV4签名，并把Access Key对应的用户放入请求扩展
+pub async fn require_signature(
+    State(state): State<Arc<AppState>>,
+    mut req: Request,
+    next: Next,
+) -> Response {
+    match authenticate(&state, &req) {
+        Ok((user, signed)) => {
+            req.extensions_mut().insert(user);
+            req.extensions_mut().insert(signed);
+            next.run(req).await
+        }
+        Err(e) => {
+            tracing::warn!("Rejected S3 request {} {}: {}", req.method(), req.uri().path(), e.message);
+            e.into_response()
+        }
+    }
+}
+
+fn authenticate(state: &AppState, req: &Request) -> Result<(AuthUser, SignedRequest), S3Error> {
+    if !sigv4::is_signed(req.uri(), req.headers()) {
+        return Err(S3Error::new(StatusCode::FORBIDDEN, "AccessDenied", "Anonymous access is not allowed"));
+    }
+
+    let signed = sigv4::verify(req.method(), req.uri(), req.headers(), |access_key| {
+        state.s3.credentials.get(access_key).map(|c| c.secret_key.clone())
+    })?;
+
+    let user = if state.auth.enabled() {
+        state.s3.credentials[&signed.access_key]
+            .user
+            .as_deref()
+            .and_then(|username| state.auth.user(username))
+            .ok_or_else(|| {
+                S3Error::new(
+                    StatusCode::FORBIDDEN,
+                    "InvalidAccessKeyId",
+                    "The access key is not bound to a valid user",
+                )
+            })?
+    } else {
+        AuthUser::anonymous()
+    };
+
+    Ok((user, signed))
+}
+
+// 单个S3请求的上下文
+struct S3Context<'a> {
+    state: &'a AppState,
+    user: &'a AuthUser,
+    headers: HeaderMap,
+    query: HashMap<String, String>,
+    payload: PayloadCheck,
+}
+
+impl S3Context<'_> {
+    fn query(&self, name: &str) -> Option<&str> {
+        self.query.get(name).map(String::as_str)
+    }
+
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers.get(name).and_then(|v| v.to_str().ok())
+    }
+}
+
+// S3请求入口：路径风格寻址，/{bucket}/{key}
+pub async fn handle(State(state): State<Arc<AppState>>, user: AuthUser, req: Request) -> Response {
+    let (parts, body) = req.into_parts();
+    let payload = parts
+        .extensions
+        .get::<SignedRequest>()
+        .map(|signed| signed.payload.clone())
+        .unwrap_or(PayloadCheck::Unsigned);
+    let ctx = S3Context {
+        state: &state,
+        user: &user,
+        query: sigv4::query_pairs(&parts.uri).into_iter().collect(),
+        headers: parts.headers,
+        payload,
+    };
+
+    match dispatch(&ctx, parts.method.as_str(), parts.uri.path(), body).await {
+        Ok(response) => response,
+        Err(e) => e.into_response(),
+    }
+}
+
+async fn dispatch(ctx: &S3Context<'_>, method: &str, path: &str, body: Body) -> Result<Response, S3Error> {
+    if UNSUPPORTED_SUBRESOURCES.iter().any(|name| ctx.query.contains_key(*name)) {
+        return Err(not_implemented());
+    }
+
+    let path = path.trim_start_matches('/');
+    if path.is_empty() {
+        return match method {
+            "GET" => list_buckets(ctx).await,
+            _ => Err(S3Error::new(StatusCode::METHOD_NOT_ALLOWED, "MethodNotAllowed", "Method not allowed")),
+        };
+    }
+
+    let (bucket, key) = path.split_once('/').unwrap_or((path, ""));
+    let bucket = percent_decode_str(bucket).decode_utf8_lossy().to_string();
+    let key = percent_decode_str(key).decode_utf8_lossy().to_string();
+
+    if key.is_empty() {
+        return match method {
+            "GET" if ctx.query.contains_key("uploads") => list_multipart_uploads(ctx, &bucket).await,
+            "GET" if ctx.query.contains_key("location") => bucket_location(ctx, &bucket).await,
+            "GET" => list_objects(ctx, &bucket).await,
+            "HEAD" => head_bucket(ctx, &bucket).await,
+            "PUT" => create_bucket(ctx, &bucket).await,
+            "DELETE" => delete_bucket(ctx, &bucket).await,
+            "POST" if ctx.query.contains_key("delete") => delete_objects(ctx, &bucket, body).await,
+            _ => Err(not_implemented()),
+        };
+    }
+
+    let upload_id = ctx.query("uploadId").map(str::to_string);
+    match (method, upload_id) {
+        ("GET", Some(id)) => list_parts(ctx, &bucket, &key, &id).await,
+        ("GET", None) => get_object(ctx, &bucket, &key).await,
+        ("HEAD", None) => head_object(ctx, &bucket, &key).await,
+        ("PUT", Some(id)) => upload_part(ctx, &bucket, &key, &id, body).await,
+        ("PUT", None) if ctx.headers.contains_key("x-amz-copy-source") => {
+            copy_object(ctx, &bucket, &key).await
+        }
+        ("PUT", None) => put_object(ctx, &bucket, &key, body).await,
+        ("DELETE", Some(id)) => abort_multipart_upload(ctx, &bucket, &key, &id).await,
+        ("DELETE", None) => delete_object(ctx, &bucket, &key).await,
+        ("POST", None) if ctx.query.contains_key("uploads") => {
+            create_multipart_upload(ctx, &bucket, &key).await
+        }
+        ("POST", Some(id)) => complete_multipart_upload(ctx, &bucket, &key, &id, body).await,
+        _ => Err(not_implemented()),
+    }
+}
+
+fn xml_response(body: String) -> Response {
+    (
+        StatusCode::OK,
+        [(header::CONTENT_TYPE, "application/xml")],
+        format!("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n{}", body),
+    )
+        .into_response()
+}
+
+// S3使用的ISO 8601时间格式
+fn iso8601(time: SystemTime) -> String {
+    DateTime::<Utc>::from(time)
+        .format("%Y-%m-%dT%H:%M:%S%.3fZ")
+        .to_string()
+}
+
+// 存储桶对应根目录下的一级目录
+fn bucket_path(state: &AppState, bucket: &str) -> Result<PathBuf, S3Error> {
+    if !is_valid_file_name(bucket) {
+        return Err(S3Error::new(StatusCode::BAD_REQUEST, "InvalidBucketName", "The specified bucket is not valid"));
+    }
+    let full_path = state.base_dir.join(bucket);
+    if !is_safe_path(&state.base_dir, &full_path) {
+        return Err(no_such_bucket());
+    }
+    Ok(full_path)
+}
+
+async fn existing_bucket(state: &AppState, bucket: &str) -> Result<PathBuf, S3Error> {
+    let full_path = bucket_path(state, bucket)?;
+    match fs::metadata(&full_path).await {
+        Ok(metadata) if metadata.is_dir() => Ok(full_path),
+        _ => Err(no_such_bucket()),
+    }
+}
+
+// 对象键映射为存储桶目录下的相对路径；以 / 结尾的键表示目录
+async fn object_path(state: &AppState, bucket: &str, key: &str) -> Result<(String, PathBuf), S3Error> {
+    existing_bucket(state, bucket).await?;
+
+    let trimmed = key.strip_suffix('/').unwrap_or(key);
+    if trimmed.is_empty() || !trimmed.split('/').all(is_valid_file_name) {
+        return Err(invalid_argument("Object key is not a valid file path"));
+    }
+
+    let rel_path = format!("{}/{}", bucket, trimmed);
+    let full_path = state.base_dir.join(&rel_path);
+    if !is_safe_path(&state.base_dir, &full_path) {
+        return Err(S3Error::new(StatusCode::FORBIDDEN, "AccessDenied", "Access denied"));
+    }
+    Ok((rel_path, full_path))
+}
+
+// 为对象创建上级目录；路径中已有同名文件时无法创建
+async fn ensure_parent(full_path: &Path) -> Result<(), S3Error> {
+    if fs::metadata(full_path).await.map(|m| m.is_dir()).unwrap_or(false) {
+        return Err(AppError::Conflict("A directory exists with this key".to_string()).into());
+    }
+    if let Some(parent) = full_path.parent() {
+        fs::create_dir_all(parent).await.map_err(|_| {
+            S3Error::from(AppError::Conflict(
+                "A parent of this key is an existing object".to_string(),
+            ))
+        })?;
+    }
+    Ok(())
+}
+
+// 写入请求体时同时计算MD5（用作分片ETag和校验Content-MD5）和SHA-256（校验签名中的负载哈希）
+struct BodySink {
+    file: fs::File,
+    md5: Md5,
+    sha256: Sha256,
+    size: u64,
+}
+
+impl BodySink {
+    async fn write(&mut self, data: &[u8]) -> std::io::Result<()> {
+        self.md5.update(data);
+        self.sha256.update(data);
+        self.size += data.len() as u64;
+        self.file.write_all(data).await
+    }
+}
+
+// 接收到的请求体：大小和MD5
+struct ReceivedBody {
+    size: u64,
+    md5: [u8; 16],
+}
+
+impl ReceivedBody {
+    fn etag(&self) -> String {
+        format!("\"{}\"", hex::encode(self.md5))
+    }
+}
+
+fn check_content_md5(ctx: &S3Context<'_>, md5: &[u8]) -> Result<(), S3Error> {
+    if let Some(expected) = ctx.header("content-md5") {
+        let expected = base64::engine::general_purpose::STANDARD
+            .decode(expected.trim())
+            .map_err(|_| S3Error::new(StatusCode::BAD_REQUEST, "InvalidDigest", "Content-MD5 is not valid base64"))?;
+        if expected != md5 {
+            return Err(S3Error::new(
+                StatusCode::BAD_REQUEST,
+                "BadDigest",
+                "The Content-MD5 you specified did not match what was received",
+            ));
+        }
+    }
+    Ok(())
+}
+
+// 流式写入请求体并按签名方式校验，aws-chunked编码的请求体会被解码
+async fn receive_body(ctx: &S3Context<'_>, body: Body, path: &Path) -> Result<ReceivedBody, S3Error> {
+    let mut sink = BodySink {
+        file: fs::File::create(path).await?,
+        md5: Md5::new(),
+        sha256: Sha256::new(),
+        size: 0,
+    };
+    let mut decoder = match &ctx.payload {
+        PayloadCheck::Streaming(signer) => Some(ChunkedDecoder::new(Some(signer.clone()))),
+        PayloadCheck::StreamingUnsigned => Some(ChunkedDecoder::new(None)),
+        _ => None,
+    };
+
+    let mut stream = body.into_data_stream();
+    while let Some(data) = stream.next().await {
+        let data = data.map_err(|e| S3Error::new(StatusCode::BAD_REQUEST, "IncompleteBody", e.to_string()))?;
+        match decoder.as_mut() {
+            Some(decoder) => {
+                for chunk in decoder.feed(&data)? {
+                    sink.write(&chunk).await?;
+                }
+            }
+            None => sink.write(&data).await?,
+        }
+    }
+    if let Some(decoder) = &decoder {
+        decoder.finish()?;
+    }
+
+    if let PayloadCheck::Sha256(expected) = &ctx.payload {
+        if hex::encode(sink.sha256.finalize_reset()) != *expected {
+            return Err(S3Error::new(
+                StatusCode::BAD_REQUEST,
+                "XAmzContentSHA256Mismatch",
+                "The provided 'x-amz-content-sha256' header does not match what was computed",
+            ));
+        }
+    }
+
+    sink.file.flush().await?;
+    sink.file.sync_all().await?;
+    let received = ReceivedBody {
+        size: sink.size,
+        md5: sink.md5.finalize().into(),
+    };
+    check_content_md5(ctx, &received.md5)?;
+    Ok(received)
+}
+
+// 读取较小的XML请求体
+async fn receive_small_body(ctx: &S3Context<'_>, body: Body) -> Result<Vec<u8>, S3Error> {
+    let bytes = axum::body::to_bytes(body, MAX_XML_BODY)
+        .await
+        .map_err(|_| S3Error::new(StatusCode::BAD_REQUEST, "EntityTooLarge", "Request body is too large"))?;
+
+    let data = match &ctx.payload {
+        PayloadCheck::Streaming(signer) => decode_chunked(Some(signer.clone()), &bytes)?,
+        PayloadCheck::StreamingUnsigned => decode_chunked(None, &bytes)?,
+        PayloadCheck::Sha256(expected) => {
+            if hex::encode(Sha256::digest(&bytes)) != *expected {
+                return Err(S3Error::new(
+                    StatusCode::BAD_REQUEST,
+                    "XAmzContentSHA256Mismatch",
+                    "The provided 'x-amz-content-sha256' header does not match what was computed",
+                ));
+            }
+            bytes.to_vec()
+        }
+        PayloadCheck::Unsigned => bytes.to_vec(),
+    };
+    check_content_md5(ctx, &Md5::digest(&data))?;
+    Ok(data)
+}
+
+fn decode_chunked(signer: Option<sigv4::ChunkSigner>, bytes: &[u8]) -> Result<Vec<u8>, S3Error> {
+    let mut decoder = ChunkedDecoder::new(signer);
+    let data = decoder.feed(bytes)?.concat();
+    decoder.finish()?;
+    Ok(data)
+}
+
+fn parse_xml_body(data: &[u8]) -> Result<XmlElement, S3Error> {
+    xml::parse(data)
+        .ok()
+        .flatten()
+        .ok_or_else(|| S3Error::new(StatusCode::BAD_REQUEST, "MalformedXML", "The XML you provided was not well-formed"))
+}
+
+async fn list_buckets(ctx: &S3Context<'_>) -> Result<Response, S3Error> {
+    let mut buckets = Vec::new();
+    let mut entries = fs::read_dir(&ctx.state.base_dir).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if !is_safe_path(&ctx.state.base_dir, &entry.path())
+            || !ctx.state.acl.allows(ctx.user, &name, Permission::Read)
+        {
+            continue;
+        }
+        if let Ok(metadata) = fs::metadata(entry.path()).await {
+            if metadata.is_dir() {
+                let created = metadata.created().unwrap_or_else(|_| modified_time(&metadata));
+                buckets.push((name, created));
+            }
+        }
+    }
+    buckets.sort_by(|a, b| a.0.cmp(&b.0));
+
+    let buckets: String = buckets
+        .iter()
+        .map(|(name, created)| {
+            format!(
+                "<Bucket><Name>{}</Name><CreationDate>{}</CreationDate></Bucket>",
+                xml::escape(name),
+                iso8601(*created)
+            )
+        })
+        .collect();
+    let owner = xml::escape(&ctx.user.username);
+    Ok(xml_response(format!(
+        "<ListAllMyBucketsResult xmlns=\"{}\"><Owner><ID>{}</ID><DisplayName>{}</DisplayName></Owner><Buckets>{}</Buckets></ListAllMyBucketsResult>",
+        S3_NS, owner, owner, buckets
+    )))
+}
+
+async fn head_bucket(ctx: &S3Context<'_>, bucket: &str) -> Result<Response, S3Error> {
+    existing_bucket(ctx.state, bucket).await?;
+    ctx.state.acl.check(ctx.user, bucket, Permission::Read)?;
+    Ok(StatusCode::OK.into_response())
+}
+
+async fn bucket_location(ctx: &S3Context<'_>, bucket: &str) -> Result<Response, S3Error> {
+    existing_bucket(ctx.state, bucket).await?;
+    ctx.state.acl.check(ctx.user, bucket, Permission::Read)?;
+    // us-east-1的LocationConstraint为空
+    let region = if ctx.state.s3.region == "us-east-1" { "" } else { &ctx.state.s3.region };
+    Ok(xml_response(format!(
+        "<LocationConstraint xmlns=\"{}\">{}</LocationConstraint>",
+        S3_NS,
+        xml::escape(region)
+    )))
+}
+
+async fn create_bucket(ctx: &S3Context<'_>, bucket: &str) -> Result<Response, S3Error> {
+    let full_path = bucket_path(ctx.state, bucket)?;
+    ctx.state.acl.check(ctx.user, bucket, Permission::Write)?;
+    if fs::symlink_metadata(&full_path).await.is_ok() {
+        return Err(S3Error::new(
+            StatusCode::CONFLICT,
+            "BucketAlreadyOwnedByYou",
+            "Your previous request to create the named bucket succeeded and you already own it",
+        ));
+    }
+
+    fs::create_dir(&full_path).await?;
+    events::file_changed(ctx.state, ChangeKind::Created, bucket, &ctx.user.username).await;
+    tracing::info!("{} created bucket {} via S3", ctx.user, bucket);
+    Ok((StatusCode::OK, [(header::LOCATION, format!("/{}", bucket))]).into_response())
+}
+
+// 只能删除空的存储桶
+async fn delete_bucket(ctx: &S3Context<'_>, bucket: &str) -> Result<Response, S3Error> {
+    let full_path = existing_bucket(ctx.state, bucket).await?;
+    ctx.state.acl.check(ctx.user, bucket, Permission::Delete)?;
+    if fs::read_dir(&full_path).await?.next_entry().await?.is_some() {
+        return Err(S3Error::new(
+            StatusCode::CONFLICT,
+            "BucketNotEmpty",
+            "The bucket you tried to delete is not empty",
+        ));
+    }
+
+    fs::remove_dir(&full_path).await?;
+    events::file_changed(ctx.state, ChangeKind::Deleted, bucket, &ctx.user.username).await;
+    tracing::info!("{} deleted bucket {} via S3", ctx.user, bucket);
+    Ok(StatusCode::NO_CONTENT.into_response())
+}
+
+struct ObjectEntry {
+    key: String,
+    metadata: std::fs::Metadata,
+}
+
+// 列表中的一项：对象或按分隔符折叠的公共前缀
+enum ListItem {
+    Object(ObjectEntry),
+    Prefix(String),
+}
+
+impl ListItem {
+    fn name(&self) -> &str {
+        match self {
+            ListItem::Object(object) => &object.key,
+            ListItem::Prefix(prefix) => prefix,
+        }
+    }
+}
+
+struct ListScope {
+    bucket_dir: PathBuf,
+    bucket: String,
+    prefix: String,
+    delimiter: String,
+    acl: Arc<Acl>,
+    user: AuthUser,
+}
+
+impl ListScope {
+    fn readable(&self, key: &str) -> bool {
+        self.acl
+            .allows(&self.user, &format!("{}/{}", self.bucket, key), Permission::Read)
+    }
+
+    fn object(&self, key: String, metadata: &std::fs::Metadata) -> ListItem {
+        ListItem::Object(ObjectEntry {
+            key,
+            metadata: metadata.clone(),
+        })
+    }
+
+    // 收集前缀下的对象和公共前缀，按键排序
+    fn collect(&self) -> std::io::Result<Vec<ListItem>> {
+        let (dir_part, name_prefix) = match self.prefix.rfind('/') {
+            Some(pos) => (&self.prefix[..pos], &self.prefix[pos + 1..]),
+            None => ("", self.prefix.as_str()),
+        };
+        if !dir_part.is_empty() && !dir_part.split('/').all(is_valid_file_name) {
+            return Ok(Vec::new());
+        }
+        let start_dir = self.bucket_dir.join(dir_part);
+        if !start_dir.is_dir() {
+            return Ok(Vec::new());
+        }
+
+        let mut items = Vec::new();
+        if self.delimiter == "/" {
+            // 以 / 为分隔符时只需列出前缀所在的一层目录，子目录即公共前缀
+            for entry in std::fs::read_dir(&start_dir)? {
+                let entry = entry?;
+                let name = entry.file_name().to_string_lossy().to_string();
+                if !name.starts_with(name_prefix) {
+                    continue;
+                }
+                let key = join_relative_path(dir_part, &name);
+                if !self.readable(&key) {
+                    continue;
+                }
+                // 与递归列出一致，不跟随指向目录的符号链接
+                if entry.file_type()?.is_dir() {
+                    items.push(ListItem::Prefix(format!("{}/", key)));
+                    continue;
+                }
+                if let Ok(metadata) = std::fs::metadata(entry.path()) {
+                    if metadata.is_file() {
+                        items.push(self.object(key, &metadata));
+                    }
+                }
+            }
+        } else {
+            let mut stack = vec![(start_dir, dir_part.to_string(), true)];
+            while let Some((dir, dir_key, top)) = stack.pop() {
+                for entry in std::fs::read_dir(&dir)? {
+                    let entry = entry?;
+                    let name = entry.file_name().to_string_lossy().to_string();
+                    if top && !name.starts_with(name_prefix) {
+                        continue;
+                    }
+                    let key = join_relative_path(&dir_key, &name);
+                    let file_type = entry.file_type()?;
+                    // 不跟随指向目录的符号链接，避免循环
+                    if file_type.is_dir() {
+                        stack.push((entry.path(), key, false));
+                        continue;
+                    }
+                    if let Ok(metadata) = std::fs::metadata(entry.path()) {
+                        if metadata.is_file() && self.readable(&key) {
+                            items.push(self.object(key, &metadata));
+                        }
+                    }
+                }
+            }
+
+            if !self.delimiter.is_empty() {
+                let mut grouped = Vec::new();
+                for item in items {
+                    let folded = item.name()[self.prefix.len()..]
+                        .find(&self.delimiter)
+                        .map(|pos| item.name()[..self.prefix.len() + pos + self.delimiter.len()].to_string());
+                    match folded {
+                        Some(prefix) => grouped.push(ListItem::Prefix(prefix)),
+                        None => grouped.push(item),
+                    }
+                }
+                items = grouped;
+            }
+        }
+
+        items.sort_by(|a, b| a.name().cmp(b.name()));
+        items.dedup_by(|a, b| a.name() == b.name());
+        Ok(items)
+    }
+}
+
+// ListObjects（V1）和ListObjectsV2
+async fn list_objects(ctx: &S3Context<'_>, bucket: &str) -> Result<Response, S3Error> {
+    let bucket_dir = existing_bucket(ctx.state, bucket).await?;
+    ctx.state.acl.check(ctx.user, bucket, Permission::Read)?;
+
+    let v2 = ctx.query("list-type") == Some("2");
+    let prefix = ctx.query("prefix").unwrap_or_default().to_string();
+    let delimiter = ctx.query("delimiter").unwrap_or_default().to_string();
+    let max_keys = match ctx.query("max-keys") {
+        Some(value) => value
+            .parse::<usize>()
+            .map_err(|_| invalid_argument("max-keys must be a non-negative integer"))?
+            .min(MAX_KEYS),
+        None => MAX_KEYS,
+    };
+    let url_encoding = ctx.query("encoding-type") == Some("url");
+    let encode = |value: &str| {
+        if url_encoding {
+            sigv4::uri_encode(value)
+        } else {
+            xml::escape(value)
+        }
+    };
+
+    let continuation_token = ctx.query("continuation-token").map(str::to_string);
+    // 续传令牌为上一页最后一个键的base64编码
+    let marker = if v2 {
+        match &continuation_token {
+            Some(token) => {
+                let decoded = base64::engine::general_purpose::URL_SAFE_NO_PAD
+                    .decode(token)
+                    .ok()
+                    .and_then(|bytes| String::from_utf8(bytes).ok())
+                    .ok_or_else(|| invalid_argument("The continuation token provided is incorrect"))?;
+                Some(decoded)
+            }
+            None => ctx.query("start-after").map(str::to_string),
+        }
+    } else {
+        ctx.query("marker").map(str::to_string)
+    };
+
+    let scope = ListScope {
+        bucket_dir,
+        bucket: bucket.to_string(),
+        prefix: prefix.clone(),
+        delimiter: delimiter.clone(),
+        acl: ctx.state.acl.clone(),
+        user: ctx.user.clone(),
+    };
+    let items = tokio::task::spawn_blocking(move || scope.collect())
+        .await
+        .map_err(|e| AppError::IoError(e.to_string()))??;
+
+    let mut page: Vec<&ListItem> = items
+        .iter()
+        .filter(|item| marker.as_deref().map(|m| item.name() > m).unwrap_or(true))
+        .take(max_keys + 1)
+        .collect();
+    let truncated = page.len() > max_keys;
+    page.truncate(max_keys);
+    let last = page.last().map(|item| item.name().to_string());
+
+    let mut body = format!(
+        "<ListBucketResult xmlns=\"{}\"><Name>{}</Name><Prefix>{}</Prefix>",
+        S3_NS,
+        xml::escape(bucket),
+        encode(&prefix)
+    );
+    if !delimiter.is_empty() {
+        body.push_str(&format!("<Delimiter>{}</Delimiter>", encode(&delimiter)));
+    }
+    body.push_str(&format!("<MaxKeys>{}</MaxKeys>", max_keys));
+    if url_encoding {
+        body.push_str("<EncodingType>url</EncodingType>");
+    }
+    body.push_str(&format!("<IsTruncated>{}</IsTruncated>", truncated));
+
+    if v2 {
+        body.push_str(&format!("<KeyCount>{}</KeyCount>", page.len()));
+        if let Some(token) = &continuation_token {
+            body.push_str(&format!("<ContinuationToken>{}</ContinuationToken>", xml::escape(token)));
+        }
+        if let Some(start_after) = ctx.query("start-after") {
+            body.push_str(&format!("<StartAfter>{}</StartAfter>", encode(start_after)));
+        }
+        if let (true, Some(last)) = (truncated, &last) {
+            body.push_str(&format!(
+                "<NextContinuationToken>{}</NextContinuationToken>",
+                base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(last)
+            ));
+        }
+    } else {
+        body.push_str(&format!(
+            "<Marker>{}</Marker>",
+            encode(ctx.query("marker").unwrap_or_default())
+        ));
+        if let (true, Some(last)) = (truncated, &last) {
+            body.push_str(&format!("<NextMarker>{}</NextMarker>", encode(last)));
+        }
+    }
+
+    for item in &page {
+        match item {
+            ListItem::Object(object) => {
+                let etag = ctx
+                    .state
+                    .s3
+                    .object_etag(&format!("{}/{}", bucket, object.key), &object.metadata)
+                    .await;
+                body.push_str(&format!(
+                    "<Contents><Key>{}</Key><LastModified>{}</LastModified><ETag>{}</ETag><Size>{}</Size><StorageClass>STANDARD</StorageClass></Contents>",
+                    encode(&object.key),
+                    iso8601(modified_time(&object.metadata)),
+                    xml::escape(&etag),
+                    object.metadata.len()
+                ))
+            }
+            ListItem::Prefix(prefix) => body.push_str(&format!(
+                "<CommonPrefixes><Prefix>{}</Prefix></CommonPrefixes>",
+                encode(prefix)
+            )),
+        }
+    }
+    body.push_str("</ListBucketResult>");
+
+    Ok(xml_response(body))
+}
+
+async fn object_headers(
+    ctx: &S3Context<'_>,
+    rel_path: &str,
+    metadata: &std::fs::Metadata,
+) -> [(header::HeaderName, String); 3] {
+    [
+        (header::ETAG, ctx.state.s3.object_etag(rel_path, metadata).await),
+        (header::LAST_MODIFIED, format_http_date(modified_time(metadata))),
+        (header::ACCEPT_RANGES, "bytes".to_string()),
+    ]
+}
+
+async fn object_metadata(key: &str, full_path: &Path) -> Result<std::fs::Metadata, S3Error> {
+    let metadata = fs::metadata(full_path).await.map_err(|_| no_such_key())?;
+    // 以 / 结尾的键对应目录，其余键只对应文件
+    if metadata.is_dir() != key.ends_with('/') {
+        return Err(no_such_key());
+    }
+    Ok(metadata)
+}
+
+async fn get_object(ctx: &S3Context<'_>, bucket: &str, key: &str) -> Result<Response, S3Error> {
+    let (rel_path, full_path) = object_path(ctx.state, bucket, key).await?;
+    ctx.state.acl.check(ctx.user, &rel_path, Permission::Read)?;
+    let metadata = object_metadata(key, &full_path).await?;
+
+    if metadata.is_dir() {
+        return Ok((object_headers(ctx, &rel_path, &metadata).await, [(header::CONTENT_LENGTH, "0")]).into_response());
+    }
+
+    let response = send_file(&full_path, &ctx.headers).await?;
+    Ok((object_headers(ctx, &rel_path, &metadata).await, response).into_response())
+}
+
+async fn head_object(ctx: &S3Context<'_>, bucket: &str, key: &str) -> Result<Response, S3Error> {
+    let (rel_path, full_path) = object_path(ctx.state, bucket, key).await?;
+    ctx.state.acl.check(ctx.user, &rel_path, Permission::Read)?;
+    let metadata = object_metadata(key, &full_path).await?;
+
+    let (content_type, length) = if metadata.is_dir() {
+        ("application/x-directory".to_string(), 0)
+    } else {
+        (
+            get_mime_type(&full_path).unwrap_or_else(|| "application/octet-stream".to_string()),
+            metadata.len(),
+        )
+    };
+    Ok((
+        object_headers(ctx, &rel_path, &metadata).await,
+        [
+            (header::CONTENT_TYPE, content_type),
+            (header::CONTENT_LENGTH, length.to_string()),
+        ],
+    )
+        .into_response())
+}
+
+// 上传对象；以 / 结尾的空对象创建目录
+async fn put_object(ctx: &S3Context<'_>, bucket: &str, key: &str, body: Body) -> Result<Response, S3Error> {
+    let (rel_path, full_path) = object_path(ctx.state, bucket, key).await?;
+    ctx.state.acl.check(ctx.user, &rel_path, Permission::Write)?;
+
+    if key.ends_with('/') {
+        if !receive_small_body(ctx, body).await?.is_empty() {
+            return Err(invalid_argument("Directory objects must be empty"));
+        }
+        fs::create_dir_all(&full_path).await.map_err(|_| {
+            S3Error::from(AppError::Conflict("An object exists with this key".to_string()))
+        })?;
+        events::file_changed(ctx.state, ChangeKind::Created, &rel_path, &ctx.user.username).await;
+        tracing::info!("{} created directory {} via S3", ctx.user, rel_path);
+        return Ok([(header::ETAG, format!("\"{}\"", EMPTY_MD5))].into_response());
+    }
+
+    ensure_parent(&full_path).await?;
+    let guard = TempFileGuard::new(temp_path_for(&full_path));
+    let received = receive_body(ctx, body, guard.path()).await?;
+
+    let existed = full_path.exists();
+    ctx.state
+        .versions
+        .preserve(&full_path, &rel_path, &ctx.user.username)
+        .await?;
+    guard.commit(&full_path).await?;
+    events::file_changed(ctx.state, ChangeKind::written(existed), &rel_path, &ctx.user.username).await;
+
+    tracing::info!("{} uploaded {} via S3 ({} bytes)", ctx.user, rel_path, received.size);
+    // 返回内容MD5作为ETag，SDK会与自己计算的MD5比对
+    let metadata = fs::metadata(&full_path).await?;
+    ctx.state.s3.save_etag(&rel_path, &metadata, &received.etag()).await;
+    Ok([(header::ETAG, received.etag())].into_response())
+}
+
+// 解析x-amz-copy-source：[/]bucket/key[?versionId=...]，经过URL编码
+fn copy_source(ctx: &S3Context<'_>) -> Result<(String, String), S3Error> {
+    let source = ctx.header("x-amz-copy-source").unwrap_or_default();
+    let source = source.split('?').next().unwrap_or_default();
+    let source = percent_decode_str(source.trim_start_matches('/'))
+        .decode_utf8_lossy()
+        .to_string();
+    source
+        .split_once('/')
+        .filter(|(bucket, key)| !bucket.is_empty() && !key.is_empty())
+        .map(|(bucket, key)| (bucket.to_string(), key.to_string()))
+        .ok_or_else(|| invalid_argument("Invalid x-amz-copy-source"))
+}
+
+async fn copy_object(ctx: &S3Context<'_>, bucket: &str, key: &str) -> Result<Response, S3Error> {
+    let (src_bucket, src_key) = copy_source(ctx)?;
+    let (src_rel, src_path) = object_path(ctx.state, &src_bucket, &src_key).await?;
+    let (rel_path, full_path) = object_path(ctx.state, bucket, key).await?;
+    ctx.state.acl.check(ctx.user, &src_rel, Permission::Read)?;
+    ctx.state.acl.check(ctx.user, &rel_path, Permission::Write)?;
+
+    let src_metadata = object_metadata(&src_key, &src_path).await?;
+    if src_metadata.is_dir() || key.ends_with('/') {
+        return Err(invalid_argument("Directories cannot be copied"));
+    }
+
+    // 复制到自身（用于替换元数据）时内容不变
+    if src_rel != rel_path {
+        ensure_parent(&full_path).await?;
+        let guard = TempFileGuard::new(temp_path_for(&full_path));
+        fs::copy(&src_path, guard.path()).await?;
+        let existed = full_path.exists();
+        ctx.state
+            .versions
+            .preserve(&full_path, &rel_path, &ctx.user.username)
+            .await?;
+        guard.commit(&full_path).await?;
+        events::file_changed(ctx.state, ChangeKind::written(existed), &rel_path, &ctx.user.username).await;
+        tracing::info!("{} copied {} to {} via S3", ctx.user, src_rel, rel_path);
+    }
+
+    let metadata = fs::metadata(&full_path).await?;
+    Ok(xml_response(format!(
+        "<CopyObjectResult xmlns=\"{}\"><LastModified>{}</LastModified><ETag>{}</ETag></CopyObjectResult>",
+        S3_NS,
+        iso8601(modified_time(&metadata)),
+        xml::escape(&file_etag(&metadata))
+    )))
+}
+
+// 删除对象（移入回收站），对象不存在时同样视为成功
+async fn delete_one(ctx: &S3Context<'_>, bucket: &str, key: &str) -> Result<(), S3Error> {
+    let (rel_path, full_path) = object_path(ctx.state, bucket, key).await?;
+    ctx.state.acl.check(ctx.user, &rel_path, Permission::Delete)?;
+
+    let metadata = match fs::metadata(&full_path).await {
+        Ok(metadata) => metadata,
+        Err(_) => return Ok(()),
+    };
+    if metadata.is_dir() {
+        // 目录对象只在目录为空时删除
+        if key.ends_with('/') && fs::read_dir(&full_path).await?.next_entry().await?.is_none() {
+            fs::remove_dir(&full_path).await?;
+            events::file_changed(ctx.state, ChangeKind::Deleted, &rel_path, &ctx.user.username).await;
+        }
+        return Ok(());
+    }
+
+    ctx.state.trash.remove(&full_path, &rel_path, ctx.user).await?;
+    ctx.state.s3.forget_etag(&rel_path).await;
+    events::file_changed(ctx.state, ChangeKind::Deleted, &rel_path, &ctx.user.username).await;
+    tracing::info!("{} deleted {} via S3", ctx.user, rel_path);
+    Ok(())
+}
+
+async fn delete_object(ctx: &S3Context<'_>, bucket: &str, key: &str) -> Result<Response, S3Error> {
+    delete_one(ctx, bucket, key).await?;
+    Ok(StatusCode::NO_CONTENT.into_response())
+}
+
+// DeleteObjects：批量删除，逐个返回结果
+async fn delete_objects(ctx: &S3Context<'_>, bucket: &str, body: Body) -> Result<Response, S3Error> {
+    existing_bucket(ctx.state, bucket).await?;
+    let request = parse_xml_body(&receive_small_body(ctx, body).await?)?;
+    let quiet = request
+        .child_text("Quiet")
+        .map(|q| q.trim().eq_ignore_ascii_case("true"))
+        .unwrap_or(false);
+    let keys: Vec<String> = request
+        .children_named("Object")
+        .filter_map(|object| object.child_text("Key"))
+        .collect();
+    if keys.len() > MAX_KEYS {
+        return Err(S3Error::new(StatusCode::BAD_REQUEST, "MalformedXML", "Too many keys in one request"));
+    }
+
+    let mut result = format!("<DeleteResult xmlns=\"{}\">", S3_NS);
+    for key in keys {
+        match delete_one(ctx, bucket, &key).await {
+            Ok(()) if quiet => {}
+            Ok(()) => result.push_str(&format!("<Deleted><Key>{}</Key></Deleted>", xml::escape(&key))),
+            Err(e) => result.push_str(&format!(
+                "<Error><Key>{}</Key><Code>{}</Code><Message>{}</Message></Error>",
+                xml::escape(&key),
+                e.code,
+                xml::escape(&e.message)
+            )),
+        }
+    }
+    result.push_str("</DeleteResult>");
+    Ok(xml_response(result))
+}
+
+async fn create_multipart_upload(ctx: &S3Context<'_>, bucket: &str, key: &str) -> Result<Response, S3Error> {
+    let (rel_path, _) = object_path(ctx.state, bucket, key).await?;
+    ctx.state.acl.check(ctx.user, &rel_path, Permission::Write)?;
+    if key.ends_with('/') {
+        return Err(invalid_argument("Directory objects cannot be uploaded in parts"));
+    }
+
+    let upload = MultipartUpload {
+        id: uuid::Uuid::new_v4().simple().to_string(),
+        bucket: bucket.to_string(),
+        key: key.to_string(),
+        user: ctx.user.username.clone(),
+        initiated: Utc::now().timestamp(),
+    };
+    let dir = ctx.state.s3.upload_dir(&upload.id);
+    fs::create_dir_all(&dir).await?;
+    let content = serde_json::to_vec(&upload).map_err(|e| AppError::IoError(e.to_string()))?;
+    write_file_atomic(&dir.join("upload.json"), &content).await?;
+
+    tracing::info!("{} started multipart upload {} for {} via S3", ctx.user, upload.id, rel_path);
+    Ok(xml_response(format!(
+        "<InitiateMultipartUploadResult xmlns=\"{}\"><Bucket>{}</Bucket><Key>{}</Key><UploadId>{}</UploadId></InitiateMultipartUploadResult>",
+        S3_NS,
+        xml::escape(bucket),
+        xml::escape(key),
+        upload.id
+    )))
+}
+
+fn part_path(dir: &Path, number: u32) -> PathBuf {
+    dir.join(format!("{:05}.part", number))
+}
+
+fn part_etag_path(dir: &Path, number: u32) -> PathBuf {
+    dir.join(format!("{:05}.etag", number))
+}
+
+fn part_number(ctx: &S3Context<'_>) -> Result<u32, S3Error> {
+    ctx.query("partNumber")
+        .and_then(|n| n.parse::<u32>().ok())
+        .filter(|n| (1..=MAX_PART_NUMBER).contains(n))
+        .ok_or_else(|| invalid_argument("Part number must be an integer between 1 and 10000"))
+}
+
+async fn upload_part(
+    ctx: &S3Context<'_>,
+    bucket: &str,
+    key: &str,
+    id: &str,
+    body: Body,
+) -> Result<Response, S3Error> {
+    let (rel_path, _) = object_path(ctx.state, bucket, key).await?;
+    ctx.state.acl.check(ctx.user, &rel_path, Permission::Write)?;
+    let number = part_number(ctx)?;
+    ctx.state.s3.load_upload(id, bucket, key).await?;
+    if ctx.headers.contains_key("x-amz-copy-source") {
+        return Err(not_implemented());
+    }
+
+    let dir = ctx.state.s3.upload_dir(id);
+    let target = part_path(&dir, number);
+    let guard = TempFileGuard::new(temp_path_for(&target));
+    let received = receive_body(ctx, body, guard.path()).await?;
+    guard.commit(&target).await?;
+    write_file_atomic(&part_etag_path(&dir, number), received.etag().as_bytes()).await?;
+
+    Ok([(header::ETAG, received.etag())].into_response())
+}
+
+// 已上传的分片：(序号, ETag, 大小, 修改时间)
+async fn uploaded_parts(dir: &Path) -> Result<Vec<(u32, String, u64, SystemTime)>, S3Error> {
+    let mut parts = Vec::new();
+    let mut entries = fs::read_dir(dir).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let name = entry.file_name().to_string_lossy().to_string();
+        let number = match name.strip_suffix(".part").and_then(|n| n.parse::<u32>().ok()) {
+            Some(number) => number,
+            None => continue,
+        };
+        let etag = match fs::read_to_string(part_etag_path(dir, number)).await {
+            Ok(etag) => etag,
+            Err(_) => continue,
+        };
+        let metadata = entry.metadata().await?;
+        parts.push((number, etag, metadata.len(), modified_time(&metadata)));
+    }
+    parts.sort_by_key(|part| part.0);
+    Ok(parts)
+}
+
+async fn list_parts(ctx: &S3Context<'_>, bucket: &str, key: &str, id: &str) -> Result<Response, S3Error> {
+    let (rel_path, _) = object_path(ctx.state, bucket, key).await?;
+    ctx.state.acl.check(ctx.user, &rel_path, Permission::Write)?;
+    let upload = ctx.state.s3.load_upload(id, bucket, key).await?;
+
+    let parts: String = uploaded_parts(&ctx.state.s3.upload_dir(id))
+        .await?
+        .iter()
+        .map(|(number, etag, size, modified)| {
+            format!(
+                "<Part><PartNumber>{}</PartNumber><LastModified>{}</LastModified><ETag>{}</ETag><Size>{}</Size></Part>",
+                number,
+                iso8601(*modified),
+                xml::escape(etag),
+                size
+            )
+        })
+        .collect();
+    let initiator = xml::escape(&upload.user);
+    Ok(xml_response(format!(
+        "<ListPartsResult xmlns=\"{}\"><Bucket>{}</Bucket><Key>{}</Key><UploadId>{}</UploadId>\
+         <Initiator><ID>{}</ID><DisplayName>{}</DisplayName></Initiator><StorageClass>STANDARD</StorageClass>\
+         <MaxParts>{}</MaxParts><IsTruncated>false</IsTruncated>{}</ListPartsResult>",
+        S3_NS,
+        xml::escape(bucket),
+        xml::escape(key),
+        id,
+        initiator,
+        initiator,
+        MAX_PART_NUMBER,
+        parts
+    )))
+}
+
+// 按请求中的分片列表拼接出最终文件，覆盖时保存历史版本
+async fn complete_multipart_upload(
+    ctx: &S3Context<'_>,
+    bucket: &str,
+    key: &str,
+    id: &str,
+    body: Body,
+) -> Result<Response, S3Error> {
+    let (rel_path, full_path) = object_path(ctx.state, bucket, key).await?;
+    ctx.state.acl.check(ctx.user, &rel_path, Permission::Write)?;
+    ctx.state.s3.load_upload(id, bucket, key).await?;
+
+    let request = parse_xml_body(&receive_small_body(ctx, body).await?)?;
+    let mut requested = Vec::new();
+    for part in request.children_named("Part") {
+        let number = part
+            .child_text("PartNumber")
+            .and_then(|n| n.trim().parse::<u32>().ok())
+            .ok_or_else(|| S3Error::new(StatusCode::BAD_REQUEST, "MalformedXML", "Part is missing PartNumber"))?;
+        let etag = part.child_text("ETag").unwrap_or_default();
+        requested.push((number, etag.trim().trim_matches('"').to_string()));
+    }
+    if requested.is_empty() {
+        return Err(S3Error::new(StatusCode::BAD_REQUEST, "MalformedXML", "No parts specified"));
+    }
+    if requested.windows(2).any(|w| w[0].0 >= w[1].0) {
+        return Err(S3Error::new(
+            StatusCode::BAD_REQUEST,
+            "InvalidPartOrder",
+            "The list of parts was not in ascending order",
+        ));
+    }
+
+    let dir = ctx.state.s3.upload_dir(id);
+    let uploaded: HashMap<u32, String> = uploaded_parts(&dir)
+        .await?
+        .into_iter()
+        .map(|(number, etag, _, _)| (number, etag.trim_matches('"').to_string()))
+        .collect();
+    for (number, etag) in &requested {
+        if uploaded.get(number) != Some(etag) {
+            return Err(S3Error::new(
+                StatusCode::BAD_REQUEST,
+                "InvalidPart",
+                format!("Part {} was not uploaded or its ETag does not match", number),
+            ));
+        }
+    }
+
+    ensure_parent(&full_path).await?;
+    let guard = TempFileGuard::new(temp_path_for(&full_path));
+    let mut output = fs::File::create(guard.path()).await?;
+    for (number, _) in &requested {
+        let mut part = fs::File::open(part_path(&dir, *number)).await?;
+        tokio::io::copy(&mut part, &mut output).await?;
+    }
+    output.flush().await?;
+    output.sync_all().await?;
+    drop(output);
+
+    let existed = full_path.exists();
+    ctx.state
+        .versions
+        .preserve(&full_path, &rel_path, &ctx.user.username)
+        .await?;
+    guard.commit(&full_path).await?;
+    events::file_changed(ctx.state, ChangeKind::written(existed), &rel_path, &ctx.user.username).await;
+    let _ = fs::remove_dir_all(&dir).await;
+
+    tracing::info!(
+        "{} completed multipart upload {} for {} via S3 ({} parts)",
+        ctx.user,
+        id,
+        rel_path,
+        requested.len()
+    );
+    let metadata = fs::metadata(&full_path).await?;
+    Ok(xml_response(format!(
+        "<CompleteMultipartUploadResult xmlns=\"{}\"><Location>/{}/{}</Location><Bucket>{}</Bucket><Key>{}</Key><ETag>{}</ETag></CompleteMultipartUploadResult>",
+        S3_NS,
+        xml::escape(bucket),
+        xml::escape(key),
+        xml::escape(bucket),
+        xml::escape(key),
+        xml::escape(&file_etag(&metadata))
+    )))
+}
+
+async fn abort_multipart_upload(ctx: &S3Context<'_>, bucket: &str, key: &str, id: &str) -> Result<Response, S3Error> {
+    let (rel_path, _) = object_path(ctx.state, bucket, key).await?;
+    ctx.state.acl.check(ctx.user, &rel_path, Permission::Write)?;
+    ctx.state.s3.load_upload(id, bucket, key).await?;
+
+    fs::remove_dir_all(ctx.state.s3.upload_dir(id)).await?;
+    tracing::info!("{} aborted multipart upload {} for {} via S3", ctx.user, id, rel_path);
+    Ok(StatusCode::NO_CONTENT.into_response())
+}
+
+async fn list_multipart_uploads(ctx: &S3Context<'_>, bucket: &str) -> Result<Response, S3Error> {
+    existing_bucket(ctx.state, bucket).await?;
+    ctx.state.acl.check(ctx.user, bucket, Permission::Read)?;
+    let prefix = ctx.query("prefix").unwrap_or_default();
+
+    let mut uploads: Vec<MultipartUpload> = ctx
+        .state
+        .s3
+        .uploads()
+        .await
+        .into_iter()
+        .filter(|u| u.bucket == bucket && u.key.starts_with(prefix))
+        .collect();
+    uploads.sort_by(|a, b| a.key.cmp(&b.key).then(a.initiated.cmp(&b.initiated)));
+
+    let entries: String = uploads
+        .iter()
+        .map(|upload| {
+            let initiator = xml::escape(&upload.user);
+            let initiated = DateTime::<Utc>::from_timestamp(upload.initiated, 0)
+                .map(|t| t.format("%Y-%m-%dT%H:%M:%S%.3fZ").to_string())
+                .unwrap_or_default();
+            format!(
+                "<Upload><Key>{}</Key><UploadId>{}</UploadId><Initiator><ID>{}</ID><DisplayName>{}</DisplayName></Initiator>\
+                 <Owner><ID>{}</ID><DisplayName>{}</DisplayName></Owner><StorageClass>STANDARD</StorageClass><Initiated>{}</Initiated></Upload>",
+                xml::escape(&upload.key),
+                upload.id,
+                initiator,
+                initiator,
+                initiator,
+                initiator,
+                initiated
+            )
+        })
+        .collect();
+    Ok(xml_response(format!(
+        "<ListMultipartUploadsResult xmlns=\"{}\"><Bucket>{}</Bucket><KeyMarker></KeyMarker><UploadIdMarker></UploadIdMarker>\
+         <Prefix>{}</Prefix><MaxUploads>{}</MaxUploads><IsTruncated>false</IsTruncated>{}</ListMultipartUploadsResult>",
+        S3_NS,
+        xml::escape(bucket),
+        xml::escape(prefix),
+        MAX_KEYS,
+        entries
+    )))
+}
@@ -0,0 +1,775 @@
+use axum::{
+    body::{Body, Bytes},
+    extract::{Query, State},
+    http::header,
+    response::{IntoResponse, Response},
+};
+use globset::{GlobBuilder, GlobMatcher};
+use regex::{Regex, RegexBuilder};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::fs::{File, Metadata};
+use std::io::{self, BufRead, BufReader, Read};
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc;
+
+use crate::{
+    acl::{Acl, Permission},
+    auth::AuthUser,
+    errors::AppError,
+    index::IndexedEntry,
+    models::*,
+    utils::*,
+    AppState,
+};
+
+// 单次搜索默认和最多返回的结果数
+const DEFAULT_LIMIT: usize = 1000;
+const MAX_LIMIT: usize = 10000;
+
+// 正则表达式编译后的大小上限，防止构造超大的自动机
+const REGEX_SIZE_LIMIT: usize = 1 << 20;
+
+// 判断文件是否为文本时读取的开头字节数
+const SNIFF_SIZE: usize = 8192;
+
+// 匹配片段在匹配内容前后保留的字符数，以及匹配内容本身最多保留的字符数
+const SNIPPET_CONTEXT: usize = 60;
+const SNIPPET_MATCH_MAX: usize = 120;
+
+// 内容搜索的限制，防止大目录树长时间占用服务器
+#[derive(Debug, Clone, Copy)]
+pub struct SearchLimits {
+    pub max_file_size: u64,
+    pub max_matches: usize,
+    pub timeout: Duration,
+}
+
+impl SearchLimits {
+    pub fn new(config: &serde_json::Value) -> Self {
+        let max_file_size_mb = config["max_file_size_mb"].as_u64().unwrap_or(10);
+        let max_matches = config["max_matches"].as_u64().unwrap_or(1000);
+        let timeout_secs = config["timeout_secs"].as_u64().unwrap_or(30);
+
+        SearchLimits {
+            max_file_size: max_file_size_mb * 1024 * 1024,
+            max_matches: (max_matches as usize).max(1),
+            timeout: Duration::from_secs(timeout_secs.max(1)),
+        }
+    }
+}
+
+// 搜索用的目录遍历器：只跟随目标仍位于根目录内的符号链接，跳过没有读权限的条目
+pub(crate) struct TreeWalker {
+    base_dir: PathBuf,
+    canonical_base: PathBuf,
+    acl: Arc<Acl>,
+    user: AuthUser,
+    max_depth: Option<usize>,
+    visited: HashSet<PathBuf>,
+    sorted: bool,
+    hidden: bool,
+    all_entries: bool,
+}
+
+impl TreeWalker {
+    pub(crate) fn new(state: &AppState, user: AuthUser, max_depth: Option<usize>) -> io::Result<Self> {
+        Ok(TreeWalker {
+            base_dir: state.base_dir.clone(),
+            canonical_base: std::fs::canonicalize(&state.base_dir)?,
+            acl: state.acl.clone(),
+            user,
+            max_depth,
+            visited: HashSet::new(),
+            sorted: true,
+            hidden: true,
+            all_entries: false,
+        })
+    }
+
+    // 按read_dir返回的顺序访问，不先收集并排序每个目录的条目，内存占用与目录大小无关
+    pub(crate) fn unsorted(mut self) -> Self {
+        self.sorted = false;
+        self
+    }
+
+    // 跳过以点开头的隐藏条目，也不进入隐藏目录
+    pub(crate) fn skip_hidden(mut self) -> Self {
+        self.hidden = false;
+        self
+    }
+
+    // 与普通目录列表一致，没有读权限的条目和指向根目录外的符号链接也会访问，只是不进入其中
+    pub(crate) fn all_entries(mut self) -> Self {
+        self.all_entries = true;
+        self
+    }
+
+    // 依次访问dir下的每个条目，visit返回Break时停止遍历
+    pub(crate) fn walk<F>(&mut self, dir: &Path, rel_path: &str, visit: &mut F) -> ControlFlow<()>
+    where
+        F: FnMut(&Path, &str, &Metadata) -> ControlFlow<()>,
+    {
+        if let Ok(canonical) = std::fs::canonicalize(dir) {
+            self.visited.insert(canonical);
+        }
+        self.walk_dir(dir, rel_path, 1, visit)
+    }
+
+    fn walk_dir<F>(&mut self, dir: &Path, rel_path: &str, depth: usize, visit: &mut F) -> ControlFlow<()>
+    where
+        F: FnMut(&Path, &str, &Metadata) -> ControlFlow<()>,
+    {
+        let read_dir = match std::fs::read_dir(dir) {
+            Ok(read_dir) => read_dir,
+            Err(e) => {
+                tracing::debug!("Failed to read directory {:?}: {}", dir, e);
+                return ControlFlow::Continue(());
+            }
+        };
+        let names = read_dir
+            .filter_map(|e| e.ok())
+            .map(|e| e.file_name().to_string_lossy().to_string());
+
+        if self.sorted {
+            let mut children = names.collect::<Vec<_>>();
+            children.sort();
+            for name in children {
+                self.walk_child(dir, rel_path, &name, depth, visit)?;
+            }
+        } else {
+            for name in names {
+                self.walk_child(dir, rel_path, &name, depth, visit)?;
+            }
+        }
+
+        ControlFlow::Continue(())
+    }
+
+    fn walk_child<F>(&mut self, dir: &Path, rel_path: &str, name: &str, depth: usize, visit: &mut F) -> ControlFlow<()>
+    where
+        F: FnMut(&Path, &str, &Metadata) -> ControlFlow<()>,
+    {
+        if !self.hidden && name.starts_with('.') {
+            return ControlFlow::Continue(());
+        }
+
+        let full_path = dir.join(name);
+        if !is_safe_path(&self.base_dir, &full_path) {
+            return ControlFlow::Continue(());
+        }
+
+        let child_rel = join_relative_path(rel_path, name);
+        let readable = self.acl.allows(&self.user, &child_rel, Permission::Read);
+        if !readable && !self.all_entries {
+            return ControlFlow::Continue(());
+        }
+
+        let Some((metadata, contained)) = self.resolve_metadata(&full_path) else {
+            return ControlFlow::Continue(());
+        };
+        if !contained && !self.all_entries {
+            return ControlFlow::Continue(());
+        }
+
+        visit(&full_path, &child_rel, &metadata)?;
+
+        let descend = readable && contained && metadata.is_dir() && self.max_depth.is_none_or(|max| depth < max);
+        if descend {
+            // 符号链接可能形成环，已访问过的目录不再进入
+            let Ok(canonical) = std::fs::canonicalize(&full_path) else {
+                return ControlFlow::Continue(());
+            };
+            if self.visited.insert(canonical) {
+                self.walk_dir(&full_path, &child_rel, depth + 1, visit)?;
+            }
+        }
+
+        ControlFlow::Continue(())
+    }
+
+    // 返回跟随符号链接后的元数据，以及目标是否位于根目录内
+    fn resolve_metadata(&self, path: &Path) -> Option<(Metadata, bool)> {
+        let metadata = std::fs::symlink_metadata(path).ok()?;
+        if !metadata.file_type().is_symlink() {
+            return Some((metadata, true));
+        }
+
+        let target = std::fs::canonicalize(path).ok()?;
+        let metadata = std::fs::metadata(&target).ok()?;
+        Some((metadata, target.starts_with(&self.canonical_base)))
+    }
+}
+
+// 文件名匹配规则
+enum NameMatcher {
+    Any,
+    Substring { needle: String, case_sensitive: bool },
+    Glob(GlobMatcher),
+    Regex(Regex),
+}
+
+impl NameMatcher {
+    fn new(pattern: &str, mode: MatchMode, case_sensitive: bool) -> Result<Self, AppError> {
+        if pattern.is_empty() {
+            return Ok(NameMatcher::Any);
+        }
+
+        Ok(match mode {
+            MatchMode::Substring => NameMatcher::Substring {
+                needle: if case_sensitive {
+                    pattern.to_string()
+                } else {
+                    pattern.to_lowercase()
+                },
+                case_sensitive,
+            },
+            MatchMode::Glob => NameMatcher::Glob(
+                GlobBuilder::new(pattern)
+                    .case_insensitive(!case_sensitive)
+                    .literal_separator(true)
+                    .build()
+                    .map_err(|e| AppError::InvalidRequest(format!("Invalid glob pattern: {}", e)))?
+                    .compile_matcher(),
+            ),
+            MatchMode::Regex => NameMatcher::Regex(build_regex(pattern, case_sensitive)?),
+        })
+    }
+
+    fn is_match(&self, name: &str) -> bool {
+        match self {
+            NameMatcher::Any => true,
+            NameMatcher::Substring { needle, case_sensitive } => {
+                if *case_sensitive {
+                    name.contains(needle.as_str())
+                } else {
+                    name.to_lowercase().contains(needle.as_str())
+                }
+            }
+            NameMatcher::Glob(glob) => glob.is_match(name),
+            NameMatcher::Regex(regex) => regex.is_match(name),
+        }
+    }
+}
+
+pub(crate) fn build_regex(pattern: &str, case_sensitive: bool) -> Result<Regex, AppError> {
+    RegexBuilder::new(pattern)
+        .case_insensitive(!case_sensitive)
+        .size_limit(REGEX_SIZE_LIMIT)
+        .build()
+        .map_err(|e| AppError::InvalidRequest(format!("Invalid regex: {}", e)))
+}
+
+// 类型、大小、修改时间和扩展名过滤条件
+struct EntryFilter {
+    kind: Option<EntryKind>,
+    min_size: Option<u64>,
+    max_size: Option<u64>,
+    modified_after: Option<i64>,
+    modified_before: Option<i64>,
+    extensions: Vec<String>,
+}
+
+impl EntryFilter {
+    fn from_query(query: &SearchQuery) -> Result<Self, AppError> {
+        Ok(EntryFilter {
+            kind: query.kind,
+            min_size: query.min_size,
+            max_size: query.max_size,
+            modified_after: query.modified_after.as_deref().map(|v| parse_time_bound(v, true)).transpose()?,
+            modified_before: query.modified_before.as_deref().map(|v| parse_time_bound(v, false)).transpose()?,
+            extensions: parse_extensions(query.ext.as_deref()),
+        })
+    }
+
+    fn matches(&self, name: &str, is_dir: bool, size: u64, modified: Option<i64>) -> bool {
+        match self.kind {
+            Some(EntryKind::File) if is_dir => return false,
+            Some(EntryKind::Dir) if !is_dir => return false,
+            _ => {}
+        }
+
+        // 大小和扩展名只对文件有意义，设置了这些条件时不返回目录
+        let file_only = self.min_size.is_some() || self.max_size.is_some() || !self.extensions.is_empty();
+        if file_only && is_dir {
+            return false;
+        }
+
+        if self.min_size.is_some_and(|min| size < min) || self.max_size.is_some_and(|max| size > max) {
+            return false;
+        }
+
+        if self.modified_after.is_some() || self.modified_before.is_some() {
+            let Some(mtime) = modified else {
+                return false;
+            };
+            if self.modified_after.is_some_and(|after| mtime < after)
+                || self.modified_before.is_some_and(|before| mtime > before)
+            {
+                return false;
+            }
+        }
+
+        self.extensions.is_empty() || self.extensions.contains(&file_extension(name))
+    }
+}
+
+// 解析逗号分隔的扩展名列表，统一为不带点的小写形式
+pub(crate) fn parse_extensions(value: Option<&str>) -> Vec<String> {
+    value
+        .unwrap_or("")
+        .split(',')
+        .map(|ext| ext.trim().trim_start_matches('.').to_lowercase())
+        .filter(|ext| !ext.is_empty())
+        .collect()
+}
+
+pub(crate) fn file_extension(name: &str) -> String {
+    Path::new(name)
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_lowercase())
+        .unwrap_or_default()
+}
+
+// 只有日期的下限按当天0点计算，上限按当天结束计算
+fn parse_time_bound(value: &str, start_of_day: bool) -> Result<i64, AppError> {
+    let value = value.trim();
+    let parsed = if start_of_day && !value.contains(' ') {
+        parse_datetime(&format!("{} 00:00:00", value))
+    } else {
+        parse_datetime(value)
+    };
+    parsed.ok_or_else(|| AppError::InvalidRequest(format!("Invalid date: {}", value)))
+}
+
+// 解析搜索起点，要求是根目录内有读权限的目录
+pub(crate) fn resolve_search_root(state: &AppState, user: &AuthUser, path: &str) -> Result<(PathBuf, String), AppError> {
+    let safe_path = sanitize_path(path);
+    let full_path = if safe_path.is_empty() {
+        state.base_dir.clone()
+    } else {
+        state.base_dir.join(&safe_path)
+    };
+
+    if !is_safe_path(&state.base_dir, &full_path) {
+        return Err(AppError::PermissionDenied("Access denied".to_string()));
+    }
+
+    state.acl.check(user, &safe_path, Permission::Read)?;
+
+    if !full_path.exists() {
+        return Err(AppError::NotFound("Path not found".to_string()));
+    }
+
+    if !full_path.is_dir() {
+        return Err(AppError::InvalidPath("Not a directory".to_string()));
+    }
+
+    // 起点本身是指向根目录外的符号链接时拒绝
+    let canonical_base = std::fs::canonicalize(&state.base_dir)?;
+    if !std::fs::canonicalize(&full_path)?.starts_with(&canonical_base) {
+        return Err(AppError::PermissionDenied("Access denied".to_string()));
+    }
+
+    Ok((full_path, safe_path))
+}
+
+// 把一个值序列化为一行NDJSON发送，客户端断开时返回false
+pub(crate) fn send_line<T: Serialize>(tx: &mpsc::Sender<io::Result<Bytes>>, value: &T) -> bool {
+    let mut line = match serde_json::to_vec(value) {
+        Ok(line) => line,
+        Err(e) => {
+            tracing::error!("Failed to serialize search result: {}", e);
+            return true;
+        }
+    };
+    line.push(b'\n');
+    tx.blocking_send(Ok(Bytes::from(line))).is_ok()
+}
+
+pub(crate) fn ndjson_response(mut rx: mpsc::Receiver<io::Result<Bytes>>) -> Response {
+    let stream = futures_util::stream::poll_fn(move |cx| rx.poll_recv(cx));
+    (
+        [(header::CONTENT_TYPE, "application/x-ndjson")],
+        Body::from_stream(stream),
+    )
+        .into_response()
+}
+
+// 按文件名递归搜索，结果以NDJSON逐行返回
+pub async fn search_files(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Query(query): Query<SearchQuery>,
+) -> Result<Response, AppError> {
+    let (root, root_rel) = resolve_search_root(&state, &user, &query.path)?;
+    let matcher = NameMatcher::new(&query.q, query.mode, query.case_sensitive)?;
+    let filter = EntryFilter::from_query(&query)?;
+    let limit = query.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    let max_depth = query.depth.map(|d| d.max(1));
+
+    tracing::info!("{} searching {} for {:?} ({:?})", user, root_rel, query.q, query.mode);
+
+    let mut walker = TreeWalker::new(&state, user.clone(), max_depth)?;
+    let (tx, rx) = mpsc::channel::<io::Result<Bytes>>(32);
+
+    tokio::task::spawn_blocking(move || {
+        let mut matched = 0;
+        let mut truncated = false;
+
+        let mut emit = |entry: FileEntry| {
+            // 已达到上限时再遇到匹配项，说明结果被截断
+            if matched >= limit {
+                truncated = true;
+                return ControlFlow::Break(());
+            }
+            matched += 1;
+            if send_line(&tx, &entry) {
+                ControlFlow::Continue(())
+            } else {
+                ControlFlow::Break(())
+            }
+        };
+
+        let mut visibility = IndexVisibility::new(&state, &user, &root_rel, max_depth);
+        let indexed = state.index.for_each_under_blocking(&root_rel, |entry| {
+            if visibility.is_visible(entry)
+                && matcher.is_match(&entry.name)
+                && filter.matches(&entry.name, entry.is_dir, entry.size, Some(entry.modified))
+            {
+                emit(entry.to_file_entry())
+            } else {
+                ControlFlow::Continue(())
+            }
+        });
+        let flow = match indexed {
+            Some(flow) => flow,
+            None => walker.walk(&root, &root_rel, &mut |path, _rel, metadata| {
+                let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+                let modified = metadata
+                    .modified()
+                    .ok()
+                    .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+                    .map(|d| d.as_secs() as i64);
+                if !matcher.is_match(name) || !filter.matches(name, metadata.is_dir(), metadata.len(), modified) {
+                    return ControlFlow::Continue(());
+                }
+                match file_entry_from_metadata(path, &state.base_dir, metadata) {
+                    Ok(entry) => emit(entry),
+                    Err(_) => ControlFlow::Continue(()),
+                }
+            }),
+        };
+
+        if flow.is_continue() || truncated {
+            send_line(&tx, &SearchSummary { done: true, matched, truncated });
+        }
+    });
+
+    Ok(ndjson_response(rx))
+}
+
+// 判断索引条目在遍历目录时是否可见：在深度限制内、各级目录都有读权限、符号链接不指向根目录外
+struct IndexVisibility<'a> {
+    state: &'a AppState,
+    user: &'a AuthUser,
+    root_rel: &'a str,
+    max_depth: Option<usize>,
+    canonical_base: PathBuf,
+    // 已检查过的上级目录 -> 是否可读
+    readable: HashMap<String, bool>,
+}
+
+impl<'a> IndexVisibility<'a> {
+    fn new(state: &'a AppState, user: &'a AuthUser, root_rel: &'a str, max_depth: Option<usize>) -> Self {
+        IndexVisibility {
+            state,
+            user,
+            root_rel,
+            max_depth,
+            canonical_base: std::fs::canonicalize(&state.base_dir).unwrap_or_else(|_| state.base_dir.clone()),
+            readable: HashMap::new(),
+        }
+    }
+
+    fn is_visible(&mut self, entry: &IndexedEntry) -> bool {
+        let relative = match self.root_rel {
+            "" => entry.path.as_str(),
+            root => entry.path.strip_prefix(root).unwrap_or(&entry.path).trim_start_matches('/'),
+        };
+        let depth = relative.split('/').count();
+        if self.max_depth.is_some_and(|max| depth > max) {
+            return false;
+        }
+
+        // 逐级检查从搜索起点到条目本身的读权限，只缓存上级目录的结果
+        let (parents, _) = relative.rsplit_once('/').unwrap_or(("", relative));
+        let mut prefix = self.root_rel.to_string();
+        for component in parents.split('/').filter(|c| !c.is_empty()) {
+            prefix = join_relative_path(&prefix, component);
+            let allowed = match self.readable.get(&prefix) {
+                Some(allowed) => *allowed,
+                None => {
+                    let allowed = self.state.acl.allows(self.user, &prefix, Permission::Read);
+                    self.readable.insert(prefix.clone(), allowed);
+                    allowed
+                }
+            };
+            if !allowed {
+                return false;
+            }
+        }
+        if !self.state.acl.allows(self.user, &entry.path, Permission::Read) {
+            return false;
+        }
+
+        !entry.is_link
+            || std::fs::canonicalize(self.state.base_dir.join(&entry.path))
+                .is_ok_and(|target| target.starts_with(&self.canonical_base))
+    }
+}
+
+// 根据扩展名判断是否为文本文件，未知扩展名时检查开头是否含有NUL字节或非法UTF-8
+fn is_text(path: &Path, head: &[u8]) -> bool {
+    if let Some(mime) = get_mime_type(path) {
+        return mime.starts_with("text/")
+            || matches!(mime.as_str(), "application/json" | "application/javascript" | "image/svg+xml");
+    }
+
+    if head.contains(&0) {
+        return false;
+    }
+    match std::str::from_utf8(head) {
+        Ok(_) => true,
+        // 末尾被截断的多字节字符不算非法
+        Err(e) => e.error_len().is_none(),
+    }
+}
+
+fn floor_boundary(text: &str, mut index: usize) -> usize {
+    while !text.is_char_boundary(index) {
+        index -= 1;
+    }
+    index
+}
+
+fn ceil_boundary(text: &str, index: usize) -> usize {
+    let mut index = index.min(text.len());
+    while !text.is_char_boundary(index) {
+        index += 1;
+    }
+    index
+}
+
+// 截取匹配内容及其前后的一段文本，过长的行用省略号表示截断
+fn make_snippet(line: &str, start: usize, end: usize) -> String {
+    let end = ceil_boundary(line, end.min(start + SNIPPET_MATCH_MAX));
+    let from = floor_boundary(line, start.saturating_sub(SNIPPET_CONTEXT));
+    let to = ceil_boundary(line, end + SNIPPET_CONTEXT);
+
+    let mut snippet = String::new();
+    if from > 0 {
+        snippet.push('…');
+    }
+    snippet.push_str(&line[from..to]);
+    if to < line.len() {
+        snippet.push('…');
+    }
+    snippet
+}
+
+// 内容搜索任务，在阻塞线程中运行
+struct ContentScan {
+    regex: Regex,
+    extensions: Vec<String>,
+    max_file_size: u64,
+    limit: usize,
+    deadline: Instant,
+    tx: mpsc::Sender<io::Result<Bytes>>,
+    matched: usize,
+    files_scanned: usize,
+    truncated: bool,
+    timed_out: bool,
+}
+
+impl ContentScan {
+    fn visit(&mut self, path: &Path, rel_path: &str, metadata: &Metadata) -> ControlFlow<()> {
+        if Instant::now() >= self.deadline {
+            self.timed_out = true;
+            return ControlFlow::Break(());
+        }
+
+        if metadata.is_dir() || metadata.len() > self.max_file_size {
+            return ControlFlow::Continue(());
+        }
+
+        let name = path.file_name().and_then(|n| n.to_str()).unwrap_or("");
+        if !self.extensions.is_empty() && !self.extensions.contains(&file_extension(name)) {
+            return ControlFlow::Continue(());
+        }
+
+        match self.scan_file(path, rel_path) {
+            Ok(flow) => flow,
+            Err(e) => {
+                tracing::debug!("Failed to scan {:?}: {}", path, e);
+                ControlFlow::Continue(())
+            }
+        }
+    }
+
+    fn scan_file(&mut self, path: &Path, rel_path: &str) -> io::Result<ControlFlow<()>> {
+        let mut file = File::open(path)?;
+        let mut head = Vec::with_capacity(SNIFF_SIZE);
+        (&mut file).take(SNIFF_SIZE as u64).read_to_end(&mut head)?;
+        if !is_text(path, &head) {
+            return Ok(ControlFlow::Continue(()));
+        }
+        self.files_scanned += 1;
+
+        let mut reader = BufReader::new(io::Cursor::new(head).chain(file));
+        let mut buf = Vec::new();
+        let mut line_number = 0;
+
+        loop {
+            buf.clear();
+            if reader.read_until(b'\n', &mut buf)? == 0 {
+                break;
+            }
+            line_number += 1;
+
+            // 每隔一段检查一次超时，避免单个大文件拖住整个搜索
+            if line_number % 1024 == 0 && Instant::now() >= self.deadline {
+                self.timed_out = true;
+                return Ok(ControlFlow::Break(()));
+            }
+
+            let line = String::from_utf8_lossy(&buf);
+            let line = line.trim_end_matches(['\n', '\r']);
+            let Some(found) = self.regex.find(line) else {
+                continue;
+            };
+
+            if self.matched >= self.limit {
+                self.truncated = true;
+                return Ok(ControlFlow::Break(()));
+            }
+            self.matched += 1;
+
+            let hit = ContentMatch {
+                path: rel_path.to_string(),
+                line: line_number,
+                snippet: make_snippet(line, found.start(), found.end()),
+            };
+            if !send_line(&self.tx, &hit) {
+                return Ok(ControlFlow::Break(()));
+            }
+        }
+
+        Ok(ControlFlow::Continue(()))
+    }
+}
+
+// 在文本文件内容中搜索字面文本或正则表达式，结果以NDJSON逐行返回
+pub async fn search_content(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Query(query): Query<ContentSearchQuery>,
+) -> Result<Response, AppError> {
+    if query.q.is_empty() {
+        return Err(AppError::InvalidRequest("Search pattern is required".to_string()));
+    }
+
+    let (root, root_rel) = resolve_search_root(&state, &user, &query.path)?;
+    let regex = if query.regex {
+        build_regex(&query.q, query.case_sensitive)?
+    } else {
+        build_regex(&regex::escape(&query.q), query.case_sensitive)?
+    };
+    let limits = state.search;
+    let limit = query.limit.unwrap_or(limits.max_matches).clamp(1, limits.max_matches);
+    let max_depth = query.depth.map(|d| d.max(1));
+
+    tracing::info!("{} searching content in {} for {:?}", user, root_rel, query.q);
+
+    let mut walker = TreeWalker::new(&state, user, max_depth)?;
+    let (tx, rx) = mpsc::channel::<io::Result<Bytes>>(32);
+    let mut scan = ContentScan {
+        regex,
+        extensions: parse_extensions(query.ext.as_deref()),
+        max_file_size: limits.max_file_size,
+        limit,
+        deadline: Instant::now() + limits.timeout,
+        tx,
+        matched: 0,
+        files_scanned: 0,
+        truncated: false,
+        timed_out: false,
+    };
+
+    tokio::task::spawn_blocking(move || {
+        let flow = walker.walk(&root, &root_rel, &mut |path, rel, metadata| scan.visit(path, rel, metadata));
+
+        if flow.is_continue() || scan.truncated || scan.timed_out {
+            if scan.timed_out {
+                tracing::warn!("Content search in {} timed out", root_rel);
+            }
+            let summary = ContentSearchSummary {
+                done: true,
+                matched: scan.matched,
+                files_scanned: scan.files_scanned,
+                truncated: scan.truncated,
+                timed_out: scan.timed_out,
+            };
+            send_line(&scan.tx, &summary);
+        }
+    });
+
+    Ok(ndjson_response(rx))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::test_state;
+
+    async fn search(state: &Arc<AppState>, query: serde_json::Value) -> Vec<serde_json::Value> {
+        let query: SearchQuery = serde_json::from_value(query).unwrap();
+        let response = search_files(State(state.clone()), AuthUser::anonymous(), Query(query))
+            .await
+            .unwrap();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        body.split(|b| *b == b'\n')
+            .filter(|line| !line.is_empty())
+            .map(|line| serde_json::from_slice(line).unwrap())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn indexed_search_respects_acl_and_limit() {
+        let config = serde_json::json!({
+            "index": { "enabled": true },
+            "acl": { "default": ["read"], "rules": [{ "path": "hidden", "permissions": [] }] }
+        });
+        let (state, _dir) = test_state(config).await;
+        for path in ["docs/x1", "docs/x2", "docs/x3", "hidden/x4", "other"] {
+            let full_path = state.base_dir.join(path);
+            std::fs::create_dir_all(full_path.parent().unwrap()).unwrap();
+            std::fs::write(full_path, "").unwrap();
+        }
+        state.index.sync_all(true).await.unwrap();
+
+        let lines = search(&state, serde_json::json!({ "q": "x" })).await;
+        let paths: Vec<&str> = lines[..lines.len() - 1].iter().map(|l| l["path"].as_str().unwrap()).collect();
+        assert_eq!(paths, ["docs/x1", "docs/x2", "docs/x3"]);
+        assert_eq!(lines.last().unwrap()["truncated"], false);
+
+        let lines = search(&state, serde_json::json!({ "q": "x", "limit": 2 })).await;
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[2]["matched"], 2);
+        assert_eq!(lines[2]["truncated"], true);
+    }
+}
@@ -0,0 +1,437 @@
+use axum::{
+    extract::{Multipart, Path, State},
+    http::{header, HeaderMap, HeaderName},
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use tokio::fs;
+
+use crate::{
+    acl::Permission,
+    auth::{self, AuthUser},
+    errors::AppError,
+    handlers::{list_directory, parse_range_header, send_file, upload_file_impl},
+    listing,
+    models::*,
+    utils::*,
+    AppState,
+};
+
+const SHARE_PASSWORD: HeaderName = HeaderName::from_static("x-share-password");
+
+// 持久化的分享记录
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct ShareRecord {
+    token: String,
+    path: String,
+    is_dir: bool,
+    mode: ShareMode,
+    password_hash: Option<String>,
+    expires: Option<i64>,
+    max_downloads: Option<u64>,
+    downloads: u64,
+    created_by: String,
+    created: i64,
+}
+
+impl ShareRecord {
+    fn info(&self) -> ShareInfo {
+        ShareInfo {
+            token: self.token.clone(),
+            url: format!("/s/{}", self.token),
+            path: self.path.clone(),
+            is_dir: self.is_dir,
+            mode: self.mode,
+            has_password: self.password_hash.is_some(),
+            expires: self.expires.map(format_timestamp),
+            max_downloads: self.max_downloads,
+            downloads: self.downloads,
+            created_by: self.created_by.clone(),
+            created: format_timestamp(self.created),
+        }
+    }
+
+    fn is_active(&self, now: i64) -> bool {
+        self.expires.is_none_or(|expires| expires > now)
+            && self.max_downloads.is_none_or(|max| self.downloads < max)
+    }
+}
+
+pub struct ShareStore {
+    file: PathBuf,
+    // token -> 记录
+    shares: Mutex<HashMap<String, ShareRecord>>,
+}
+
+impl ShareStore {
+    pub async fn new(file: PathBuf) -> anyhow::Result<Self> {
+        let shares = match fs::read_to_string(&file).await {
+            Ok(content) => serde_json::from_str::<Vec<ShareRecord>>(&content)?
+                .into_iter()
+                .map(|record| (record.token.clone(), record))
+                .collect(),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => return Err(e.into()),
+        };
+
+        Ok(ShareStore {
+            file,
+            shares: Mutex::new(shares),
+        })
+    }
+
+    async fn persist(&self) -> Result<(), AppError> {
+        let content = {
+            let shares = self.shares.lock().unwrap();
+            let mut records: Vec<&ShareRecord> = shares.values().collect();
+            records.sort_by_key(|r| r.created);
+            serde_json::to_vec_pretty(&records).map_err(anyhow::Error::from)?
+        };
+
+        write_file_atomic(&self.file, &content).await?;
+        Ok(())
+    }
+
+    fn get(&self, token: &str) -> Option<ShareRecord> {
+        self.shares.lock().unwrap().get(token).cloned()
+    }
+
+    // 下载计数：超过次数上限时返回错误
+    async fn record_download(&self, token: &str) -> Result<(), AppError> {
+        {
+            let mut shares = self.shares.lock().unwrap();
+            let record = shares
+                .get_mut(token)
+                .ok_or_else(|| AppError::NotFound("Share not found".to_string()))?;
+            if record.max_downloads.is_some_and(|max| record.downloads >= max) {
+                return Err(AppError::NotFound("Share download limit reached".to_string()));
+            }
+            record.downloads += 1;
+        }
+        self.persist().await
+    }
+}
+
+// 分享创建者的身份，访问分享时仍按创建者的权限检查
+fn share_owner(state: &AppState, share: &ShareRecord) -> Result<AuthUser, AppError> {
+    if !state.auth.enabled() {
+        return Ok(AuthUser::anonymous());
+    }
+    state
+        .auth
+        .user(&share.created_by)
+        .ok_or_else(|| AppError::NotFound("Share not found".to_string()))
+}
+
+// 创建分享链接
+pub async fn create_share(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Json(req): Json<CreateShareRequest>,
+) -> Result<Json<ApiResponse<ShareInfo>>, AppError> {
+    // 分享按创建者身份访问，不能让受限的API令牌借此扩大权限
+    if user.token.is_some() {
+        return Err(AppError::PermissionDenied("API tokens cannot create shares".to_string()));
+    }
+
+    let safe_path = sanitize_path(&req.path);
+    let full_path = state.base_dir.join(&safe_path);
+
+    if !is_safe_path(&state.base_dir, &full_path) {
+        return Err(AppError::PermissionDenied("Access denied".to_string()));
+    }
+
+    if !full_path.exists() {
+        return Err(AppError::NotFound("Path not found".to_string()));
+    }
+
+    let is_dir = full_path.is_dir();
+    match req.mode {
+        ShareMode::Read => state.acl.check(&user, &safe_path, Permission::Read)?,
+        ShareMode::Upload => {
+            if !is_dir {
+                return Err(AppError::InvalidRequest(
+                    "Upload shares must point to a directory".to_string(),
+                ));
+            }
+            state.acl.check(&user, &safe_path, Permission::Write)?
+        }
+    }
+
+    let expires = match req.expires_at.as_deref() {
+        Some(value) => {
+            let expires = parse_datetime(value)
+                .ok_or_else(|| AppError::InvalidRequest(format!("Invalid expires_at: {}", value)))?;
+            if expires <= Utc::now().timestamp() {
+                return Err(AppError::InvalidRequest("expires_at is in the past".to_string()));
+            }
+            Some(expires)
+        }
+        None => None,
+    };
+
+    let password_hash = match req.password.as_deref().filter(|p| !p.is_empty()) {
+        Some(password) => Some(auth::hash_password(password)?),
+        None => None,
+    };
+
+    let record = ShareRecord {
+        token: uuid::Uuid::new_v4().simple().to_string(),
+        path: safe_path,
+        is_dir,
+        mode: req.mode,
+        password_hash,
+        expires,
+        max_downloads: req.max_downloads,
+        downloads: 0,
+        created_by: user.username.clone(),
+        created: Utc::now().timestamp(),
+    };
+    let info = record.info();
+
+    state
+        .shares
+        .shares
+        .lock()
+        .unwrap()
+        .insert(record.token.clone(), record);
+    state.shares.persist().await?;
+
+    tracing::info!("{} shared /{} as {}", user, info.path, info.url);
+    Ok(Json(ApiResponse::success(info)))
+}
+
+// 列出分享链接：管理员可以看到所有分享，其他用户只能看到自己创建的
+pub async fn list_shares(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+) -> Result<Json<ApiResponse<Vec<ShareInfo>>>, AppError> {
+    let now = Utc::now().timestamp();
+    let mut records: Vec<ShareRecord> = state
+        .shares
+        .shares
+        .lock()
+        .unwrap()
+        .values()
+        .filter(|r| user.is_admin || r.created_by == user.username)
+        .filter(|r| r.is_active(now))
+        .cloned()
+        .collect();
+    records.sort_by_key(|r| r.created);
+
+    Ok(Json(ApiResponse::success(
+        records.iter().map(ShareRecord::info).collect(),
+    )))
+}
+
+// 撤销分享链接
+pub async fn revoke_share(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Path(token): Path<String>,
+) -> Result<Json<ApiResponse<()>>, AppError> {
+    let removed = {
+        let mut shares = state.shares.shares.lock().unwrap();
+        match shares.get(&token) {
+            Some(r) if user.is_admin || r.created_by == user.username => shares.remove(&token),
+            _ => None,
+        }
+    };
+
+    let record = removed.ok_or_else(|| AppError::NotFound("Share not found".to_string()))?;
+    state.shares.persist().await?;
+
+    tracing::info!("{} revoked share of /{}", user, record.path);
+    Ok(Json(ApiResponse::success(())))
+}
+
+// 校验分享链接是否有效及访问密码，返回分享记录和创建者身份
+async fn open_share(
+    state: &AppState,
+    token: &str,
+    headers: &HeaderMap,
+) -> Result<(ShareRecord, AuthUser), AppError> {
+    let share = state
+        .shares
+        .get(token)
+        .ok_or_else(|| AppError::NotFound("Share not found".to_string()))?;
+
+    if !share.is_active(Utc::now().timestamp()) {
+        return Err(AppError::NotFound("Share link has expired".to_string()));
+    }
+
+    if let Some(hash) = &share.password_hash {
+        let password = headers
+            .get(&SHARE_PASSWORD)
+            .and_then(|v| v.to_str().ok())
+            .ok_or_else(|| AppError::Unauthorized("Share password required".to_string()))?;
+        if !auth::verify_password_hash(hash, password).await {
+            return Err(AppError::Unauthorized("Invalid share password".to_string()));
+        }
+    }
+
+    let owner = share_owner(state, &share)?;
+    Ok((share, owner))
+}
+
+// 把分享内的子路径解析为相对base_dir的路径，确保不会越出分享目录
+fn resolve_share_path(state: &AppState, share: &ShareRecord, sub_path: &str) -> Result<String, AppError> {
+    let sub_path = sanitize_path(sub_path);
+    if sub_path.is_empty() {
+        return Ok(share.path.clone());
+    }
+    if !share.is_dir {
+        return Err(AppError::NotFound("File not found".to_string()));
+    }
+
+    let share_root = state.base_dir.join(&share.path);
+    if !is_safe_path(&share_root, &share_root.join(&sub_path)) {
+        return Err(AppError::PermissionDenied("Access denied".to_string()));
+    }
+    Ok(join_relative_path(&share.path, &sub_path))
+}
+
+async fn share_get_impl(
+    state: Arc<AppState>,
+    token: &str,
+    sub_path: &str,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let (share, owner) = open_share(&state, token, &headers).await?;
+    if share.mode != ShareMode::Read {
+        return Err(AppError::PermissionDenied("This share only accepts uploads".to_string()));
+    }
+
+    let rel_path = resolve_share_path(&state, &share, sub_path)?;
+    let full_path = state.base_dir.join(&rel_path);
+    if !is_safe_path(&state.base_dir, &full_path) {
+        return Err(AppError::PermissionDenied("Access denied".to_string()));
+    }
+
+    state.acl.check(&owner, &rel_path, Permission::Read)?;
+
+    if !full_path.exists() {
+        return Err(AppError::NotFound("File not found".to_string()));
+    }
+
+    if full_path.is_dir() {
+        let share_root = state.base_dir.join(&share.path);
+        let entries = list_directory(&full_path, &share_root).await?;
+        let listing = listing::paginate(entries, &ListQuery::default())?;
+        return Ok(Json(ApiResponse::success(listing)).into_response());
+    }
+
+    // 起始位置大于0的Range请求是续传，不重复计数；无法解析的Range会返回整个文件，照常计数
+    let file_size = fs::metadata(&full_path).await?.len();
+    let is_continuation = headers
+        .get(header::RANGE)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| parse_range_header(v, file_size))
+        .is_some_and(|(start, _)| start > 0);
+    if !is_continuation {
+        state.shares.record_download(token).await?;
+    }
+
+    send_file(&full_path, &headers).await
+}
+
+async fn share_upload_impl(
+    state: Arc<AppState>,
+    token: &str,
+    sub_path: &str,
+    headers: HeaderMap,
+    multipart: Multipart,
+) -> Result<Json<ApiResponse<serde_json::Value>>, AppError> {
+    let (share, owner) = open_share(&state, token, &headers).await?;
+    if share.mode != ShareMode::Upload {
+        return Err(AppError::PermissionDenied("This share is read-only".to_string()));
+    }
+
+    // 收件箱只能添加文件，同名文件自动改名，不能覆盖已有内容
+    let rel_path = resolve_share_path(&state, &share, sub_path)?;
+    upload_file_impl(state, &owner, &rel_path, multipart, false).await
+}
+
+// 访问分享（文件下载或目录列表）
+pub async fn share_root(
+    State(state): State<Arc<AppState>>,
+    Path(token): Path<String>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    share_get_impl(state, &token, "", headers).await
+}
+
+// 访问分享目录下的子路径
+pub async fn share_path(
+    State(state): State<Arc<AppState>>,
+    Path((token, path)): Path<(String, String)>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    share_get_impl(state, &token, &path, headers).await
+}
+
+// 上传到分享目录（收件箱模式）
+pub async fn share_upload_root(
+    State(state): State<Arc<AppState>>,
+    Path(token): Path<String>,
+    headers: HeaderMap,
+    multipart: Multipart,
+) -> Result<Json<ApiResponse<serde_json::Value>>, AppError> {
+    share_upload_impl(state, &token, "", headers, multipart).await
+}
+
+// 上传到分享目录的子目录
+pub async fn share_upload_path(
+    State(state): State<Arc<AppState>>,
+    Path((token, path)): Path<(String, String)>,
+    headers: HeaderMap,
+    multipart: Multipart,
+) -> Result<Json<ApiResponse<serde_json::Value>>, AppError> {
+    share_upload_impl(state, &token, &path, headers, multipart).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::test_state;
+    use axum::{body::Body, extract::FromRequest, http::Request};
+
+    fn multipart(file_name: &str, content: &str) -> Request<Body> {
+        let body = format!(
+            "--X\r\nContent-Disposition: form-data; name=\"file\"; filename=\"{}\"\r\n\r\n{}\r\n--X--\r\n",
+            file_name, content
+        );
+        Request::builder()
+            .header(header::CONTENT_TYPE, "multipart/form-data; boundary=X")
+            .body(Body::from(body))
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn upload_share_does_not_overwrite() {
+        let (state, _dir) = test_state(serde_json::Value::Null).await;
+        std::fs::create_dir(state.base_dir.join("inbox")).unwrap();
+        std::fs::write(state.base_dir.join("inbox/a.txt"), "original").unwrap();
+
+        let req = serde_json::from_value(serde_json::json!({ "path": "inbox", "mode": "upload" })).unwrap();
+        let Json(response) = create_share(State(state.clone()), AuthUser::anonymous(), Json(req))
+            .await
+            .unwrap();
+        let token = response.data.unwrap().token;
+
+        for expected in ["a (1).txt", "a (2).txt"] {
+            let multipart = Multipart::from_request(multipart("a.txt", "new"), &()).await.unwrap();
+            let Json(response) = share_upload_impl(state.clone(), &token, "", HeaderMap::new(), multipart)
+                .await
+                .unwrap();
+            assert_eq!(response.data.unwrap()["uploaded"], serde_json::json!([expected]));
+            assert_eq!(std::fs::read_to_string(state.base_dir.join("inbox").join(expected)).unwrap(), "new");
+        }
+        assert_eq!(std::fs::read_to_string(state.base_dir.join("inbox/a.txt")).unwrap(), "original");
+    }
+}
@@ -0,0 +1,43 @@
+// 单元测试共用的辅助函数：在临时目录中创建完整的服务状态
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use crate::{build_state, AppState};
+
+// 测试用的临时目录，drop时删除
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    pub fn new() -> Self {
+        static COUNTER: AtomicU64 = AtomicU64::new(0);
+        let path = std::env::temp_dir().join(format!(
+            "file-server-test-{}-{}",
+            std::process::id(),
+            COUNTER.fetch_add(1, Ordering::Relaxed)
+        ));
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).unwrap();
+        TempDir(path)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.0);
+    }
+}
+
+// 文件根目录为临时目录下的files，数据目录为data；config中未给出的部分使用默认值
+pub async fn test_state(config: serde_json::Value) -> (Arc<AppState>, TempDir) {
+    let dir = TempDir::new();
+    let mut config = if config.is_null() { serde_json::json!({}) } else { config };
+    config["dir"] = dir.path().join("files").to_string_lossy().into();
+    config["data_dir"] = dir.path().join("data").to_string_lossy().into();
+    let state = build_state(&config).await.unwrap();
+    (state, dir)
+}
@@ -0,0 +1,322 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::{IntoResponse, Response},
+};
+use image::{
+    codecs::jpeg::JpegEncoder, metadata::Orientation, DynamicImage, ImageDecoder, ImageError, ImageFormat,
+    ImageReader,
+};
+use std::io::Cursor;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::fs;
+use tokio::sync::Semaphore;
+
+use crate::{
+    acl::{self, Permission},
+    auth::AuthUser,
+    errors::AppError,
+    models::*,
+    utils::*,
+    AppState,
+};
+
+// 支持生成缩略图的图片格式
+const SUPPORTED_FORMATS: &[ImageFormat] = &[
+    ImageFormat::Jpeg,
+    ImageFormat::Png,
+    ImageFormat::Gif,
+    ImageFormat::WebP,
+    ImageFormat::Bmp,
+];
+
+const DEFAULT_SIZES: &[u32] = &[128, 256, 512, 1024];
+const DEFAULT_SIZE: u32 = 256;
+const MIN_SIZE: u32 = 16;
+const MAX_SIZE: u32 = 4096;
+
+// 缩略图地址不随源文件变化，浏览器每次使用前通过ETag确认
+const CACHE_CONTROL: &str = "private, no-cache";
+
+// 图片缩略图的生成和磁盘缓存
+pub struct ThumbnailStore {
+    dir: PathBuf,
+    enabled: bool,
+    // 允许的边长（像素），请求的尺寸向上取到其中之一
+    sizes: Vec<u32>,
+    quality: u8,
+    max_source_size: u64,
+    // 限制同时解码的图片数，解码大图片占用大量CPU和内存
+    permits: Semaphore,
+}
+
+impl ThumbnailStore {
+    pub async fn new(dir: PathBuf, config: &serde_json::Value) -> anyhow::Result<Self> {
+        fs::create_dir_all(&dir).await?;
+
+        let mut sizes: Vec<u32> = config["sizes"]
+            .as_array()
+            .map(|sizes| {
+                sizes
+                    .iter()
+                    .filter_map(|size| size.as_u64())
+                    .map(|size| size.clamp(MIN_SIZE as u64, MAX_SIZE as u64) as u32)
+                    .collect()
+            })
+            .unwrap_or_default();
+        if sizes.is_empty() {
+            sizes = DEFAULT_SIZES.to_vec();
+        }
+        sizes.sort_unstable();
+        sizes.dedup();
+
+        let max_concurrent = config["max_concurrent"]
+            .as_u64()
+            .map(|n| n.max(1) as usize)
+            .unwrap_or_else(|| {
+                std::thread::available_parallelism()
+                    .map(|n| n.get())
+                    .unwrap_or(1)
+                    .min(4)
+            });
+
+        Ok(ThumbnailStore {
+            dir,
+            enabled: config["enabled"].as_bool().unwrap_or(true),
+            sizes,
+            quality: config["quality"].as_u64().unwrap_or(80).clamp(1, 100) as u8,
+            max_source_size: config["max_source_size_mb"].as_u64().unwrap_or(50) * 1024 * 1024,
+            permits: Semaphore::new(max_concurrent),
+        })
+    }
+
+    fn pick_size(&self, requested: Option<u32>) -> u32 {
+        let requested = requested.unwrap_or(DEFAULT_SIZE);
+        self.sizes
+            .iter()
+            .copied()
+            .find(|&size| size >= requested)
+            .or_else(|| self.sizes.last().copied())
+            .unwrap_or(DEFAULT_SIZE)
+    }
+
+    // 每个源文件的缩略图保存在以其规范化后的相对路径命名的目录中，
+    // 删除目录时整个子树的缩略图一并失效
+    fn cache_dir(&self, rel_path: &str) -> PathBuf {
+        self.dir.join(acl::path_components(rel_path).join("/"))
+    }
+
+    // 源文件（或目录）删除、移走后删除它的缩略图；内容变化时缓存键随之变化，无需在此处理
+    pub async fn invalidate(&self, rel_path: &str) {
+        if acl::path_components(rel_path).is_empty() {
+            return;
+        }
+        match fs::remove_dir_all(self.cache_dir(rel_path)).await {
+            Ok(_) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+            Err(e) => tracing::warn!("Failed to remove thumbnails of {}: {}", rel_path, e),
+        }
+    }
+
+    async fn load_or_render(
+        &self,
+        source: &std::path::Path,
+        rel_path: &str,
+        key: &str,
+        size: u32,
+    ) -> Result<Vec<u8>, AppError> {
+        let cache_dir = self.cache_dir(rel_path);
+        if let Ok(data) = fs::read(cache_dir.join(key)).await {
+            return Ok(data);
+        }
+
+        let _permit = self
+            .permits
+            .acquire()
+            .await
+            .map_err(|e| AppError::IoError(e.to_string()))?;
+
+        // 等待期间其他请求可能已经生成了同一缩略图
+        if let Ok(data) = fs::read(cache_dir.join(key)).await {
+            return Ok(data);
+        }
+
+        let source = source.to_path_buf();
+        let quality = self.quality;
+        let data = tokio::task::spawn_blocking(move || render(&source, size, quality))
+            .await
+            .map_err(|e| AppError::IoError(e.to_string()))??;
+
+        // 写入缓存失败只影响后续请求的速度
+        if let Err(e) = store(&cache_dir, key, &data).await {
+            tracing::warn!("Failed to cache thumbnail of {}: {}", rel_path, e);
+        }
+        Ok(data)
+    }
+}
+
+// 保存缩略图，同时删除同一源文件旧版本的缩略图
+async fn store(cache_dir: &std::path::Path, key: &str, data: &[u8]) -> std::io::Result<()> {
+    fs::create_dir_all(cache_dir).await?;
+
+    let version = key.split_once('-').map(|(_, version)| version).unwrap_or(key);
+    let mut entries = fs::read_dir(cache_dir).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let name = entry.file_name().to_string_lossy().to_string();
+        let current = name.split_once('-').map(|(_, v)| v) == Some(version);
+        // 跳过其他请求正在写入的临时文件
+        if !current && !name.starts_with('.') && entry.file_type().await?.is_file() {
+            let _ = fs::remove_file(entry.path()).await;
+        }
+    }
+
+    let target = cache_dir.join(key);
+    let guard = TempFileGuard::new(temp_path_for(&target));
+    fs::write(guard.path(), data).await?;
+    guard.commit(&target).await
+}
+
+// 解码图片、按EXIF方向旋转并缩小到size以内；有透明通道时输出PNG，否则输出JPEG
+fn render(source: &std::path::Path, size: u32, quality: u8) -> Result<Vec<u8>, AppError> {
+    let reader = ImageReader::open(source)?.with_guessed_format()?;
+    match reader.format() {
+        Some(format) if SUPPORTED_FORMATS.contains(&format) => {}
+        _ => return Err(AppError::UnsupportedMediaType("Unsupported image format".to_string())),
+    }
+
+    // 读取器默认限制解码时最多分配512MB内存
+    let mut decoder = reader.into_decoder().map_err(image_error)?;
+    let orientation = decoder.orientation().unwrap_or(Orientation::NoTransforms);
+    let mut image = DynamicImage::from_decoder(decoder).map_err(image_error)?;
+    image.apply_orientation(orientation);
+
+    // 不放大比目标尺寸小的图片
+    if image.width() > size || image.height() > size {
+        image = image.thumbnail(size, size);
+    }
+
+    let mut data = Vec::new();
+    if image.color().has_alpha() {
+        image
+            .to_rgba8()
+            .write_to(&mut Cursor::new(&mut data), ImageFormat::Png)
+            .map_err(image_error)?;
+    } else {
+        JpegEncoder::new_with_quality(&mut data, quality)
+            .encode_image(&image.to_rgb8())
+            .map_err(image_error)?;
+    }
+    Ok(data)
+}
+
+fn image_error(e: ImageError) -> AppError {
+    match e {
+        ImageError::IoError(e) => AppError::IoError(e.to_string()),
+        ImageError::Limits(_) => AppError::PayloadTooLarge("Image is too large for a thumbnail".to_string()),
+        ImageError::Unsupported(e) => AppError::UnsupportedMediaType(e.to_string()),
+        e => AppError::InvalidRequest(format!("Failed to decode image: {}", e)),
+    }
+}
+
+fn content_type(data: &[u8]) -> &'static str {
+    if data.starts_with(b"\x89PNG") {
+        "image/png"
+    } else {
+        "image/jpeg"
+    }
+}
+
+// 图片缩略图
+pub async fn thumbnail(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Path(path): Path<String>,
+    Query(query): Query<ThumbnailQuery>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let thumbnails = &state.thumbnails;
+    if !thumbnails.enabled {
+        return Err(AppError::NotFound("Thumbnails are disabled".to_string()));
+    }
+
+    let safe_path = sanitize_path(&path);
+    let full_path = state.base_dir.join(&safe_path);
+
+    if !is_safe_path(&state.base_dir, &full_path) {
+        return Err(AppError::PermissionDenied("Access denied".to_string()));
+    }
+
+    state.acl.check(&user, &safe_path, Permission::Read)?;
+
+    let metadata = fs::metadata(&full_path)
+        .await
+        .map_err(|_| AppError::NotFound("File not found".to_string()))?;
+    if metadata.is_dir() {
+        return Err(AppError::InvalidPath("Cannot create thumbnail of a directory".to_string()));
+    }
+    if metadata.len() > thumbnails.max_source_size {
+        return Err(AppError::PayloadTooLarge("Image is too large for a thumbnail".to_string()));
+    }
+
+    // 缓存键由尺寸和源文件的修改时间、大小组成，源文件变化后自动失效
+    let size = thumbnails.pick_size(query.size);
+    let key = format!("{}-{}", size, file_etag(&metadata).trim_matches('"'));
+    let etag = format!("\"{}\"", key);
+    let last_modified = format_http_date(modified_time(&metadata));
+
+    let not_modified = headers
+        .get(header::IF_NONE_MATCH)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.split(',').any(|tag| tag.trim() == etag || tag.trim() == "*"));
+    if not_modified {
+        return Ok((
+            StatusCode::NOT_MODIFIED,
+            [(header::ETAG, etag), (header::CACHE_CONTROL, CACHE_CONTROL.to_string())],
+        )
+            .into_response());
+    }
+
+    let data = thumbnails.load_or_render(&full_path, &safe_path, &key, size).await?;
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, content_type(&data).to_string()),
+            (header::CONTENT_LENGTH, data.len().to_string()),
+            (header::ETAG, etag),
+            (header::LAST_MODIFIED, last_modified),
+            (header::CACHE_CONTROL, CACHE_CONTROL.to_string()),
+        ],
+        data,
+    )
+        .into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+
+    #[tokio::test]
+    async fn invalidate_matches_normalized_paths() {
+        let dir = TempDir::new();
+        let store = ThumbnailStore::new(dir.path().join("thumbnails"), &serde_json::Value::Null)
+            .await
+            .unwrap();
+        let source = dir.path().join("b.png");
+        image::RgbImage::new(8, 8).save(&source).unwrap();
+
+        store.load_or_render(&source, "a//b.png", "k", 4).await.unwrap();
+        assert!(store.cache_dir("a/b.png").join("k").exists());
+
+        store.invalidate("./a/./b.png").await;
+        assert!(!store.cache_dir("a/b.png").exists());
+
+        // 目录失效时其下所有文件的缩略图一并删除
+        store.load_or_render(&source, "a/b.png", "k", 4).await.unwrap();
+        store.invalidate("a/").await;
+        assert!(!store.cache_dir("a").exists());
+        store.invalidate("a/..").await;
+        assert!(dir.path().join("thumbnails").exists());
+    }
+}
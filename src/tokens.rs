@@ -0,0 +1,226 @@
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use tokio::fs;
+
+use crate::{
+    acl::Permission,
+    auth::{AuthStore, AuthUser, TokenScope},
+    errors::AppError,
+    models::*,
+    utils::*,
+    AppState,
+};
+
+pub const TOKEN_PREFIX: &str = "fst_";
+
+// 持久化的令牌记录，只保存令牌的SHA-256哈希
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct TokenRecord {
+    id: String,
+    label: String,
+    token_hash: String,
+    user: String,
+    path: String,
+    permissions: Vec<Permission>,
+    created_by: String,
+    created: i64,
+    expires: Option<i64>,
+}
+
+impl TokenRecord {
+    fn info(&self) -> TokenInfo {
+        TokenInfo {
+            id: self.id.clone(),
+            label: self.label.clone(),
+            user: self.user.clone(),
+            path: self.path.clone(),
+            permissions: self.permissions.clone(),
+            created_by: self.created_by.clone(),
+            created: format_timestamp(self.created),
+            expires: self.expires.map(format_timestamp),
+        }
+    }
+}
+
+pub struct TokenStore {
+    file: PathBuf,
+    // token_hash -> 记录
+    tokens: Mutex<HashMap<String, TokenRecord>>,
+}
+
+fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+// 解析过期时间，支持 "YYYY-MM-DD" 和 "YYYY-MM-DD HH:MM:SS"（本地时间）
+fn parse_expires(value: &str) -> Result<i64, AppError> {
+    parse_datetime(value).ok_or_else(|| AppError::InvalidRequest(format!("Invalid expires_at: {}", value)))
+}
+
+impl TokenStore {
+    pub async fn new(file: PathBuf) -> anyhow::Result<Self> {
+        let tokens = match fs::read_to_string(&file).await {
+            Ok(content) => serde_json::from_str::<Vec<TokenRecord>>(&content)?
+                .into_iter()
+                .map(|record| (record.token_hash.clone(), record))
+                .collect(),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => return Err(e.into()),
+        };
+
+        Ok(TokenStore {
+            file,
+            tokens: Mutex::new(tokens),
+        })
+    }
+
+    async fn persist(&self) -> Result<(), AppError> {
+        let content = {
+            let tokens = self.tokens.lock().unwrap();
+            let mut records: Vec<&TokenRecord> = tokens.values().collect();
+            records.sort_by_key(|r| r.created);
+            serde_json::to_vec_pretty(&records).map_err(anyhow::Error::from)?
+        };
+
+        write_file_atomic(&self.file, &content).await?;
+        Ok(())
+    }
+
+    // 用Bearer令牌认证，返回令牌所代表用户（受令牌范围限制）
+    pub fn authenticate(&self, token: &str, auth: &AuthStore) -> Option<AuthUser> {
+        let record = self.tokens.lock().unwrap().get(&hash_token(token))?.clone();
+
+        if let Some(expires) = record.expires {
+            if expires <= Utc::now().timestamp() {
+                tracing::warn!("Rejected expired API token {}", record.label);
+                return None;
+            }
+        }
+
+        let mut user = auth.user(&record.user)?;
+        user.is_admin = false;
+        user.token = Some(TokenScope {
+            label: record.label,
+            path: record.path,
+            permissions: record.permissions,
+        });
+        Some(user)
+    }
+}
+
+pub(crate) fn require_admin(user: &AuthUser) -> Result<(), AppError> {
+    if !user.is_admin || user.token.is_some() {
+        return Err(AppError::PermissionDenied("Administrator required".to_string()));
+    }
+    Ok(())
+}
+
+// 创建API令牌，明文令牌只在创建时返回一次
+pub async fn create_token(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Json(req): Json<CreateTokenRequest>,
+) -> Result<Json<ApiResponse<CreatedToken>>, AppError> {
+    require_admin(&user)?;
+
+    if req.label.trim().is_empty() {
+        return Err(AppError::InvalidRequest("Token label is required".to_string()));
+    }
+    if req.permissions.is_empty() {
+        return Err(AppError::InvalidRequest("At least one permission is required".to_string()));
+    }
+
+    let token_user = req.user.unwrap_or_else(|| user.username.clone());
+    if state.auth.user(&token_user).is_none() {
+        return Err(AppError::InvalidRequest(format!("Unknown user: {}", token_user)));
+    }
+
+    let safe_path = sanitize_path(&req.path);
+    if !is_safe_path(&state.base_dir, &state.base_dir.join(&safe_path)) {
+        return Err(AppError::PermissionDenied("Access denied".to_string()));
+    }
+
+    let expires = req.expires_at.as_deref().map(parse_expires).transpose()?;
+    if let Some(expires) = expires {
+        if expires <= Utc::now().timestamp() {
+            return Err(AppError::InvalidRequest("expires_at is in the past".to_string()));
+        }
+    }
+
+    let token = format!(
+        "{}{}{}",
+        TOKEN_PREFIX,
+        uuid::Uuid::new_v4().simple(),
+        uuid::Uuid::new_v4().simple()
+    );
+    let record = TokenRecord {
+        id: uuid::Uuid::new_v4().simple().to_string(),
+        label: req.label.trim().to_string(),
+        token_hash: hash_token(&token),
+        user: token_user,
+        path: safe_path,
+        permissions: req.permissions,
+        created_by: user.username.clone(),
+        created: Utc::now().timestamp(),
+        expires,
+    };
+    let info = record.info();
+
+    state
+        .tokens
+        .tokens
+        .lock()
+        .unwrap()
+        .insert(record.token_hash.clone(), record);
+    state.tokens.persist().await?;
+
+    tracing::info!("{} created API token {} for /{}", user, info.label, info.path);
+    Ok(Json(ApiResponse::success(CreatedToken { token, info })))
+}
+
+// 列出所有API令牌（不含令牌明文和哈希）
+pub async fn list_tokens(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+) -> Result<Json<ApiResponse<Vec<TokenInfo>>>, AppError> {
+    require_admin(&user)?;
+
+    let mut records: Vec<TokenRecord> = state.tokens.tokens.lock().unwrap().values().cloned().collect();
+    records.sort_by_key(|r| r.created);
+
+    Ok(Json(ApiResponse::success(
+        records.iter().map(TokenRecord::info).collect(),
+    )))
+}
+
+// 吊销API令牌
+pub async fn revoke_token(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Path(id): Path<String>,
+) -> Result<Json<ApiResponse<()>>, AppError> {
+    require_admin(&user)?;
+
+    let removed = {
+        let mut tokens = state.tokens.tokens.lock().unwrap();
+        let hash = tokens
+            .iter()
+            .find(|(_, record)| record.id == id)
+            .map(|(hash, _)| hash.clone());
+        hash.and_then(|hash| tokens.remove(&hash))
+    };
+
+    let record = removed.ok_or_else(|| AppError::NotFound("Token not found".to_string()))?;
+    state.tokens.persist().await?;
+
+    tracing::info!("{} revoked API token {}", user, record.label);
+    Ok(Json(ApiResponse::success(())))
+}
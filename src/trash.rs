@@ -0,0 +1,491 @@
+use axum::{
+    extract::{Path, State},
+    Json,
+};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::fs;
+
+use crate::{
+    acl::Permission,
+    auth::AuthUser,
+    errors::AppError,
+    events,
+    models::*,
+    utils::*,
+    AppState,
+};
+
+// 回收站条目元数据，持久化为 .trash/{id}.json，被删除的文件或目录移动到 .trash/{id}
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct TrashRecord {
+    id: String,
+    name: String,
+    original_path: String,
+    deleted_at: i64,
+    deleted_by: String,
+    is_dir: bool,
+    size: u64,
+}
+
+impl TrashRecord {
+    fn info(&self) -> TrashEntry {
+        TrashEntry {
+            id: self.id.clone(),
+            name: self.name.clone(),
+            original_path: self.original_path.clone(),
+            deleted_at: format_timestamp(self.deleted_at),
+            deleted_by: self.deleted_by.clone(),
+            is_dir: self.is_dir,
+            size: self.size,
+        }
+    }
+}
+
+pub struct TrashStore {
+    dir: PathBuf,
+    enabled: bool,
+    // 保留时间，0表示永久保留
+    retention_secs: i64,
+}
+
+// 统计目录总大小（不跟随符号链接）
+fn dir_size(path: &std::path::Path) -> u64 {
+    let mut size = 0;
+    if let Ok(read_dir) = std::fs::read_dir(path) {
+        for entry in read_dir.flatten() {
+            match entry.metadata() {
+                Ok(metadata) if metadata.is_dir() => size += dir_size(&entry.path()),
+                Ok(metadata) => size += metadata.len(),
+                Err(_) => {}
+            }
+        }
+    }
+    size
+}
+
+impl TrashStore {
+    pub async fn new(base_dir: &std::path::Path, config: &serde_json::Value) -> anyhow::Result<Self> {
+        let enabled = config["enabled"].as_bool().unwrap_or(true);
+        let retention_days = config["retention_days"].as_u64().unwrap_or(30);
+
+        let dir = base_dir.join(TRASH_DIR);
+        if enabled {
+            fs::create_dir_all(&dir).await?;
+        }
+
+        Ok(TrashStore {
+            dir,
+            enabled,
+            retention_secs: (retention_days * 86400) as i64,
+        })
+    }
+
+    fn info_path(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", id))
+    }
+
+    fn data_path(&self, id: &str) -> PathBuf {
+        self.dir.join(id)
+    }
+
+    // 删除文件或目录：启用回收站时移入回收站，否则直接永久删除
+    pub async fn remove(&self, full_path: &std::path::Path, rel_path: &str, user: &AuthUser) -> Result<(), AppError> {
+        if rel_path.is_empty() {
+            return Err(AppError::InvalidPath("Cannot delete root directory".to_string()));
+        }
+
+        let metadata = fs::symlink_metadata(full_path).await?;
+        let is_dir = metadata.is_dir();
+
+        if !self.enabled {
+            if is_dir {
+                fs::remove_dir_all(full_path).await?;
+            } else {
+                fs::remove_file(full_path).await?;
+            }
+            return Ok(());
+        }
+
+        let size = if is_dir {
+            let path = full_path.to_path_buf();
+            tokio::task::spawn_blocking(move || dir_size(&path))
+                .await
+                .unwrap_or(0)
+        } else {
+            metadata.len()
+        };
+
+        let record = TrashRecord {
+            id: uuid::Uuid::new_v4().simple().to_string(),
+            name: full_path
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default(),
+            original_path: rel_path.to_string(),
+            deleted_at: Utc::now().timestamp(),
+            deleted_by: user.username.clone(),
+            is_dir,
+            size,
+        };
+
+        let data_path = self.data_path(&record.id);
+        if is_dir {
+            move_dir(full_path, &data_path).await?;
+        } else {
+            move_file(full_path, &data_path).await?;
+        }
+
+        let content = serde_json::to_vec(&record).map_err(anyhow::Error::from)?;
+        if let Err(e) = write_file_atomic(&self.info_path(&record.id), &content).await {
+            // 元数据写入失败时放回原处，避免条目在回收站中丢失
+            let _ = if is_dir {
+                move_dir(&data_path, full_path).await
+            } else {
+                move_file(&data_path, full_path).await
+            };
+            return Err(e.into());
+        }
+
+        Ok(())
+    }
+
+    async fn load(&self, id: &str) -> Result<TrashRecord, AppError> {
+        // 条目ID只能是uuid的hex形式，避免拼接出回收站之外的路径
+        if id.len() != 32 || !id.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(AppError::NotFound("Trash entry not found".to_string()));
+        }
+
+        let content = match fs::read_to_string(self.info_path(id)).await {
+            Ok(content) => content,
+            Err(_) => return Err(AppError::NotFound("Trash entry not found".to_string())),
+        };
+        serde_json::from_str(&content)
+            .map_err(|e| AppError::IoError(format!("Corrupted trash entry: {}", e)))
+    }
+
+    async fn records(&self) -> Vec<TrashRecord> {
+        let mut records = Vec::new();
+        let mut read_dir = match fs::read_dir(&self.dir).await {
+            Ok(read_dir) => read_dir,
+            Err(_) => return records,
+        };
+
+        while let Ok(Some(entry)) = read_dir.next_entry().await {
+            let path = entry.path();
+            if path.extension().and_then(|e| e.to_str()) != Some("json") {
+                continue;
+            }
+            if let Ok(content) = fs::read_to_string(&path).await {
+                if let Ok(record) = serde_json::from_str::<TrashRecord>(&content) {
+                    records.push(record);
+                }
+            }
+        }
+
+        records
+    }
+
+    // 永久删除条目数据和元数据
+    async fn purge(&self, record: &TrashRecord) -> std::io::Result<()> {
+        let data_path = self.data_path(&record.id);
+        let result = match fs::symlink_metadata(&data_path).await {
+            Ok(metadata) if metadata.is_dir() => fs::remove_dir_all(&data_path).await,
+            Ok(_) => fs::remove_file(&data_path).await,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e),
+        };
+        result?;
+        fs::remove_file(self.info_path(&record.id)).await
+    }
+
+    // 永久删除超过保留时间的条目，返回删除数量
+    pub async fn sweep_expired(&self) -> usize {
+        if !self.enabled || self.retention_secs == 0 {
+            return 0;
+        }
+
+        let cutoff = Utc::now().timestamp() - self.retention_secs;
+        let mut removed = 0;
+        for record in self.records().await {
+            if record.deleted_at > cutoff {
+                continue;
+            }
+            match self.purge(&record).await {
+                Ok(_) => removed += 1,
+                Err(e) => tracing::error!("Failed to purge trash entry {}: {}", record.id, e),
+            }
+        }
+        removed
+    }
+}
+
+// 后台定期清理过期的回收站条目
+pub fn spawn_sweep_task(state: Arc<AppState>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(3600));
+        loop {
+            interval.tick().await;
+            let removed = state.trash.sweep_expired().await;
+            if removed > 0 {
+                tracing::info!("Purged {} expired trash entries", removed);
+            }
+        }
+    });
+}
+
+fn require_enabled(state: &AppState) -> Result<(), AppError> {
+    if !state.trash.enabled {
+        return Err(AppError::NotFound("Trash is disabled".to_string()));
+    }
+    Ok(())
+}
+
+// 列出回收站条目（只包含对原路径有读权限的条目），最近删除的在前
+pub async fn list_trash(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+) -> Result<Json<ApiResponse<Vec<TrashEntry>>>, AppError> {
+    require_enabled(&state)?;
+
+    let mut records: Vec<TrashRecord> = state
+        .trash
+        .records()
+        .await
+        .into_iter()
+        .filter(|r| state.acl.allows(&user, &r.original_path, Permission::Read))
+        .collect();
+    records.sort_by_key(|r| std::cmp::Reverse(r.deleted_at));
+
+    Ok(Json(ApiResponse::success(
+        records.iter().map(TrashRecord::info).collect(),
+    )))
+}
+
+// 把条目恢复到原路径，按冲突策略处理原路径上已存在的文件
+pub async fn restore_trash(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Json(req): Json<RestoreRequest>,
+) -> Result<Json<ApiResponse<serde_json::Value>>, AppError> {
+    require_enabled(&state)?;
+
+    let mut restored = Vec::new();
+    let mut skipped = Vec::new();
+    let mut failed = Vec::new();
+
+    for id in req.ids {
+        let record = match state.trash.load(&id).await {
+            Ok(record) => record,
+            Err(e) => {
+                failed.push((id, e.to_string()));
+                continue;
+            }
+        };
+
+        match restore_record(&state, &user, &record, req.on_conflict).await {
+            Ok(Some(path)) => restored.push(path),
+            Ok(None) => skipped.push(record.original_path),
+            Err(e) => failed.push((id, e.to_string())),
+        }
+    }
+
+    tracing::info!("{} restored {} items from trash", user, restored.len());
+    Ok(Json(ApiResponse::success(serde_json::json!({
+        "restored": restored,
+        "skipped": skipped,
+        "failed": failed
+    }))))
+}
+
+// 恢复单个条目，返回恢复后的相对路径；按策略跳过时返回None
+async fn restore_record(
+    state: &AppState,
+    user: &AuthUser,
+    record: &TrashRecord,
+    policy: ConflictPolicy,
+) -> Result<Option<String>, AppError> {
+    let mut target = state.base_dir.join(&record.original_path);
+    if !is_safe_path(&state.base_dir, &target) {
+        return Err(AppError::PermissionDenied("Access denied".to_string()));
+    }
+
+    // 恢复目录等同于写入整个目录树，子路径上更严格的规则同样生效
+    if record.is_dir {
+        state.acl.check_tree(user, &record.original_path, Permission::Write)?;
+    } else {
+        state.acl.check(user, &record.original_path, Permission::Write)?;
+    }
+
+    let mut overwritten = false;
+    if fs::symlink_metadata(&target).await.is_ok() {
+        match policy {
+            ConflictPolicy::Skip => return Ok(None),
+            // 被覆盖的现有条目同样移入回收站
+            ConflictPolicy::Overwrite => {
+                state.acl.check_tree(user, &record.original_path, Permission::Delete)?;
+                state.trash.remove(&target, &record.original_path, user).await?;
+                overwritten = true;
+            }
+            ConflictPolicy::Rename => target = unique_path(&target),
+        }
+    }
+
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent).await?;
+    }
+
+    let data_path = state.trash.data_path(&record.id);
+    if record.is_dir {
+        move_dir(&data_path, &target).await?;
+    } else {
+        move_file(&data_path, &target).await?;
+    }
+    let _ = fs::remove_file(state.trash.info_path(&record.id)).await;
+
+    let restored = target
+        .strip_prefix(&state.base_dir)
+        .unwrap_or(&target)
+        .to_string_lossy()
+        .replace('\\', "/");
+    events::file_changed(state, ChangeKind::written(overwritten), &restored, &user.username).await;
+    Ok(Some(restored))
+}
+
+// 永久删除单个回收站条目
+pub async fn purge_trash_entry(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Path(id): Path<String>,
+) -> Result<Json<ApiResponse<()>>, AppError> {
+    require_enabled(&state)?;
+
+    let record = state.trash.load(&id).await?;
+    state.acl.check_tree(&user, &record.original_path, Permission::Delete)?;
+    state.trash.purge(&record).await?;
+
+    tracing::info!("{} purged {} from trash", user, record.original_path);
+    Ok(Json(ApiResponse::success(())))
+}
+
+// 清空回收站（只删除对原路径有删除权限的条目）
+pub async fn empty_trash(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+) -> Result<Json<ApiResponse<serde_json::Value>>, AppError> {
+    require_enabled(&state)?;
+
+    let mut purged = Vec::new();
+    let mut failed = Vec::new();
+
+    for record in state.trash.records().await {
+        if !state.acl.allows(&user, &record.original_path, Permission::Delete) {
+            continue;
+        }
+        match state.trash.purge(&record).await {
+            Ok(_) => purged.push(record.id),
+            Err(e) => failed.push((record.id, e.to_string())),
+        }
+    }
+
+    tracing::info!("{} emptied trash, purged {} entries", user, purged.len());
+    Ok(Json(ApiResponse::success(serde_json::json!({
+        "purged": purged,
+        "failed": failed
+    }))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::test_state;
+
+    async fn delete(state: &AppState, rel_path: &str) -> String {
+        let user = AuthUser::anonymous();
+        state.trash.remove(&state.base_dir.join(rel_path), rel_path, &user).await.unwrap();
+        let records = state.trash.records().await;
+        records.iter().find(|r| r.original_path == rel_path).unwrap().id.clone()
+    }
+
+    async fn restore(state: &Arc<AppState>, id: &str, policy: ConflictPolicy) -> serde_json::Value {
+        let req = RestoreRequest {
+            ids: vec![id.to_string()],
+            on_conflict: policy,
+        };
+        let Json(response) = restore_trash(State(state.clone()), AuthUser::anonymous(), Json(req))
+            .await
+            .unwrap();
+        response.data.unwrap()
+    }
+
+    #[tokio::test]
+    async fn deleted_entries_restore_to_original_path() {
+        let (state, _dir) = test_state(serde_json::json!({ "acl": { "default": ["read", "write", "delete"] } })).await;
+        std::fs::create_dir_all(state.base_dir.join("docs/sub")).unwrap();
+        std::fs::write(state.base_dir.join("docs/sub/a.txt"), "a").unwrap();
+
+        let id = delete(&state, "docs").await;
+        assert!(!state.base_dir.join("docs").exists());
+
+        let result = restore(&state, &id, ConflictPolicy::Skip).await;
+        assert_eq!(result["restored"], serde_json::json!(["docs"]));
+        assert_eq!(std::fs::read_to_string(state.base_dir.join("docs/sub/a.txt")).unwrap(), "a");
+        assert!(state.trash.records().await.is_empty());
+    }
+
+    #[tokio::test]
+    async fn restore_follows_conflict_policy() {
+        let (state, _dir) = test_state(serde_json::json!({ "acl": { "default": ["read", "write", "delete"] } })).await;
+        let path = state.base_dir.join("a.txt");
+
+        std::fs::write(&path, "old").unwrap();
+        let id = delete(&state, "a.txt").await;
+        std::fs::write(&path, "new").unwrap();
+
+        // 跳过时条目留在回收站
+        let result = restore(&state, &id, ConflictPolicy::Skip).await;
+        assert_eq!(result["skipped"], serde_json::json!(["a.txt"]));
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "new");
+
+        let result = restore(&state, &id, ConflictPolicy::Rename).await;
+        assert_eq!(result["restored"], serde_json::json!(["a (1).txt"]));
+        assert_eq!(std::fs::read_to_string(state.base_dir.join("a (1).txt")).unwrap(), "old");
+
+        // 覆盖时原有文件移入回收站
+        let id = delete(&state, "a (1).txt").await;
+        std::fs::rename(state.base_dir.join("a.txt"), state.base_dir.join("a (1).txt")).unwrap();
+        let result = restore(&state, &id, ConflictPolicy::Overwrite).await;
+        assert_eq!(result["restored"], serde_json::json!(["a (1).txt"]));
+        assert_eq!(std::fs::read_to_string(state.base_dir.join("a (1).txt")).unwrap(), "old");
+        let records = state.trash.records().await;
+        assert_eq!(records.len(), 1);
+        assert_eq!(std::fs::read_to_string(state.trash.data_path(&records[0].id)).unwrap(), "new");
+    }
+
+    #[tokio::test]
+    async fn directory_restore_needs_write_on_whole_tree() {
+        let config = serde_json::json!({
+            "acl": {
+                "default": ["read", "write", "delete"],
+                "rules": [{ "path": "docs/locked", "permissions": ["read"] }]
+            }
+        });
+        let (state, _dir) = test_state(config).await;
+        std::fs::create_dir_all(state.base_dir.join("docs/locked")).unwrap();
+
+        state
+            .trash
+            .remove(&state.base_dir.join("docs"), "docs", &AuthUser::anonymous())
+            .await
+            .unwrap();
+        let id = state.trash.records().await[0].id.clone();
+
+        let result = restore(&state, &id, ConflictPolicy::Skip).await;
+        assert_eq!(result["restored"], serde_json::json!([]));
+        assert_eq!(result["failed"].as_array().unwrap().len(), 1);
+        assert!(!state.base_dir.join("docs").exists());
+    }
+}
@@ -0,0 +1,619 @@
+use axum::{
+    body::Body,
+    extract::{Path, State},
+    http::{header, HeaderMap, HeaderName, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+};
+use base64::Engine;
+use chrono::{TimeZone, Utc};
+use futures_util::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::fs;
+use tokio::io::AsyncWriteExt;
+
+use crate::{acl::Permission, auth::AuthUser, errors::AppError, events, models::ChangeKind, utils::*, AppState};
+
+const TUS_VERSION: &str = "1.0.0";
+const TUS_EXTENSIONS: &str = "creation,termination,expiration";
+
+const TUS_RESUMABLE: HeaderName = HeaderName::from_static("tus-resumable");
+const TUS_VERSION_HEADER: HeaderName = HeaderName::from_static("tus-version");
+const TUS_EXTENSION: HeaderName = HeaderName::from_static("tus-extension");
+const TUS_MAX_SIZE: HeaderName = HeaderName::from_static("tus-max-size");
+const UPLOAD_OFFSET: HeaderName = HeaderName::from_static("upload-offset");
+const UPLOAD_LENGTH: HeaderName = HeaderName::from_static("upload-length");
+const UPLOAD_METADATA: HeaderName = HeaderName::from_static("upload-metadata");
+const UPLOAD_EXPIRES: HeaderName = HeaderName::from_static("upload-expires");
+
+// 上传会话元数据，持久化为 {id}.json，数据写入 {id}.bin
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct UploadInfo {
+    id: String,
+    length: u64,
+    file_name: String,
+    path: String,
+    #[serde(default)]
+    owner: String,
+    created: i64,
+    expires: i64,
+}
+
+pub struct TusStore {
+    dir: PathBuf,
+    expire_secs: i64,
+    max_size: u64,
+    // 正在处理PATCH的会话，防止同一会话被并发写入
+    active: Mutex<HashSet<String>>,
+}
+
+// 会话占用守卫，drop时释放
+struct ActiveGuard<'a> {
+    store: &'a TusStore,
+    id: String,
+}
+
+impl Drop for ActiveGuard<'_> {
+    fn drop(&mut self) {
+        self.store.active.lock().unwrap().remove(&self.id);
+    }
+}
+
+impl TusStore {
+    pub async fn new(dir: PathBuf, config: &serde_json::Value) -> anyhow::Result<Self> {
+        fs::create_dir_all(&dir).await?;
+
+        let expire_hours = config["expire_hours"].as_u64().unwrap_or(24);
+        let max_size = config["max_size"].as_u64().unwrap_or(1024 * 1024 * 1024);
+
+        Ok(TusStore {
+            dir,
+            expire_secs: (expire_hours * 3600) as i64,
+            max_size,
+            active: Mutex::new(HashSet::new()),
+        })
+    }
+
+    fn info_path(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{}.json", id))
+    }
+
+    fn data_path(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{}.bin", id))
+    }
+
+    // 加载会话并确认属于当前用户
+    async fn load_owned(&self, id: &str, user: &AuthUser) -> Result<UploadInfo, AppError> {
+        let info = self.load(id).await?;
+        if info.owner != user.username {
+            return Err(AppError::NotFound("Upload not found".to_string()));
+        }
+        Ok(info)
+    }
+
+    async fn load(&self, id: &str) -> Result<UploadInfo, AppError> {
+        // 会话ID只能是uuid的hex形式，避免拼接出数据目录之外的路径
+        if id.len() != 32 || !id.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(AppError::NotFound("Upload not found".to_string()));
+        }
+
+        let content = match fs::read_to_string(self.info_path(id)).await {
+            Ok(content) => content,
+            Err(_) => return Err(AppError::NotFound("Upload not found".to_string())),
+        };
+        let info: UploadInfo = serde_json::from_str(&content)
+            .map_err(|e| AppError::IoError(format!("Corrupted upload info: {}", e)))?;
+
+        if info.expires <= Utc::now().timestamp() {
+            self.remove(id).await;
+            return Err(AppError::NotFound("Upload expired".to_string()));
+        }
+
+        Ok(info)
+    }
+
+    async fn save(&self, info: &UploadInfo) -> Result<(), AppError> {
+        let content = serde_json::to_vec(info).map_err(anyhow::Error::from)?;
+        write_file_atomic(&self.info_path(&info.id), &content).await?;
+        Ok(())
+    }
+
+    async fn remove(&self, id: &str) {
+        let _ = fs::remove_file(self.data_path(id)).await;
+        let _ = fs::remove_file(self.info_path(id)).await;
+    }
+
+    async fn current_offset(&self, id: &str) -> Result<u64, AppError> {
+        match fs::metadata(self.data_path(id)).await {
+            Ok(metadata) => Ok(metadata.len()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(0),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn acquire(&self, id: &str) -> Result<ActiveGuard<'_>, AppError> {
+        let mut active = self.active.lock().unwrap();
+        if !active.insert(id.to_string()) {
+            return Err(AppError::Conflict("Upload is locked by another request".to_string()));
+        }
+        Ok(ActiveGuard {
+            store: self,
+            id: id.to_string(),
+        })
+    }
+
+    // 删除所有过期会话，返回删除数量
+    pub async fn cleanup_expired(&self) -> usize {
+        let mut removed = 0;
+        let now = Utc::now().timestamp();
+        let mut read_dir = match fs::read_dir(&self.dir).await {
+            Ok(read_dir) => read_dir,
+            Err(_) => return 0,
+        };
+
+        while let Ok(Some(entry)) = read_dir.next_entry().await {
+            let path = entry.path();
+            if path.extension().and_then(|e| e.to_str()) != Some("json") {
+                continue;
+            }
+            let id = match path.file_stem().and_then(|s| s.to_str()) {
+                Some(id) => id.to_string(),
+                None => continue,
+            };
+            if self.active.lock().unwrap().contains(&id) {
+                continue;
+            }
+
+            let expired = match fs::read_to_string(&path).await {
+                Ok(content) => serde_json::from_str::<UploadInfo>(&content)
+                    .map(|info| info.expires <= now)
+                    .unwrap_or(true),
+                Err(_) => false,
+            };
+            if expired {
+                self.remove(&id).await;
+                removed += 1;
+            }
+        }
+
+        removed
+    }
+}
+
+// 后台定期清理过期会话
+pub fn spawn_cleanup_task(state: Arc<AppState>) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(600));
+        loop {
+            interval.tick().await;
+            let removed = state.tus.cleanup_expired().await;
+            if removed > 0 {
+                tracing::info!("Removed {} expired tus uploads", removed);
+            }
+        }
+    });
+}
+
+fn check_tus_version(headers: &HeaderMap) -> Result<(), AppError> {
+    match headers.get(&TUS_RESUMABLE).and_then(|v| v.to_str().ok()) {
+        Some(TUS_VERSION) => Ok(()),
+        _ => Err(AppError::PreconditionFailed("Unsupported Tus-Resumable version".to_string())),
+    }
+}
+
+// 解析Upload-Metadata：逗号分隔的 "key base64(value)" 对
+fn parse_metadata(value: &str) -> Result<HashMap<String, String>, AppError> {
+    let mut metadata = HashMap::new();
+    for pair in value.split(',') {
+        let pair = pair.trim();
+        if pair.is_empty() {
+            continue;
+        }
+        let mut parts = pair.splitn(2, ' ');
+        let key = parts.next().unwrap_or_default().to_string();
+        let decoded = match parts.next() {
+            Some(encoded) => base64::engine::general_purpose::STANDARD
+                .decode(encoded.trim())
+                .ok()
+                .and_then(|bytes| String::from_utf8(bytes).ok())
+                .ok_or_else(|| AppError::InvalidRequest(format!("Invalid metadata value for {}", key)))?,
+            None => String::new(),
+        };
+        metadata.insert(key, decoded);
+    }
+    Ok(metadata)
+}
+
+fn http_date(timestamp: i64) -> String {
+    Utc.timestamp_opt(timestamp, 0)
+        .single()
+        .unwrap_or_else(Utc::now)
+        .format("%a, %d %b %Y %H:%M:%S GMT")
+        .to_string()
+}
+
+fn header_value(value: impl ToString) -> HeaderValue {
+    HeaderValue::from_str(&value.to_string()).unwrap_or_else(|_| HeaderValue::from_static(""))
+}
+
+// 服务能力查询
+pub async fn options(State(state): State<Arc<AppState>>) -> Response {
+    (
+        StatusCode::NO_CONTENT,
+        [
+            (TUS_RESUMABLE, header_value(TUS_VERSION)),
+            (TUS_VERSION_HEADER, header_value(TUS_VERSION)),
+            (TUS_EXTENSION, header_value(TUS_EXTENSIONS)),
+            (TUS_MAX_SIZE, header_value(state.tus.max_size)),
+        ],
+    )
+        .into_response()
+}
+
+// 创建上传会话
+pub async fn create_upload(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    check_tus_version(&headers)?;
+
+    let length = headers
+        .get(&UPLOAD_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<u64>().ok())
+        .ok_or_else(|| AppError::InvalidRequest("Missing or invalid Upload-Length".to_string()))?;
+
+    if length > state.tus.max_size {
+        return Err(AppError::PayloadTooLarge("Upload exceeds Tus-Max-Size".to_string()));
+    }
+
+    let metadata = match headers.get(&UPLOAD_METADATA).and_then(|v| v.to_str().ok()) {
+        Some(value) => parse_metadata(value)?,
+        None => HashMap::new(),
+    };
+
+    let file_name = metadata
+        .get("filename")
+        .cloned()
+        .ok_or_else(|| AppError::InvalidRequest("Missing filename in Upload-Metadata".to_string()))?;
+    if !is_valid_file_name(&file_name) {
+        return Err(AppError::InvalidPath("Invalid file name".to_string()));
+    }
+
+    let safe_path = sanitize_path(metadata.get("path").map(String::as_str).unwrap_or(""));
+    let target_dir = state.base_dir.join(&safe_path);
+    if !is_safe_path(&state.base_dir, &target_dir.join(&file_name)) {
+        return Err(AppError::PermissionDenied("Access denied".to_string()));
+    }
+
+    state
+        .acl
+        .check(&user, &join_relative_path(&safe_path, &file_name), Permission::Write)?;
+
+    let now = Utc::now().timestamp();
+    let info = UploadInfo {
+        id: uuid::Uuid::new_v4().simple().to_string(),
+        length,
+        file_name,
+        path: safe_path,
+        owner: user.username.clone(),
+        created: now,
+        expires: now + state.tus.expire_secs,
+    };
+
+    fs::File::create(state.tus.data_path(&info.id)).await?;
+    state.tus.save(&info).await?;
+
+    // 零长度文件无需PATCH，直接完成
+    if length == 0 {
+        finalize_upload(&state, &info).await?;
+    }
+
+    Ok((
+        StatusCode::CREATED,
+        [
+            (header::LOCATION, header_value(format!("/api/tus/{}", info.id))),
+            (TUS_RESUMABLE, header_value(TUS_VERSION)),
+            (UPLOAD_EXPIRES, header_value(http_date(info.expires))),
+        ],
+    )
+        .into_response())
+}
+
+// 查询当前偏移量
+pub async fn upload_offset(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    check_tus_version(&headers)?;
+
+    let info = state.tus.load_owned(&id, &user).await?;
+    let offset = state.tus.current_offset(&id).await?;
+
+    Ok((
+        StatusCode::OK,
+        [
+            (TUS_RESUMABLE, header_value(TUS_VERSION)),
+            (UPLOAD_OFFSET, header_value(offset)),
+            (UPLOAD_LENGTH, header_value(info.length)),
+            (UPLOAD_EXPIRES, header_value(http_date(info.expires))),
+            (header::CACHE_CONTROL, header_value("no-store")),
+        ],
+    )
+        .into_response())
+}
+
+// 在指定偏移量追加数据
+pub async fn upload_chunk(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+    body: Body,
+) -> Result<Response, AppError> {
+    check_tus_version(&headers)?;
+
+    let content_type = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or("");
+    if content_type != "application/offset+octet-stream" {
+        return Err(AppError::InvalidRequest(
+            "Content-Type must be application/offset+octet-stream".to_string(),
+        ));
+    }
+
+    let client_offset = headers
+        .get(&UPLOAD_OFFSET)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<u64>().ok())
+        .ok_or_else(|| AppError::InvalidRequest("Missing or invalid Upload-Offset".to_string()))?;
+
+    let info = state.tus.load_owned(&id, &user).await?;
+    let _guard = state.tus.acquire(&id)?;
+
+    let mut offset = state.tus.current_offset(&id).await?;
+    if offset != client_offset {
+        return Err(AppError::Conflict(format!(
+            "Upload-Offset mismatch, current offset is {}",
+            offset
+        )));
+    }
+
+    let mut file = fs::OpenOptions::new()
+        .append(true)
+        .open(state.tus.data_path(&id))
+        .await?;
+
+    // 已写入的数据即使中途出错也保留，客户端可通过HEAD查询后续传
+    let mut stream = body.into_data_stream();
+    let mut result = Ok(());
+    while let Some(chunk) = stream.next().await {
+        let chunk = match chunk {
+            Ok(chunk) => chunk,
+            Err(e) => {
+                result = Err(AppError::InvalidRequest(format!("Body error: {}", e)));
+                break;
+            }
+        };
+        if offset + chunk.len() as u64 > info.length {
+            result = Err(AppError::PayloadTooLarge("Chunk exceeds Upload-Length".to_string()));
+            break;
+        }
+        file.write_all(&chunk).await?;
+        offset += chunk.len() as u64;
+    }
+    file.flush().await?;
+    file.sync_all().await?;
+    drop(file);
+    result?;
+
+    if offset == info.length {
+        finalize_upload(&state, &info).await?;
+    }
+
+    Ok((
+        StatusCode::NO_CONTENT,
+        [
+            (TUS_RESUMABLE, header_value(TUS_VERSION)),
+            (UPLOAD_OFFSET, header_value(offset)),
+            (UPLOAD_EXPIRES, header_value(http_date(info.expires))),
+        ],
+    )
+        .into_response())
+}
+
+// 终止上传并删除已上传数据
+pub async fn terminate_upload(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    check_tus_version(&headers)?;
+
+    state.tus.load_owned(&id, &user).await?;
+    let _guard = state.tus.acquire(&id)?;
+    state.tus.remove(&id).await;
+
+    Ok((
+        StatusCode::NO_CONTENT,
+        [(TUS_RESUMABLE, header_value(TUS_VERSION))],
+    )
+        .into_response())
+}
+
+// 上传完成：重新校验目标路径后移动到目标目录
+async fn finalize_upload(state: &AppState, info: &UploadInfo) -> Result<(), AppError> {
+    let target_dir = state.base_dir.join(&info.path);
+    let target = target_dir.join(&info.file_name);
+
+    if !is_safe_path(&state.base_dir, &target) {
+        return Err(AppError::PermissionDenied("Access denied".to_string()));
+    }
+
+    let rel_path = join_relative_path(&info.path, &info.file_name);
+    fs::create_dir_all(&target_dir).await?;
+    let existed = target.exists();
+    state.versions.preserve(&target, &rel_path, &info.owner).await?;
+    move_file(&state.tus.data_path(&info.id), &target).await?;
+    let _ = fs::remove_file(state.tus.info_path(&info.id)).await;
+    events::file_changed(state, ChangeKind::written(existed), &rel_path, &info.owner).await;
+
+    tracing::info!("{} completed tus upload {}: {}", info.owner, info.id, target.display());
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::test_state;
+
+    fn user(name: &str) -> AuthUser {
+        AuthUser {
+            username: name.to_string(),
+            groups: Vec::new(),
+            is_admin: false,
+            token: None,
+        }
+    }
+
+    fn tus_headers() -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(TUS_RESUMABLE, header_value(TUS_VERSION));
+        headers
+    }
+
+    async fn create(state: &Arc<AppState>, length: u64, file_name: &str) -> String {
+        let mut headers = tus_headers();
+        headers.insert(UPLOAD_LENGTH, header_value(length));
+        let encoded = base64::engine::general_purpose::STANDARD.encode(file_name);
+        headers.insert(UPLOAD_METADATA, header_value(format!("filename {}", encoded)));
+
+        let response = create_upload(State(state.clone()), user("alice"), headers).await.unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let location = response.headers()[header::LOCATION].to_str().unwrap();
+        location.rsplit('/').next().unwrap().to_string()
+    }
+
+    async fn patch_as(
+        state: &Arc<AppState>,
+        user: AuthUser,
+        id: &str,
+        offset: u64,
+        data: &[u8],
+    ) -> Result<Response, AppError> {
+        let mut headers = tus_headers();
+        headers.insert(header::CONTENT_TYPE, header_value("application/offset+octet-stream"));
+        headers.insert(UPLOAD_OFFSET, header_value(offset));
+        upload_chunk(State(state.clone()), user, Path(id.to_string()), headers, Body::from(data.to_vec())).await
+    }
+
+    async fn patch(state: &Arc<AppState>, id: &str, offset: u64, data: &[u8]) -> Result<Response, AppError> {
+        patch_as(state, user("alice"), id, offset, data).await
+    }
+
+    #[tokio::test]
+    async fn chunks_resume_and_finalize_into_place() {
+        let (state, _dir) = test_state(serde_json::Value::Null).await;
+        let id = create(&state, 10, "a.txt").await;
+
+        let response = patch(&state, &id, 0, b"hello").await.unwrap();
+        assert_eq!(response.headers()[UPLOAD_OFFSET], "5");
+        let response = patch(&state, &id, 5, b"world").await.unwrap();
+        assert_eq!(response.headers()[UPLOAD_OFFSET], "10");
+
+        // 完成后移动到目标位置，会话数据一并删除
+        assert_eq!(std::fs::read(state.base_dir.join("a.txt")).unwrap(), b"helloworld");
+        assert!(!state.tus.data_path(&id).exists());
+        assert!(!state.tus.info_path(&id).exists());
+    }
+
+    #[tokio::test]
+    async fn offset_mismatch_is_a_conflict() {
+        let (state, _dir) = test_state(serde_json::Value::Null).await;
+        let id = create(&state, 10, "a.txt").await;
+        patch(&state, &id, 0, b"hello").await.unwrap();
+
+        // 客户端以为还在0，重复发送的数据不能追加
+        let err = patch(&state, &id, 0, b"hello").await.unwrap_err();
+        assert!(matches!(err, AppError::Conflict(_)), "{:?}", err);
+        let err = patch(&state, &id, 7, b"abc").await.unwrap_err();
+        assert!(matches!(err, AppError::Conflict(_)), "{:?}", err);
+        assert_eq!(state.tus.current_offset(&id).await.unwrap(), 5);
+    }
+
+    #[tokio::test]
+    async fn concurrent_patch_is_rejected() {
+        let (state, _dir) = test_state(serde_json::Value::Null).await;
+        let id = create(&state, 10, "a.txt").await;
+
+        let guard = state.tus.acquire(&id).unwrap();
+        let err = patch(&state, &id, 0, b"hello").await.unwrap_err();
+        assert!(matches!(err, AppError::Conflict(_)), "{:?}", err);
+
+        // 守卫释放后可以继续写入
+        drop(guard);
+        patch(&state, &id, 0, b"hello").await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn uploads_belong_to_their_creator() {
+        let (state, _dir) = test_state(serde_json::Value::Null).await;
+        let id = create(&state, 10, "a.txt").await;
+
+        // 其他用户看不到会话，既不能续传也不能终止
+        let err = patch_as(&state, user("bob"), &id, 0, b"hello").await.unwrap_err();
+        assert!(matches!(err, AppError::NotFound(_)), "{:?}", err);
+        let err = terminate_upload(State(state.clone()), user("bob"), Path(id.clone()), tus_headers())
+            .await
+            .unwrap_err();
+        assert!(matches!(err, AppError::NotFound(_)), "{:?}", err);
+        assert_eq!(state.tus.current_offset(&id).await.unwrap(), 0);
+
+        terminate_upload(State(state.clone()), user("alice"), Path(id.clone()), tus_headers())
+            .await
+            .unwrap();
+        assert!(!state.tus.info_path(&id).exists());
+    }
+
+    #[tokio::test]
+    async fn chunk_beyond_length_is_rejected() {
+        let (state, _dir) = test_state(serde_json::Value::Null).await;
+        let id = create(&state, 4, "a.txt").await;
+
+        let err = patch(&state, &id, 0, b"hello").await.unwrap_err();
+        assert!(matches!(err, AppError::PayloadTooLarge(_)), "{:?}", err);
+        assert!(!state.base_dir.join("a.txt").exists());
+    }
+
+    #[tokio::test]
+    async fn expired_uploads_are_cleaned_up() {
+        let (state, _dir) = test_state(serde_json::Value::Null).await;
+        let expired = create(&state, 10, "old.txt").await;
+        let active = create(&state, 10, "new.txt").await;
+        let locked = create(&state, 10, "locked.txt").await;
+
+        for id in [&expired, &locked] {
+            let mut info = state.tus.load(id).await.unwrap();
+            info.expires = Utc::now().timestamp() - 1;
+            state.tus.save(&info).await.unwrap();
+        }
+
+        // 正在写入的会话即使过期也不清理
+        let guard = state.tus.acquire(&locked).unwrap();
+        assert_eq!(state.tus.cleanup_expired().await, 1);
+        drop(guard);
+
+        assert!(!state.tus.info_path(&expired).exists());
+        assert!(!state.tus.data_path(&expired).exists());
+        assert!(state.tus.load(&active).await.is_ok());
+        assert!(matches!(state.tus.load(&locked).await, Err(AppError::NotFound(_))));
+    }
+}
@@ -0,0 +1,548 @@
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
+use std::collections::{HashMap, VecDeque};
+use std::ops::{AddAssign, ControlFlow, SubAssign};
+use std::path::{Path as FsPath, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+use tokio::fs;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+
+use crate::{
+    acl::Permission,
+    auth::AuthUser,
+    errors::AppError,
+    models::*,
+    search::{resolve_search_root, TreeWalker},
+    utils::*,
+    AppState,
+};
+
+const DEFAULT_TREE_DEPTH: usize = 2;
+const MAX_TREE_DEPTH: usize = 32;
+// 目录树最多返回的节点数
+const MAX_TREE_NODES: usize = 10000;
+
+// 保留的最近清除记录数，判断正在进行的统计是否受到期间变化的影响
+const MAX_INVALIDATIONS: usize = 1024;
+
+// 目录（递归）的总大小、文件数和子目录数
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Usage {
+    pub size: u64,
+    pub files: u64,
+    pub dirs: u64,
+}
+
+impl AddAssign for Usage {
+    fn add_assign(&mut self, other: Usage) {
+        self.size += other.size;
+        self.files += other.files;
+        self.dirs += other.dirs;
+    }
+}
+
+impl SubAssign for Usage {
+    fn sub_assign(&mut self, other: Usage) {
+        self.size = self.size.saturating_sub(other.size);
+        self.files = self.files.saturating_sub(other.files);
+        self.dirs = self.dirs.saturating_sub(other.dirs);
+    }
+}
+
+struct CachedUsage {
+    usage: Usage,
+    at: Instant,
+}
+
+#[derive(Default)]
+struct CacheState {
+    entries: HashMap<String, CachedUsage>,
+    // 每次清除时加一
+    generation: u64,
+    // 最近清除的路径及对应的generation
+    invalidations: VecDeque<(u64, String)>,
+}
+
+impl CacheState {
+    // 统计开始（generation为start）之后是否清除过与rel_path相关的路径
+    fn changed_since(&self, start: u64, rel_path: &str) -> bool {
+        if self.generation == start {
+            return false;
+        }
+        // 记录已被淘汰，无法确定
+        if self.invalidations.front().is_none_or(|(generation, _)| *generation > start + 1) {
+            return true;
+        }
+        self.invalidations
+            .iter()
+            .any(|(generation, path)| *generation > start && is_related(path, rel_path))
+    }
+}
+
+// 一个路径是另一个的上级或下级（含相同）
+fn is_related(a: &str, b: &str) -> bool {
+    let contains = |outer: &str, inner: &str| {
+        outer.is_empty() || inner == outer || inner.strip_prefix(outer).is_some_and(|rest| rest.starts_with('/'))
+    };
+    contains(a, b) || contains(b, a)
+}
+
+// 目录占用统计的缓存：路径变化时清除它自身、下级和各级上级目录的结果，
+// 另外设置有效期，兜底没有监听到的外部修改
+pub struct UsageCache {
+    ttl: Duration,
+    max_entries: usize,
+    // 限制同时遍历磁盘的线程数
+    permits: Semaphore,
+    state: Mutex<CacheState>,
+}
+
+impl UsageCache {
+    pub fn new(config: &serde_json::Value) -> Self {
+        let max_concurrent = config["max_concurrent"]
+            .as_u64()
+            .map(|n| n.max(1) as usize)
+            .unwrap_or_else(|| {
+                std::thread::available_parallelism()
+                    .map(|n| n.get())
+                    .unwrap_or(1)
+                    .min(4)
+            });
+
+        UsageCache {
+            ttl: Duration::from_secs(config["cache_ttl_secs"].as_u64().unwrap_or(600)),
+            max_entries: config["max_cached_dirs"].as_u64().unwrap_or(100_000).max(1) as usize,
+            permits: Semaphore::new(max_concurrent),
+            state: Mutex::new(CacheState::default()),
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, CacheState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn generation(&self) -> u64 {
+        self.lock().generation
+    }
+
+    fn get(&self, rel_path: &str) -> Option<Usage> {
+        self.lock()
+            .entries
+            .get(rel_path)
+            .filter(|cached| cached.at.elapsed() < self.ttl)
+            .map(|cached| cached.usage)
+    }
+
+    // 统计期间相关路径发生过变化时结果可能不准确，不写入缓存
+    fn insert(&self, rel_path: &str, usage: Usage, start: u64) {
+        let mut state = self.lock();
+        if state.changed_since(start, rel_path) {
+            return;
+        }
+        if state.entries.len() >= self.max_entries {
+            let ttl = self.ttl;
+            state.entries.retain(|_, cached| cached.at.elapsed() < ttl);
+            if state.entries.len() >= self.max_entries {
+                state.entries.clear();
+            }
+        }
+        state.entries.insert(
+            rel_path.to_string(),
+            CachedUsage {
+                usage,
+                at: Instant::now(),
+            },
+        );
+    }
+
+    // 文件或目录变化后调用
+    pub fn invalidate(&self, rel_path: &str) {
+        let mut state = self.lock();
+        state.generation += 1;
+        let generation = state.generation;
+        state.invalidations.push_back((generation, rel_path.to_string()));
+        if state.invalidations.len() > MAX_INVALIDATIONS {
+            state.invalidations.pop_front();
+        }
+
+        state.entries.retain(|path, _| !is_related(rel_path, path));
+    }
+}
+
+// 统计目录（递归）的占用，返回结果及是否来自索引；不使用索引时并发遍历各子目录并缓存结果
+pub async fn dir_usage(state: &Arc<AppState>, full_path: &FsPath, rel_path: &str) -> Result<(Usage, bool), AppError> {
+    if let Some((size, files, dirs)) = state.index.dir_usage(rel_path).await {
+        return Ok((Usage { size, files, dirs }, true));
+    }
+    let usage = scan_concurrent(state, full_path.to_path_buf(), rel_path.to_string()).await?;
+    Ok((usage, false))
+}
+
+// 用户可见的目录占用：缓存和索引中是整棵树的统计，按权限发生变化的规则路径修正——
+// 不可读的子树整体减去，其下又可读的子树再加回；返回结果及是否来自索引
+pub async fn visible_usage(
+    state: &Arc<AppState>,
+    user: &AuthUser,
+    full_path: &FsPath,
+    rel_path: &str,
+) -> Result<(Usage, bool), AppError> {
+    let (mut usage, indexed) = dir_usage(state, full_path, rel_path).await?;
+    for rule_path in state.acl.rule_paths_under(user, rel_path) {
+        let readable = state.acl.allows(user, &rule_path, Permission::Read);
+        let parent = rule_path.rsplit_once('/').map(|(parent, _)| parent).unwrap_or("");
+        if readable == state.acl.allows(user, parent, Permission::Read) {
+            continue;
+        }
+        let Some(entry) = entry_usage(state, rel_path, &rule_path).await? else {
+            continue;
+        };
+        if readable {
+            usage += entry;
+        } else {
+            usage -= entry;
+        }
+    }
+    Ok((usage, indexed))
+}
+
+// 规则路径本身（含目录内容）计入rel_path统计的占用；统计不跟随符号链接，
+// 途经符号链接或不存在时返回None
+async fn entry_usage(state: &Arc<AppState>, rel_path: &str, rule_path: &str) -> Result<Option<Usage>, AppError> {
+    let rest = rule_path.strip_prefix(rel_path).unwrap_or(rule_path).trim_start_matches('/');
+    let mut full_path = state.base_dir.join(rel_path);
+    let mut components = rest.split('/').peekable();
+    let metadata = loop {
+        let Some(name) = components.next() else {
+            return Ok(None);
+        };
+        full_path.push(name);
+        let Ok(metadata) = fs::symlink_metadata(&full_path).await else {
+            return Ok(None);
+        };
+        if components.peek().is_none() {
+            break metadata;
+        }
+        if !metadata.is_dir() {
+            return Ok(None);
+        }
+    };
+
+    if metadata.is_file() {
+        return Ok(Some(Usage {
+            size: metadata.len(),
+            files: 1,
+            dirs: 0,
+        }));
+    }
+    if !metadata.is_dir() {
+        return Ok(None);
+    }
+    let (mut usage, _) = dir_usage(state, &full_path, rule_path).await?;
+    usage.dirs += 1;
+    Ok(Some(usage))
+}
+
+// 当前目录下的文件直接累加，各子目录分别在阻塞线程池中并发统计，并发数受permits限制
+async fn scan_concurrent(state: &Arc<AppState>, dir: PathBuf, rel_path: String) -> Result<Usage, AppError> {
+    if let Some(usage) = state.usage.get(&rel_path) {
+        return Ok(usage);
+    }
+    let start = state.usage.generation();
+
+    let base_dir = state.base_dir.clone();
+    let level_dir = dir.clone();
+    let level_rel = rel_path.clone();
+    let (mut usage, subdirs) = tokio::task::spawn_blocking(move || read_level(&base_dir, &level_dir, &level_rel))
+        .await
+        .map_err(|e| AppError::IoError(e.to_string()))??;
+
+    let mut tasks = JoinSet::new();
+    for (path, child_rel) in subdirs {
+        let state = state.clone();
+        tasks.spawn(async move {
+            let _permit = state
+                .usage
+                .permits
+                .acquire()
+                .await
+                .map_err(|e| AppError::IoError(e.to_string()))?;
+            let scan_state = state.clone();
+            tokio::task::spawn_blocking(move || {
+                scan(&scan_state.usage, &scan_state.base_dir, &path, &child_rel, start)
+            })
+            .await
+            .map_err(|e| AppError::IoError(e.to_string()))
+        });
+    }
+    while let Some(result) = tasks.join_next().await {
+        usage += result.map_err(|e| AppError::IoError(e.to_string()))??;
+        usage.dirs += 1;
+    }
+
+    state.usage.insert(&rel_path, usage, start);
+    Ok(usage)
+}
+
+// 读取一层目录：返回其中文件的占用和各子目录；不跟随符号链接，回收站不计入
+fn read_level(base_dir: &FsPath, dir: &FsPath, rel_path: &str) -> std::io::Result<(Usage, Vec<(PathBuf, String)>)> {
+    let mut usage = Usage::default();
+    let mut subdirs = Vec::new();
+    for entry in std::fs::read_dir(dir)?.filter_map(|e| e.ok()) {
+        let path = entry.path();
+        if !is_safe_path(base_dir, &path) {
+            continue;
+        }
+        let Ok(metadata) = entry.metadata() else {
+            continue;
+        };
+        if metadata.is_dir() {
+            let child_rel = join_relative_path(rel_path, &entry.file_name().to_string_lossy());
+            subdirs.push((path, child_rel));
+        } else if metadata.is_file() {
+            usage.size += metadata.len();
+            usage.files += 1;
+        }
+    }
+    Ok((usage, subdirs))
+}
+
+// 递归统计目录占用，途经的每个子目录的结果都写入缓存
+fn scan(cache: &UsageCache, base_dir: &FsPath, dir: &FsPath, rel_path: &str, start: u64) -> Usage {
+    if let Some(usage) = cache.get(rel_path) {
+        return usage;
+    }
+
+    let (mut usage, subdirs) = read_level(base_dir, dir, rel_path).unwrap_or_default();
+    for (path, child_rel) in subdirs {
+        usage += scan(cache, base_dir, &path, &child_rel, start);
+        usage.dirs += 1;
+    }
+
+    cache.insert(rel_path, usage, start);
+    usage
+}
+
+pub async fn disk_usage_root(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+) -> Result<Json<ApiResponse<DiskUsageResponse>>, AppError> {
+    disk_usage_impl(state, &user, "").await
+}
+
+pub async fn disk_usage(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Path(path): Path<String>,
+) -> Result<Json<ApiResponse<DiskUsageResponse>>, AppError> {
+    disk_usage_impl(state, &user, &path).await
+}
+
+// 目录及其各子目录的占用（类似du），只列出有读权限的子目录，统计中不含无读权限的子树
+async fn disk_usage_impl(
+    state: Arc<AppState>,
+    user: &AuthUser,
+    path: &str,
+) -> Result<Json<ApiResponse<DiskUsageResponse>>, AppError> {
+    let (full_path, safe_path) = resolve_search_root(&state, user, path)?;
+    let (total, indexed) = visible_usage(&state, user, &full_path, &safe_path).await?;
+
+    let mut children = Vec::new();
+    let mut read_dir = fs::read_dir(&full_path).await?;
+    while let Some(entry) = read_dir.next_entry().await? {
+        let child_path = entry.path();
+        if !is_safe_path(&state.base_dir, &child_path) {
+            continue;
+        }
+        // 与统计口径一致，不跟随符号链接
+        if !entry.file_type().await.is_ok_and(|t| t.is_dir()) {
+            continue;
+        }
+
+        let name = entry.file_name().to_string_lossy().to_string();
+        let child_rel = join_relative_path(&safe_path, &name);
+        if !state.acl.allows(user, &child_rel, Permission::Read) {
+            continue;
+        }
+
+        let (usage, _) = visible_usage(&state, user, &child_path, &child_rel).await?;
+        children.push(DiskUsageEntry {
+            name,
+            path: child_rel,
+            size: usage.size,
+            files: usage.files,
+            dirs: usage.dirs,
+        });
+    }
+    children.sort_by(|a, b| b.size.cmp(&a.size).then_with(|| natural_cmp(&a.name, &b.name)));
+
+    Ok(Json(ApiResponse::success(DiskUsageResponse {
+        path: safe_path,
+        size: total.size,
+        files: total.files,
+        dirs: total.dirs,
+        indexed,
+        children,
+    })))
+}
+
+pub async fn tree_root(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Query(query): Query<TreeQuery>,
+) -> Result<Json<ApiResponse<TreeResponse>>, AppError> {
+    tree_impl(state, user, "", &query).await
+}
+
+pub async fn tree(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Path(path): Path<String>,
+    Query(query): Query<TreeQuery>,
+) -> Result<Json<ApiResponse<TreeResponse>>, AppError> {
+    tree_impl(state, user, &path, &query).await
+}
+
+// 嵌套的目录树，深度和节点数有上限
+async fn tree_impl(
+    state: Arc<AppState>,
+    user: AuthUser,
+    path: &str,
+    query: &TreeQuery,
+) -> Result<Json<ApiResponse<TreeResponse>>, AppError> {
+    let (full_path, safe_path) = resolve_search_root(&state, &user, path)?;
+    let max_depth = query.depth.unwrap_or(DEFAULT_TREE_DEPTH).clamp(1, MAX_TREE_DEPTH);
+
+    let metadata = fs::metadata(&full_path).await?;
+    let mut root = tree_node(file_entry_from_metadata(&full_path, &state.base_dir, &metadata)?, true);
+    root.path = safe_path;
+    if root.path.is_empty() {
+        root.name = String::new();
+    }
+
+    let walker = TreeWalker::new(&state, user.clone(), Some(max_depth))?;
+    let base_dir = state.base_dir.clone();
+    let (mut tree, nodes, truncated) =
+        tokio::task::spawn_blocking(move || build_tree(walker, root, &full_path, &base_dir, max_depth))
+            .await
+            .map_err(|e| AppError::IoError(e.to_string()))?;
+
+    if query.usage {
+        let mut dirs = Vec::new();
+        collect_dirs(&tree, &mut dirs);
+        // 先统计根目录，其下各目录的结果随之进入缓存
+        let mut usages = HashMap::new();
+        for rel_path in dirs {
+            let (usage, _) = visible_usage(&state, &user, &state.base_dir.join(&rel_path), &rel_path).await?;
+            usages.insert(rel_path, usage);
+        }
+        apply_usage(&mut tree, &usages);
+    }
+
+    Ok(Json(ApiResponse::success(TreeResponse { tree, nodes, truncated })))
+}
+
+fn tree_node(entry: FileEntry, expanded: bool) -> TreeNode {
+    TreeNode {
+        name: entry.name,
+        path: entry.path,
+        is_dir: entry.is_dir,
+        size: if entry.is_dir { 0 } else { entry.size },
+        modified: entry.modified,
+        files: None,
+        dirs: None,
+        children: (entry.is_dir && expanded).then(Vec::new),
+    }
+}
+
+// 遍历器按深度优先的先序访问条目，用栈保存从根到当前条目上级的各个节点
+fn build_tree(
+    mut walker: TreeWalker,
+    root: TreeNode,
+    root_path: &FsPath,
+    base_dir: &FsPath,
+    max_depth: usize,
+) -> (TreeNode, usize, bool) {
+    let root_rel = root.path.clone();
+    let root_depth = path_depth(&root_rel);
+    let mut stack = vec![root];
+    let mut nodes = 0;
+    let mut truncated = false;
+
+    let _ = walker.walk(root_path, &root_rel, &mut |path, rel_path, metadata| {
+        if nodes >= MAX_TREE_NODES {
+            truncated = true;
+            return ControlFlow::Break(());
+        }
+        let Ok(mut entry) = file_entry_from_metadata(path, base_dir, metadata) else {
+            return ControlFlow::Continue(());
+        };
+        entry.path = rel_path.to_string();
+
+        let parent = rel_path.rsplit_once('/').map(|(p, _)| p).unwrap_or("");
+        while stack.len() > 1 && stack.last().is_some_and(|node| node.path != parent) {
+            pop_node(&mut stack);
+        }
+
+        let expanded = path_depth(rel_path) - root_depth < max_depth;
+        stack.push(tree_node(entry, expanded));
+        nodes += 1;
+        ControlFlow::Continue(())
+    });
+
+    while stack.len() > 1 {
+        pop_node(&mut stack);
+    }
+    let mut root = stack.pop().unwrap_or_else(|| unreachable!());
+    sort_children(&mut root);
+    (root, nodes, truncated)
+}
+
+fn path_depth(rel_path: &str) -> usize {
+    if rel_path.is_empty() {
+        0
+    } else {
+        rel_path.split('/').count()
+    }
+}
+
+// 栈顶节点的子节点已全部访问完，把它挂到上级节点下
+fn pop_node(stack: &mut Vec<TreeNode>) {
+    let Some(mut node) = stack.pop() else {
+        return;
+    };
+    sort_children(&mut node);
+    if let Some(children) = stack.last_mut().and_then(|parent| parent.children.as_mut()) {
+        children.push(node);
+    }
+}
+
+// 文件夹在前，按名称自然排序
+fn sort_children(node: &mut TreeNode) {
+    if let Some(children) = node.children.as_mut() {
+        children.sort_by(|a, b| b.is_dir.cmp(&a.is_dir).then_with(|| natural_cmp(&a.name, &b.name)));
+    }
+}
+
+fn collect_dirs(node: &TreeNode, dirs: &mut Vec<String>) {
+    if node.is_dir {
+        dirs.push(node.path.clone());
+    }
+    for child in node.children.iter().flatten() {
+        collect_dirs(child, dirs);
+    }
+}
+
+fn apply_usage(node: &mut TreeNode, usages: &HashMap<String, Usage>) {
+    if let Some(usage) = usages.get(&node.path) {
+        node.size = usage.size;
+        node.files = Some(usage.files);
+        node.dirs = Some(usage.dirs);
+    }
+    for child in node.children.iter_mut().flatten() {
+        apply_usage(child, usages);
+    }
+}
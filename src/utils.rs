@@ -1,5 +1,6 @@
 use std::path::{Path, PathBuf};
-use chrono::{DateTime, Local};
+use std::sync::atomic::{AtomicU64, Ordering};
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, TimeZone};
 use crate::models::FileEntry;
 
 pub fn sanitize_path(path: &str) -> String {
@@ -8,12 +9,37 @@ pub fn sanitize_path(path: &str) -> String {
         .to_string()
 }
 
+// 回收站目录，位于根目录下，对所有文件接口隐藏
+pub const TRASH_DIR: &str = ".trash";
+
 pub fn is_safe_path(base: &Path, target: &Path) -> bool {
     // 规范化路径而不要求它们存在
     let base_normalized = normalize_path(base);
     let target_normalized = normalize_path(target);
     
-    target_normalized.starts_with(&base_normalized)
+    match target_normalized.strip_prefix(&base_normalized) {
+        Ok(relative) => relative.components().next() != Some(std::path::Component::Normal(TRASH_DIR.as_ref())),
+        Err(_) => false,
+    }
+}
+
+// 拼接相对路径（用于权限检查和返回给前端的路径）
+pub fn join_relative_path(dir: &str, name: &str) -> String {
+    if dir.is_empty() {
+        name.to_string()
+    } else {
+        format!("{}/{}", dir, name)
+    }
+}
+
+// 校验单个文件名：不能为空、不能包含路径分隔符或指向上级目录
+pub fn is_valid_file_name(name: &str) -> bool {
+    !name.is_empty()
+        && name != "."
+        && name != ".."
+        && !name.contains('/')
+        && !name.contains('\\')
+        && !name.contains('\0')
 }
 
 fn normalize_path(path: &Path) -> PathBuf {
@@ -36,8 +62,162 @@ fn normalize_path(path: &Path) -> PathBuf {
     normalized
 }
 
+// 是否为temp_path_for生成的临时文件
+pub fn is_temp_path(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|n| n.to_str())
+        .is_some_and(|name| name.starts_with('.') && name.ends_with(".part"))
+}
+
+// 为目标文件生成同目录下的临时文件路径，保证最终rename在同一文件系统内完成
+pub fn temp_path_for(target: &Path) -> PathBuf {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    let file_name = target
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or("upload");
+    let seq = COUNTER.fetch_add(1, Ordering::Relaxed);
+    let temp_name = format!(".{}.{}-{}.part", file_name, std::process::id(), seq);
+
+    match target.parent() {
+        Some(parent) => parent.join(temp_name),
+        None => PathBuf::from(temp_name),
+    }
+}
+
+// 临时文件守卫：未调用commit时在drop中删除文件（出错或客户端断开时清理）
+pub struct TempFileGuard {
+    path: PathBuf,
+    committed: bool,
+}
+
+impl TempFileGuard {
+    pub fn new(path: PathBuf) -> Self {
+        TempFileGuard { path, committed: false }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    // 将临时文件原子地重命名为目标文件
+    pub async fn commit(mut self, target: &Path) -> std::io::Result<()> {
+        tokio::fs::rename(&self.path, target).await?;
+        self.committed = true;
+        Ok(())
+    }
+
+    // 与commit相同，但目标已存在时返回AlreadyExists而不是覆盖
+    pub async fn commit_new(mut self, target: &Path) -> std::io::Result<()> {
+        tokio::fs::hard_link(&self.path, target).await?;
+        self.committed = true;
+        let _ = tokio::fs::remove_file(&self.path).await;
+        Ok(())
+    }
+
+    // commit的同步版本，供阻塞线程中使用
+    pub fn commit_blocking(mut self, target: &Path) -> std::io::Result<()> {
+        std::fs::rename(&self.path, target)?;
+        self.committed = true;
+        Ok(())
+    }
+}
+
+impl Drop for TempFileGuard {
+    fn drop(&mut self) {
+        if !self.committed {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+}
+
+// 为已存在的路径生成不冲突的新路径，如 "a.txt" -> "a (1).txt"
+pub fn unique_path(path: &Path) -> PathBuf {
+    let parent = path.parent().unwrap_or_else(|| Path::new(""));
+    let file_name = path
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or("file");
+    let name = unique_name(file_name, path.is_dir(), |candidate| {
+        std::fs::symlink_metadata(parent.join(candidate)).is_ok()
+    });
+    parent.join(name)
+}
+
+// 生成taken不包含的名称，冲突时在扩展名前追加序号；目录和无扩展名的文件直接在末尾追加
+pub fn unique_name(name: &str, is_dir: bool, taken: impl Fn(&str) -> bool) -> String {
+    if !taken(name) {
+        return name.to_string();
+    }
+
+    let (stem, ext) = match name.rfind('.') {
+        Some(pos) if pos > 0 && !is_dir => (&name[..pos], &name[pos..]),
+        _ => (name, ""),
+    };
+
+    let mut n = 1;
+    loop {
+        let candidate = format!("{} ({}){}", stem, n, ext);
+        if !taken(&candidate) {
+            return candidate;
+        }
+        n += 1;
+    }
+}
+
+// 原子写入整个文件：先写临时文件再重命名，避免写入中断留下损坏的文件
+pub async fn write_file_atomic(path: &Path, content: &[u8]) -> std::io::Result<()> {
+    let guard = TempFileGuard::new(temp_path_for(path));
+    tokio::fs::write(guard.path(), content).await?;
+    guard.commit(path).await
+}
+
+// 移动文件，跨文件系统时退化为“复制到临时文件 + 重命名 + 删除源文件”
+pub async fn move_file(src: &Path, dst: &Path) -> std::io::Result<()> {
+    match tokio::fs::rename(src, dst).await {
+        Ok(_) => Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::CrossesDevices => {
+            let guard = TempFileGuard::new(temp_path_for(dst));
+            tokio::fs::copy(src, guard.path()).await?;
+            guard.commit(dst).await?;
+            tokio::fs::remove_file(src).await
+        }
+        Err(e) => Err(e),
+    }
+}
+
+// 移动目录，跨文件系统时退化为“复制到临时目录 + 重命名 + 删除源目录”
+pub async fn move_dir(src: &Path, dst: &Path) -> std::io::Result<()> {
+    match tokio::fs::rename(src, dst).await {
+        Ok(_) => Ok(()),
+        Err(e) if e.kind() == std::io::ErrorKind::CrossesDevices => {
+            let temp = temp_path_for(dst);
+            let copied = match crate::handlers::copy_dir_recursive(src.to_path_buf(), temp.clone()).await {
+                Ok(_) => tokio::fs::rename(&temp, dst).await,
+                Err(e) => Err(e),
+            };
+            if let Err(e) = copied {
+                let _ = tokio::fs::remove_dir_all(&temp).await;
+                return Err(e);
+            }
+            tokio::fs::remove_dir_all(src).await
+        }
+        Err(e) => Err(e),
+    }
+}
+
 pub async fn create_file_entry(path: &Path, base_dir: &Path) -> anyhow::Result<FileEntry> {
     let metadata = tokio::fs::metadata(path).await?;
+    Ok(file_entry_from_metadata(path, base_dir, &metadata)?)
+}
+
+// 根据已读取的元数据构造文件条目（同步遍历目录时使用）
+pub fn file_entry_from_metadata(
+    path: &Path,
+    base_dir: &Path,
+    metadata: &std::fs::Metadata,
+) -> std::io::Result<FileEntry> {
     let name = path
         .file_name()
         .and_then(|n| n.to_str())
@@ -66,11 +246,92 @@ pub async fn create_file_entry(path: &Path, base_dir: &Path) -> anyhow::Result<F
     })
 }
 
+// 自然排序比较：数字段按数值比较（file2 < file10），其余字符忽略大小写，完全相同时按原始字节比较
+pub fn natural_cmp(a: &str, b: &str) -> std::cmp::Ordering {
+    use std::cmp::Ordering;
+
+    let (mut x, mut y) = (a, b);
+    while let (Some(cx), Some(cy)) = (x.chars().next(), y.chars().next()) {
+        let ordering = if cx.is_ascii_digit() && cy.is_ascii_digit() {
+            let dx = x.find(|c: char| !c.is_ascii_digit()).unwrap_or(x.len());
+            let dy = y.find(|c: char| !c.is_ascii_digit()).unwrap_or(y.len());
+            let nx = x[..dx].trim_start_matches('0');
+            let ny = y[..dy].trim_start_matches('0');
+            let ordering = nx.len().cmp(&ny.len()).then_with(|| nx.cmp(ny));
+            x = &x[dx..];
+            y = &y[dy..];
+            ordering
+        } else {
+            x = &x[cx.len_utf8()..];
+            y = &y[cy.len_utf8()..];
+            cx.to_lowercase().cmp(cy.to_lowercase())
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+
+    x.len().cmp(&y.len()).then_with(|| a.cmp(b))
+}
+
 pub fn format_system_time(time: std::time::SystemTime) -> String {
     let datetime: DateTime<Local> = time.into();
     datetime.format("%Y-%m-%d %H:%M:%S").to_string()
 }
 
+pub fn format_timestamp(timestamp: i64) -> String {
+    Local
+        .timestamp_opt(timestamp, 0)
+        .single()
+        .map(|t| t.format("%Y-%m-%d %H:%M:%S").to_string())
+        .unwrap_or_default()
+}
+
+// HTTP日期格式（Last-Modified等响应头）
+pub fn format_http_date(time: std::time::SystemTime) -> String {
+    DateTime::<chrono::Utc>::from(time)
+        .format("%a, %d %b %Y %H:%M:%S GMT")
+        .to_string()
+}
+
+pub fn modified_time(metadata: &std::fs::Metadata) -> std::time::SystemTime {
+    metadata.modified().unwrap_or(std::time::UNIX_EPOCH)
+}
+
+// 纳秒精度的修改时间，用于判断文件是否变化（同一秒内的改写也能发现）
+pub fn modified_nanos(metadata: &std::fs::Metadata) -> i64 {
+    modified_time(metadata)
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_nanos() as i64)
+        .unwrap_or(0)
+}
+
+// 由修改时间和大小生成的ETag，文件内容变化时随之变化
+pub fn file_etag(metadata: &std::fs::Metadata) -> String {
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+        .map(|d| d.as_nanos())
+        .unwrap_or_default();
+    format!("\"{:x}-{:x}\"", modified, metadata.len())
+}
+
+// 解析本地时间字符串为时间戳，支持 "YYYY-MM-DD"（当天结束）和 "YYYY-MM-DD HH:MM:SS"
+pub fn parse_datetime(value: &str) -> Option<i64> {
+    let datetime = NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S")
+        .or_else(|_| {
+            NaiveDate::parse_from_str(value, "%Y-%m-%d")
+                .map(|d| d.and_hms_opt(23, 59, 59).unwrap_or_default())
+        })
+        .ok()?;
+
+    Local
+        .from_local_datetime(&datetime)
+        .single()
+        .map(|t| t.timestamp())
+}
+
 pub fn get_mime_type(path: &Path) -> Option<String> {
     path.extension()
         .and_then(|ext| ext.to_str())
@@ -93,3 +354,37 @@ pub fn get_mime_type(path: &Path) -> Option<String> {
             }.to_string())
         })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cmp::Ordering;
+
+    fn sorted(names: &[&str]) -> Vec<String> {
+        let mut names: Vec<String> = names.iter().map(|n| n.to_string()).collect();
+        names.sort_by(|a, b| natural_cmp(a, b));
+        names
+    }
+
+    #[test]
+    fn natural_order_of_numbers() {
+        assert_eq!(
+            sorted(&["file10.txt", "file2.txt", "file1.txt", "file1b.txt"]),
+            vec!["file1.txt", "file1b.txt", "file2.txt", "file10.txt"]
+        );
+        // 超出u64范围的数字段按位数和字典序比较
+        assert_eq!(natural_cmp("a99999999999999999999999", "a100000000000000000000000"), Ordering::Less);
+        assert_eq!(natural_cmp("v1.10", "v1.9"), Ordering::Greater);
+    }
+
+    #[test]
+    fn natural_order_ties() {
+        assert_eq!(natural_cmp("abc", "ABD"), Ordering::Less);
+        // 忽略大小写和前导零后相同的名称仍有确定的顺序
+        assert_eq!(natural_cmp("a01", "a1"), Ordering::Less);
+        assert_eq!(natural_cmp("B", "b"), Ordering::Less);
+        assert_eq!(natural_cmp("same", "same"), Ordering::Equal);
+        assert_eq!(natural_cmp("ab", "abc"), Ordering::Less);
+        assert_eq!(natural_cmp("文件2", "文件10"), Ordering::Less);
+    }
+}
@@ -0,0 +1,376 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::HeaderMap,
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::io;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::fs;
+
+use crate::{
+    acl::{path_components, Permission},
+    auth::AuthUser,
+    errors::AppError,
+    events,
+    handlers::send_file_as,
+    models::*,
+    utils::*,
+    AppState,
+};
+
+// 版本保留策略：keep为保留的版本数（0表示不保留历史版本），max_age_days为保留天数（0表示不限）
+#[derive(Debug, Deserialize, Clone, Copy)]
+struct RetentionPolicy {
+    #[serde(default = "default_keep")]
+    keep: usize,
+    #[serde(default)]
+    max_age_days: u64,
+}
+
+fn default_keep() -> usize {
+    10
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> Self {
+        RetentionPolicy {
+            keep: default_keep(),
+            max_age_days: 0,
+        }
+    }
+}
+
+// 按目录覆盖默认保留策略，path为相对base_dir的路径前缀
+#[derive(Debug, Deserialize)]
+struct RetentionRule {
+    #[serde(default)]
+    path: String,
+    #[serde(flatten)]
+    policy: RetentionPolicy,
+}
+
+// 单个历史版本，内容保存为 {key}/{id}.bin
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct VersionRecord {
+    id: String,
+    size: u64,
+    modified: i64,
+    archived: i64,
+    archived_by: String,
+}
+
+impl VersionRecord {
+    fn info(&self) -> VersionInfo {
+        VersionInfo {
+            id: self.id.clone(),
+            size: self.size,
+            modified: format_timestamp(self.modified),
+            archived: format_timestamp(self.archived),
+            archived_by: self.archived_by.clone(),
+        }
+    }
+}
+
+// 每个文件的版本索引，持久化为 {key}/index.json，最新的版本在前
+#[derive(Debug, Serialize, Deserialize, Default)]
+struct VersionIndex {
+    path: String,
+    versions: Vec<VersionRecord>,
+}
+
+pub struct VersionStore {
+    dir: PathBuf,
+    default_policy: RetentionPolicy,
+    rules: Vec<(Vec<String>, RetentionPolicy)>,
+    // 串行化索引的读-改-写
+    lock: tokio::sync::Mutex<()>,
+}
+
+impl VersionStore {
+    pub async fn new(dir: PathBuf, config: &serde_json::Value) -> anyhow::Result<Self> {
+        fs::create_dir_all(&dir).await?;
+
+        let mut default_policy = RetentionPolicy::default();
+        if let Some(keep) = config["keep"].as_u64() {
+            default_policy.keep = keep as usize;
+        }
+        if let Some(max_age_days) = config["max_age_days"].as_u64() {
+            default_policy.max_age_days = max_age_days;
+        }
+
+        let rules: Vec<RetentionRule> = match config.get("rules") {
+            Some(rules) => serde_json::from_value(rules.clone())?,
+            None => Vec::new(),
+        };
+
+        Ok(VersionStore {
+            dir,
+            default_policy,
+            rules: rules
+                .into_iter()
+                .map(|rule| (path_components(&rule.path), rule.policy))
+                .collect(),
+            lock: tokio::sync::Mutex::new(()),
+        })
+    }
+
+    // 按最长前缀匹配文件所在目录的保留策略
+    fn policy(&self, rel_path: &str) -> RetentionPolicy {
+        let mut components = path_components(rel_path);
+        components.pop();
+
+        self.rules
+            .iter()
+            .filter(|(prefix, _)| components.starts_with(prefix))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(_, policy)| *policy)
+            .unwrap_or(self.default_policy)
+    }
+
+    // 按规范化后的路径计算键，"a//b"、"./a/b"与"a/b"对应同一个版本目录
+    fn key_dir(&self, rel_path: &str) -> PathBuf {
+        let normalized = path_components(rel_path).join("/");
+        self.dir.join(hex::encode(Sha256::digest(normalized.as_bytes())))
+    }
+
+    fn data_path(&self, rel_path: &str, id: &str) -> PathBuf {
+        self.key_dir(rel_path).join(format!("{}.bin", id))
+    }
+
+    async fn load_index(&self, rel_path: &str) -> io::Result<VersionIndex> {
+        match fs::read_to_string(self.key_dir(rel_path).join("index.json")).await {
+            Ok(content) => serde_json::from_str(&content).map_err(io::Error::other),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(VersionIndex {
+                path: rel_path.to_string(),
+                versions: Vec::new(),
+            }),
+            Err(e) => Err(e),
+        }
+    }
+
+    async fn save_index(&self, rel_path: &str, index: &VersionIndex) -> io::Result<()> {
+        let key_dir = self.key_dir(rel_path);
+        if index.versions.is_empty() {
+            let _ = fs::remove_dir_all(&key_dir).await;
+            return Ok(());
+        }
+        let content = serde_json::to_vec(index).map_err(io::Error::other)?;
+        write_file_atomic(&key_dir.join("index.json"), &content).await
+    }
+
+    // 按保留策略删除多余和过期的版本
+    async fn prune(&self, rel_path: &str, index: &mut VersionIndex) {
+        let policy = self.policy(rel_path);
+        let cutoff = if policy.max_age_days > 0 {
+            Utc::now().timestamp() - (policy.max_age_days * 86400) as i64
+        } else {
+            i64::MIN
+        };
+
+        let mut kept = Vec::new();
+        for (i, record) in index.versions.drain(..).enumerate() {
+            if i < policy.keep && record.archived > cutoff {
+                kept.push(record);
+            } else {
+                let _ = fs::remove_file(self.data_path(rel_path, &record.id)).await;
+            }
+        }
+        index.versions = kept;
+    }
+
+    // 文件即将被覆盖前保存其当前内容；文件不存在或所在目录不保留版本时不做任何事
+    pub async fn preserve(&self, full_path: &std::path::Path, rel_path: &str, user: &str) -> io::Result<()> {
+        let metadata = match fs::metadata(full_path).await {
+            Ok(metadata) if metadata.is_file() => metadata,
+            _ => return Ok(()),
+        };
+        if self.policy(rel_path).keep == 0 {
+            return Ok(());
+        }
+
+        let _lock = self.lock.lock().await;
+        let mut index = self.load_index(rel_path).await?;
+
+        let record = VersionRecord {
+            id: uuid::Uuid::new_v4().simple().to_string(),
+            size: metadata.len(),
+            modified: metadata
+                .modified()
+                .map(|t| chrono::DateTime::<Utc>::from(t).timestamp())
+                .unwrap_or_default(),
+            archived: Utc::now().timestamp(),
+            archived_by: user.to_string(),
+        };
+
+        fs::create_dir_all(self.key_dir(rel_path)).await?;
+        let data_path = self.data_path(rel_path, &record.id);
+        // 必须复制而不是硬链接，否则原地修改原文件（如WebDAV、S3写入）会连带改动历史版本；
+        // fs::copy在Linux上使用copy_file_range，支持reflink的文件系统（btrfs、XFS）会共享数据块
+        let guard = TempFileGuard::new(temp_path_for(&data_path));
+        fs::copy(full_path, guard.path()).await?;
+        guard.commit(&data_path).await?;
+
+        index.versions.insert(0, record);
+        self.prune(rel_path, &mut index).await;
+        self.save_index(rel_path, &index).await
+    }
+
+    // 读取当前保留的版本（顺带清理已过期的版本）
+    async fn versions(&self, rel_path: &str) -> io::Result<Vec<VersionRecord>> {
+        let _lock = self.lock.lock().await;
+        let mut index = self.load_index(rel_path).await?;
+        let count = index.versions.len();
+        self.prune(rel_path, &mut index).await;
+        if index.versions.len() != count {
+            self.save_index(rel_path, &index).await?;
+        }
+        Ok(index.versions)
+    }
+
+    async fn find(&self, rel_path: &str, id: &str) -> Result<(VersionRecord, PathBuf), AppError> {
+        let record = self
+            .versions(rel_path)
+            .await?
+            .into_iter()
+            .find(|record| record.id == id)
+            .ok_or_else(|| AppError::NotFound("Version not found".to_string()))?;
+        let data_path = self.data_path(rel_path, &record.id);
+        Ok((record, data_path))
+    }
+}
+
+// 解析并校验版本接口的文件路径
+fn resolve_file(state: &AppState, path: &str) -> Result<(String, PathBuf), AppError> {
+    let safe_path = sanitize_path(path);
+    let full_path = state.base_dir.join(&safe_path);
+
+    if safe_path.is_empty() || !is_safe_path(&state.base_dir, &full_path) {
+        return Err(AppError::PermissionDenied("Access denied".to_string()));
+    }
+
+    if full_path.is_dir() {
+        return Err(AppError::InvalidPath("Directories have no versions".to_string()));
+    }
+
+    Ok((safe_path, full_path))
+}
+
+// 列出文件的历史版本，指定version参数时下载该版本
+pub async fn get_versions(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Path(path): Path<String>,
+    Query(query): Query<VersionQuery>,
+    headers: HeaderMap,
+) -> Result<Response, AppError> {
+    let (safe_path, full_path) = resolve_file(&state, &path)?;
+    state.acl.check(&user, &safe_path, Permission::Read)?;
+
+    match query.version {
+        Some(id) => {
+            let (_, data_path) = state.versions.find(&safe_path, &id).await?;
+            let file_name = full_path
+                .file_name()
+                .and_then(|n| n.to_str())
+                .unwrap_or("download");
+            send_file_as(&data_path, file_name, &headers).await
+        }
+        None => {
+            let versions = state.versions.versions(&safe_path).await?;
+            Ok(Json(ApiResponse::success(
+                versions.iter().map(VersionRecord::info).collect::<Vec<_>>(),
+            ))
+            .into_response())
+        }
+    }
+}
+
+// 把文件恢复为指定版本，恢复前的当前内容同样保存为一个版本
+pub async fn restore_version(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    Path(path): Path<String>,
+    Query(query): Query<VersionQuery>,
+) -> Result<Json<ApiResponse<()>>, AppError> {
+    let (safe_path, full_path) = resolve_file(&state, &path)?;
+    state.acl.check(&user, &safe_path, Permission::Write)?;
+
+    let id = query
+        .version
+        .ok_or_else(|| AppError::InvalidRequest("version is required".to_string()))?;
+    let (record, data_path) = state.versions.find(&safe_path, &id).await?;
+
+    if let Some(parent) = full_path.parent() {
+        fs::create_dir_all(parent).await?;
+    }
+
+    let guard = TempFileGuard::new(temp_path_for(&full_path));
+    fs::copy(&data_path, guard.path()).await?;
+    state
+        .versions
+        .preserve(&full_path, &safe_path, &user.username)
+        .await?;
+    guard.commit(&full_path).await?;
+    events::file_changed(&state, ChangeKind::Modified, &safe_path, &user.username).await;
+
+    tracing::info!(
+        "{} restored {} to version {} ({})",
+        user,
+        safe_path,
+        record.id,
+        format_timestamp(record.archived)
+    );
+    Ok(Json(ApiResponse::success(())))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::TempDir;
+
+    #[tokio::test]
+    async fn versions_are_copies_keyed_by_normalized_path() {
+        let dir = TempDir::new();
+        let store = VersionStore::new(dir.path().join("versions"), &serde_json::Value::Null)
+            .await
+            .unwrap();
+        let file = dir.path().join("a.txt");
+
+        std::fs::write(&file, "v1").unwrap();
+        store.preserve(&file, "docs//a.txt", "alice").await.unwrap();
+        // 原地改写当前文件（同一inode）不能影响已保存的版本
+        std::fs::write(&file, "v2").unwrap();
+
+        let versions = store.versions("./docs/a.txt").await.unwrap();
+        assert_eq!(versions.len(), 1);
+        let (_, data_path) = store.find("docs/a.txt", &versions[0].id).await.unwrap();
+        assert_eq!(std::fs::read_to_string(data_path).unwrap(), "v1");
+    }
+
+    #[tokio::test]
+    async fn retention_keeps_newest_versions() {
+        let dir = TempDir::new();
+        let config = serde_json::json!({ "keep": 2, "rules": [{ "path": "tmp", "keep": 0 }] });
+        let store = VersionStore::new(dir.path().join("versions"), &config).await.unwrap();
+        let file = dir.path().join("a.txt");
+
+        for content in ["v1", "v2", "v3"] {
+            std::fs::write(&file, content).unwrap();
+            store.preserve(&file, "a.txt", "alice").await.unwrap();
+            store.preserve(&file, "tmp/a.txt", "alice").await.unwrap();
+        }
+
+        let versions = store.versions("a.txt").await.unwrap();
+        let contents: Vec<String> = versions
+            .iter()
+            .map(|v| std::fs::read_to_string(store.data_path("a.txt", &v.id)).unwrap())
+            .collect();
+        assert_eq!(contents, ["v3", "v2"]);
+        assert!(store.versions("tmp/a.txt").await.unwrap().is_empty());
+    }
+}
@@ -0,0 +1,1188 @@
+use axum::{
+    body::Body,
+    extract::{Request, State},
+    http::{header, HeaderMap, HeaderName, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Utc};
+use futures_util::StreamExt;
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, CONTROLS};
+use std::collections::{BTreeMap, HashMap};
+use std::fs::Metadata;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use tokio::fs;
+use tokio::io::AsyncWriteExt;
+
+use crate::{
+    acl::{self, Permission},
+    auth::{resolve_user, AuthUser},
+    errors::AppError,
+    events,
+    handlers::{copy_dir_recursive, send_file},
+    models::ChangeKind,
+    utils::*,
+    xml::{self, XmlElement},
+    AppState,
+};
+
+pub const DAV_PREFIX: &str = "/dav";
+
+const DAV_NS: &str = "DAV:";
+const LOCK_TOKEN_PREFIX: &str = "opaquelocktoken:";
+const DEFAULT_LOCK_SECS: u64 = 3600;
+const MAX_LOCK_SECS: u64 = 7 * 86400;
+const MAX_XML_BODY: usize = 1024 * 1024;
+const ALLOWED_METHODS: &str =
+    "OPTIONS, GET, HEAD, PUT, DELETE, MKCOL, COPY, MOVE, PROPFIND, PROPPATCH, LOCK, UNLOCK";
+
+const DAV: HeaderName = HeaderName::from_static("dav");
+const DEPTH: HeaderName = HeaderName::from_static("depth");
+const DESTINATION: HeaderName = HeaderName::from_static("destination");
+const OVERWRITE: HeaderName = HeaderName::from_static("overwrite");
+const LOCK_TOKEN: HeaderName = HeaderName::from_static("lock-token");
+const IF: HeaderName = HeaderName::from_static("if");
+const TIMEOUT: HeaderName = HeaderName::from_static("timeout");
+const MS_AUTHOR_VIA: HeaderName = HeaderName::from_static("ms-author-via");
+
+// href中需要编码的字符（路径分隔符保留）
+const HREF_ENCODE: &AsciiSet = &CONTROLS
+    .add(b' ')
+    .add(b'"')
+    .add(b'#')
+    .add(b'%')
+    .add(b'<')
+    .add(b'>')
+    .add(b'?')
+    .add(b'`')
+    .add(b'{')
+    .add(b'}');
+
+// 写锁，保存在内存中，服务重启后失效
+#[derive(Debug, Clone)]
+struct DavLock {
+    token: String,
+    path: String,
+    // Depth: infinity，锁定整个子树
+    deep: bool,
+    exclusive: bool,
+    owner: String,
+    user: String,
+    timeout: u64,
+    expires: i64,
+}
+
+pub struct WebDavStore {
+    props_file: PathBuf,
+    // 相对路径 -> ("{命名空间}属性名" -> 值)，PROPPATCH设置的自定义属性
+    props: Mutex<HashMap<String, BTreeMap<String, String>>>,
+    locks: Mutex<Vec<DavLock>>,
+}
+
+// a是否为b的上级路径（根目录用空字符串表示）
+fn is_ancestor(a: &str, b: &str) -> bool {
+    if a.is_empty() {
+        !b.is_empty()
+    } else {
+        b.len() > a.len() && b.starts_with(a) && b.as_bytes()[a.len()] == b'/'
+    }
+}
+
+fn is_same_or_descendant(path: &str, root: &str) -> bool {
+    path == root || is_ancestor(root, path)
+}
+
+impl WebDavStore {
+    pub async fn new(props_file: PathBuf) -> anyhow::Result<Self> {
+        let props = match fs::read_to_string(&props_file).await {
+            Ok(content) => serde_json::from_str(&content)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => HashMap::new(),
+            Err(e) => return Err(e.into()),
+        };
+
+        Ok(WebDavStore {
+            props_file,
+            props: Mutex::new(props),
+            locks: Mutex::new(Vec::new()),
+        })
+    }
+
+    async fn persist_props(&self) -> Result<(), AppError> {
+        let content = {
+            let props = self.props.lock().unwrap_or_else(|e| e.into_inner());
+            serde_json::to_vec_pretty(&*props).map_err(anyhow::Error::from)?
+        };
+        write_file_atomic(&self.props_file, &content).await?;
+        Ok(())
+    }
+
+    fn dead_props(&self, path: &str) -> BTreeMap<String, String> {
+        self.props.lock().unwrap_or_else(|e| e.into_inner()).get(path).cloned().unwrap_or_default()
+    }
+
+    // 删除路径及其子路径的自定义属性
+    async fn remove_props(&self, path: &str) -> Result<(), AppError> {
+        let changed = {
+            let mut props = self.props.lock().unwrap_or_else(|e| e.into_inner());
+            let before = props.len();
+            props.retain(|p, _| !is_same_or_descendant(p, path));
+            props.len() != before
+        };
+        if changed {
+            self.persist_props().await?;
+        }
+        Ok(())
+    }
+
+    // 把路径及其子路径的自定义属性复制或移动到新路径下
+    async fn transfer_props(&self, from: &str, to: &str, keep_source: bool) -> Result<(), AppError> {
+        let changed = {
+            let mut props = self.props.lock().unwrap_or_else(|e| e.into_inner());
+            let moved: Vec<(String, BTreeMap<String, String>)> = props
+                .iter()
+                .filter(|(p, _)| is_same_or_descendant(p, from))
+                .map(|(p, v)| (format!("{}{}", to, &p[from.len()..]), v.clone()))
+                .collect();
+            if !keep_source {
+                props.retain(|p, _| !is_same_or_descendant(p, from));
+            }
+            let changed = !moved.is_empty();
+            props.extend(moved);
+            changed
+        };
+        if changed {
+            self.persist_props().await?;
+        }
+        Ok(())
+    }
+
+    fn active_locks(&self) -> std::sync::MutexGuard<'_, Vec<DavLock>> {
+        let now = Utc::now().timestamp();
+        let mut locks = self.locks.lock().unwrap_or_else(|e| e.into_inner());
+        locks.retain(|l| l.expires > now);
+        locks
+    }
+
+    // 作用于path的锁（path本身的锁和上级目录的深度锁）
+    fn locks_covering(&self, path: &str) -> Vec<DavLock> {
+        self.active_locks()
+            .iter()
+            .filter(|l| l.path == path || (l.deep && is_ancestor(&l.path, path)))
+            .cloned()
+            .collect()
+    }
+
+    // 修改path（deep时包括其子树）前检查锁，请求需要在If头中提交自己持有的锁令牌
+    fn check_locks(&self, path: &str, deep: bool, headers: &HeaderMap, user: &AuthUser) -> Result<(), AppError> {
+        let submitted = if_header_tokens(headers);
+        let locks = self.active_locks();
+        let blocked = locks.iter().find(|l| {
+            let affected = l.path == path
+                || (l.deep && is_ancestor(&l.path, path))
+                || (deep && is_ancestor(path, &l.path));
+            affected && !(submitted.contains(&l.token) && l.user == user.username)
+        });
+
+        match blocked {
+            Some(lock) => Err(AppError::Locked(format!("Resource is locked: /{}", lock.path))),
+            None => Ok(()),
+        }
+    }
+
+    // 删除路径及其子路径上的锁（资源被删除或移走后锁随之失效）
+    fn release_locks(&self, path: &str) {
+        self.active_locks()
+            .retain(|l| !is_same_or_descendant(&l.path, path));
+    }
+}
+
+// 从If请求头中取出所有锁令牌，如 (<opaquelocktoken:...>)
+fn if_header_tokens(headers: &HeaderMap) -> Vec<String> {
+    let value = match headers.get(&IF).and_then(|v| v.to_str().ok()) {
+        Some(value) => value,
+        None => return Vec::new(),
+    };
+
+    value
+        .split('<')
+        .skip(1)
+        .filter_map(|part| part.split_once('>'))
+        .map(|(token, _)| token.to_string())
+        .filter(|token| token.starts_with(LOCK_TOKEN_PREFIX))
+        .collect()
+}
+
+// 认证中间件：与API相同的认证方式，失败时返回Basic认证质询，供操作系统的WebDAV客户端弹出登录框
+pub async fn require_dav_auth(
+    State(state): State<Arc<AppState>>,
+    mut req: Request,
+    next: Next,
+) -> Response {
+    match resolve_user(&state, req.headers()).await {
+        Some(user) => {
+            req.extensions_mut().insert(user);
+            next.run(req).await
+        }
+        None => (
+            StatusCode::UNAUTHORIZED,
+            [(header::WWW_AUTHENTICATE, "Basic realm=\"file-server\", charset=\"UTF-8\"")],
+        )
+            .into_response(),
+    }
+}
+
+// 把 /dav 下的请求路径转换为相对于base_dir的路径
+fn request_path(uri_path: &str) -> Result<String, AppError> {
+    let path = uri_path
+        .strip_prefix(DAV_PREFIX)
+        .filter(|rest| rest.is_empty() || rest.starts_with('/'))
+        .ok_or_else(|| AppError::InvalidPath("Path is outside the WebDAV root".to_string()))?;
+    // 规范化后再用于锁和属性的查找，"./b"、"a/../b"与"b"是同一个资源
+    let decoded = percent_decode_str(path).decode_utf8_lossy();
+    Ok(acl::path_components(&decoded).join("/"))
+}
+
+// 解析Destination头，可以是完整URL或绝对路径
+fn destination_path(headers: &HeaderMap) -> Result<String, AppError> {
+    let value = headers
+        .get(&DESTINATION)
+        .and_then(|v| v.to_str().ok())
+        .ok_or_else(|| AppError::InvalidRequest("Destination header is required".to_string()))?;
+
+    let path = match value.find("://") {
+        Some(pos) => {
+            let rest = &value[pos + 3..];
+            rest.find('/').map(|i| &rest[i..]).unwrap_or("/")
+        }
+        None => value,
+    };
+    request_path(path)
+}
+
+fn resolve(state: &AppState, rel_path: &str) -> Result<PathBuf, AppError> {
+    let full_path = state.base_dir.join(rel_path);
+    if !is_safe_path(&state.base_dir, &full_path) {
+        return Err(AppError::PermissionDenied("Access denied".to_string()));
+    }
+    Ok(full_path)
+}
+
+fn href(rel_path: &str, is_dir: bool) -> String {
+    let mut href = format!("{}/", DAV_PREFIX);
+    href.push_str(&utf8_percent_encode(rel_path, HREF_ENCODE).to_string());
+    if is_dir && !rel_path.is_empty() {
+        href.push('/');
+    }
+    href
+}
+
+fn parent_exists(full_path: &std::path::Path) -> bool {
+    full_path.parent().map(|p| p.is_dir()).unwrap_or(false)
+}
+
+async fn read_xml_body(body: Body) -> Result<Option<XmlElement>, AppError> {
+    let bytes = axum::body::to_bytes(body, MAX_XML_BODY)
+        .await
+        .map_err(|_| AppError::PayloadTooLarge("XML body too large".to_string()))?;
+    xml::parse(&bytes)
+}
+
+fn multistatus(responses: &str) -> Response {
+    (
+        StatusCode::MULTI_STATUS,
+        [(header::CONTENT_TYPE, "application/xml; charset=utf-8")],
+        format!(
+            "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<D:multistatus xmlns:D=\"DAV:\">{}</D:multistatus>",
+            responses
+        ),
+    )
+        .into_response()
+}
+
+fn propstat(props: &str, status: StatusCode) -> String {
+    format!(
+        "<D:propstat><D:prop>{}</D:prop><D:status>HTTP/1.1 {}</D:status></D:propstat>",
+        props, status
+    )
+}
+
+fn prop_element(name: &str, inner: &str) -> String {
+    if inner.is_empty() {
+        format!("<D:{}/>", name)
+    } else {
+        format!("<D:{}>{}</D:{}>", name, inner, name)
+    }
+}
+
+// 自定义属性键为 "{命名空间}属性名"
+fn dead_prop_element(key: &str, value: Option<&str>) -> String {
+    let (ns, name) = key
+        .strip_prefix('{')
+        .and_then(|k| k.split_once('}'))
+        .unwrap_or(("", key));
+    match value {
+        Some(value) if !value.is_empty() => format!(
+            "<x:{} xmlns:x=\"{}\">{}</x:{}>",
+            name,
+            xml::escape(ns),
+            xml::escape(value),
+            name
+        ),
+        _ => format!("<x:{} xmlns:x=\"{}\"/>", name, xml::escape(ns)),
+    }
+}
+
+fn active_lock_xml(lock: &DavLock) -> String {
+    format!(
+        "<D:activelock><D:locktype><D:write/></D:locktype><D:lockscope>{}</D:lockscope>\
+         <D:depth>{}</D:depth>{}<D:timeout>Second-{}</D:timeout>\
+         <D:locktoken><D:href>{}</D:href></D:locktoken>\
+         <D:lockroot><D:href>{}</D:href></D:lockroot></D:activelock>",
+        if lock.exclusive { "<D:exclusive/>" } else { "<D:shared/>" },
+        if lock.deep { "infinity" } else { "0" },
+        if lock.owner.is_empty() {
+            String::new()
+        } else {
+            format!("<D:owner>{}</D:owner>", xml::escape(&lock.owner))
+        },
+        lock.timeout,
+        lock.token,
+        xml::escape(&href(&lock.path, false)),
+    )
+}
+
+// 资源的活属性，值为已经转义的XML片段
+fn live_props(state: &AppState, rel_path: &str, metadata: &Metadata) -> Vec<(&'static str, String)> {
+    let name = rel_path.rsplit('/').next().unwrap_or_default();
+    let modified = metadata.modified().unwrap_or(std::time::UNIX_EPOCH);
+    let created = metadata.created().unwrap_or(modified);
+
+    let mut props = vec![
+        (
+            "resourcetype",
+            if metadata.is_dir() { "<D:collection/>".to_string() } else { String::new() },
+        ),
+        ("displayname", xml::escape(name)),
+        ("getlastmodified", format_http_date(modified)),
+        (
+            "creationdate",
+            DateTime::<Utc>::from(created).to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+        ),
+        (
+            "supportedlock",
+            "<D:lockentry><D:lockscope><D:exclusive/></D:lockscope><D:locktype><D:write/></D:locktype></D:lockentry>\
+             <D:lockentry><D:lockscope><D:shared/></D:lockscope><D:locktype><D:write/></D:locktype></D:lockentry>"
+                .to_string(),
+        ),
+        (
+            "lockdiscovery",
+            state
+                .webdav
+                .locks_covering(rel_path)
+                .iter()
+                .map(active_lock_xml)
+                .collect(),
+        ),
+    ];
+
+    if !metadata.is_dir() {
+        props.push(("getcontentlength", metadata.len().to_string()));
+        props.push((
+            "getcontenttype",
+            get_mime_type(std::path::Path::new(name))
+                .unwrap_or_else(|| "application/octet-stream".to_string()),
+        ));
+        props.push(("getetag", xml::escape(&file_etag(metadata))));
+    }
+
+    props
+}
+
+enum PropRequest {
+    AllProp,
+    PropName,
+    Named(Vec<XmlElement>),
+}
+
+fn propfind_response(
+    state: &AppState,
+    rel_path: &str,
+    metadata: &Metadata,
+    request: &PropRequest,
+) -> String {
+    let live = live_props(state, rel_path, metadata);
+    let dead = state.webdav.dead_props(rel_path);
+
+    let propstats = match request {
+        PropRequest::AllProp => {
+            let mut found: String = live.iter().map(|(name, value)| prop_element(name, value)).collect();
+            for (key, value) in &dead {
+                found.push_str(&dead_prop_element(key, Some(value)));
+            }
+            propstat(&found, StatusCode::OK)
+        }
+        PropRequest::PropName => {
+            let mut found: String = live.iter().map(|(name, _)| prop_element(name, "")).collect();
+            for key in dead.keys() {
+                found.push_str(&dead_prop_element(key, None));
+            }
+            propstat(&found, StatusCode::OK)
+        }
+        PropRequest::Named(names) => {
+            let mut found = String::new();
+            let mut missing = String::new();
+            for prop in names {
+                let live_value = if prop.ns == DAV_NS {
+                    live.iter().find(|(name, _)| *name == prop.name)
+                } else {
+                    None
+                };
+                if let Some((name, value)) = live_value {
+                    found.push_str(&prop_element(name, value));
+                } else if let Some(value) = dead.get(&prop.key()) {
+                    found.push_str(&dead_prop_element(&prop.key(), Some(value)));
+                } else {
+                    missing.push_str(&dead_prop_element(&prop.key(), None));
+                }
+            }
+            let mut propstats = String::new();
+            if !found.is_empty() {
+                propstats.push_str(&propstat(&found, StatusCode::OK));
+            }
+            if !missing.is_empty() {
+                propstats.push_str(&propstat(&missing, StatusCode::NOT_FOUND));
+            }
+            propstats
+        }
+    };
+
+    format!(
+        "<D:response><D:href>{}</D:href>{}</D:response>",
+        xml::escape(&href(rel_path, metadata.is_dir())),
+        propstats
+    )
+}
+
+// WebDAV请求入口，按方法分发
+pub async fn handle(
+    State(state): State<Arc<AppState>>,
+    user: AuthUser,
+    req: Request,
+) -> Result<Response, AppError> {
+    let rel_path = request_path(req.uri().path())?;
+    let full_path = resolve(&state, &rel_path)?;
+    let (parts, body) = req.into_parts();
+    let headers = parts.headers;
+
+    match parts.method.as_str() {
+        "OPTIONS" => Ok(options()),
+        "GET" => get(&state, &user, &rel_path, &full_path, &headers).await,
+        "HEAD" => head(&state, &user, &rel_path, &full_path).await,
+        "PUT" => put(&state, &user, &rel_path, &full_path, &headers, body).await,
+        "DELETE" => delete(&state, &user, &rel_path, &full_path, &headers).await,
+        "MKCOL" => mkcol(&state, &user, &rel_path, &full_path, &headers, body).await,
+        "COPY" => copy_or_move(&state, &user, &rel_path, &full_path, &headers, false).await,
+        "MOVE" => copy_or_move(&state, &user, &rel_path, &full_path, &headers, true).await,
+        "PROPFIND" => propfind(&state, &user, &rel_path, &full_path, &headers, body).await,
+        "PROPPATCH" => proppatch(&state, &user, &rel_path, &full_path, &headers, body).await,
+        "LOCK" => lock(&state, &user, &rel_path, &full_path, &headers, body).await,
+        "UNLOCK" => unlock(&state, &user, &rel_path, &headers).await,
+        _ => Err(AppError::MethodNotAllowed(format!("Method {} not supported", parts.method))),
+    }
+}
+
+fn options() -> Response {
+    (
+        StatusCode::OK,
+        [
+            (DAV, "1, 2"),
+            (header::ALLOW, ALLOWED_METHODS),
+            (MS_AUTHOR_VIA, "DAV"),
+            (header::CONTENT_LENGTH, "0"),
+        ],
+    )
+        .into_response()
+}
+
+async fn existing_metadata(full_path: &std::path::Path) -> Result<Metadata, AppError> {
+    fs::metadata(full_path)
+        .await
+        .map_err(|_| AppError::NotFound("Resource not found".to_string()))
+}
+
+async fn get(
+    state: &AppState,
+    user: &AuthUser,
+    rel_path: &str,
+    full_path: &std::path::Path,
+    headers: &HeaderMap,
+) -> Result<Response, AppError> {
+    state.acl.check(user, rel_path, Permission::Read)?;
+    let metadata = existing_metadata(full_path).await?;
+    if metadata.is_dir() {
+        return Err(AppError::MethodNotAllowed(
+            "Use a WebDAV client to browse collections".to_string(),
+        ));
+    }
+    send_file(full_path, headers).await
+}
+
+// HEAD只返回响应头，不读取文件内容
+async fn head(
+    state: &AppState,
+    user: &AuthUser,
+    rel_path: &str,
+    full_path: &std::path::Path,
+) -> Result<Response, AppError> {
+    state.acl.check(user, rel_path, Permission::Read)?;
+    let metadata = existing_metadata(full_path).await?;
+    if metadata.is_dir() {
+        return Ok(StatusCode::OK.into_response());
+    }
+
+    let mime_type = get_mime_type(full_path).unwrap_or_else(|| "application/octet-stream".to_string());
+    Ok((
+        StatusCode::OK,
+        [
+            (header::CONTENT_TYPE, mime_type),
+            (header::CONTENT_LENGTH, metadata.len().to_string()),
+            (header::ACCEPT_RANGES, "bytes".to_string()),
+            (header::ETAG, file_etag(&metadata)),
+            (
+                header::LAST_MODIFIED,
+                format_http_date(metadata.modified().unwrap_or(std::time::UNIX_EPOCH)),
+            ),
+        ],
+    )
+        .into_response())
+}
+
+// 流式写入请求体，覆盖时保存历史版本
+async fn put(
+    state: &AppState,
+    user: &AuthUser,
+    rel_path: &str,
+    full_path: &std::path::Path,
+    headers: &HeaderMap,
+    body: Body,
+) -> Result<Response, AppError> {
+    state.acl.check(user, rel_path, Permission::Write)?;
+    state.webdav.check_locks(rel_path, false, headers, user)?;
+
+    if rel_path.is_empty() || full_path.is_dir() {
+        return Err(AppError::MethodNotAllowed("Cannot PUT to a collection".to_string()));
+    }
+    if !parent_exists(full_path) {
+        return Err(AppError::Conflict("Parent collection does not exist".to_string()));
+    }
+
+    let existed = full_path.exists();
+    let guard = TempFileGuard::new(temp_path_for(full_path));
+    let mut file = fs::File::create(guard.path()).await?;
+    let mut stream = body.into_data_stream();
+    let mut written: u64 = 0;
+    while let Some(chunk) = stream.next().await {
+        let chunk = chunk.map_err(|e| AppError::InvalidRequest(format!("Body error: {}", e)))?;
+        file.write_all(&chunk).await?;
+        written += chunk.len() as u64;
+    }
+    file.flush().await?;
+    file.sync_all().await?;
+    drop(file);
+
+    state.versions.preserve(full_path, rel_path, &user.username).await?;
+    guard.commit(full_path).await?;
+    events::file_changed(state, ChangeKind::written(existed), rel_path, &user.username).await;
+
+    tracing::info!("{} uploaded {} via WebDAV ({} bytes)", user, full_path.display(), written);
+    Ok(if existed { StatusCode::NO_CONTENT } else { StatusCode::CREATED }.into_response())
+}
+
+async fn delete(
+    state: &AppState,
+    user: &AuthUser,
+    rel_path: &str,
+    full_path: &std::path::Path,
+    headers: &HeaderMap,
+) -> Result<Response, AppError> {
+    if rel_path.is_empty() {
+        return Err(AppError::PermissionDenied("Cannot delete root directory".to_string()));
+    }
+    state.acl.check_tree(user, rel_path, Permission::Delete)?;
+    existing_metadata(full_path).await?;
+    state.webdav.check_locks(rel_path, true, headers, user)?;
+
+    state.trash.remove(full_path, rel_path, user).await?;
+    events::file_changed(state, ChangeKind::Deleted, rel_path, &user.username).await;
+    state.webdav.release_locks(rel_path);
+    state.webdav.remove_props(rel_path).await?;
+
+    tracing::info!("{} deleted {} via WebDAV", user, full_path.display());
+    Ok(StatusCode::NO_CONTENT.into_response())
+}
+
+async fn mkcol(
+    state: &AppState,
+    user: &AuthUser,
+    rel_path: &str,
+    full_path: &std::path::Path,
+    headers: &HeaderMap,
+    body: Body,
+) -> Result<Response, AppError> {
+    let body = axum::body::to_bytes(body, MAX_XML_BODY)
+        .await
+        .map_err(|_| AppError::PayloadTooLarge("Body too large".to_string()))?;
+    if !body.is_empty() {
+        return Err(AppError::UnsupportedMediaType("MKCOL body is not supported".to_string()));
+    }
+
+    state.acl.check(user, rel_path, Permission::Write)?;
+    state.webdav.check_locks(rel_path, false, headers, user)?;
+
+    if fs::symlink_metadata(full_path).await.is_ok() {
+        return Err(AppError::MethodNotAllowed("Resource already exists".to_string()));
+    }
+    if !parent_exists(full_path) {
+        return Err(AppError::Conflict("Parent collection does not exist".to_string()));
+    }
+
+    fs::create_dir(full_path).await?;
+    events::file_changed(state, ChangeKind::Created, rel_path, &user.username).await;
+    tracing::info!("{} created directory {} via WebDAV", user, full_path.display());
+    Ok(StatusCode::CREATED.into_response())
+}
+
+async fn copy_or_move(
+    state: &AppState,
+    user: &AuthUser,
+    rel_path: &str,
+    full_path: &std::path::Path,
+    headers: &HeaderMap,
+    is_move: bool,
+) -> Result<Response, AppError> {
+    let dest_rel = destination_path(headers)?;
+    let dest_path = resolve(state, &dest_rel)?;
+    let overwrite = headers
+        .get(&OVERWRITE)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| !v.trim().eq_ignore_ascii_case("F"))
+        .unwrap_or(true);
+
+    if rel_path.is_empty() || dest_rel.is_empty() {
+        return Err(AppError::PermissionDenied("Cannot copy or move the root directory".to_string()));
+    }
+    if is_same_or_descendant(&dest_rel, rel_path) {
+        return Err(AppError::PermissionDenied(
+            "Destination is the source or inside it".to_string(),
+        ));
+    }
+
+    if is_move {
+        state.acl.check_tree(user, rel_path, Permission::Delete)?;
+    } else {
+        state.acl.check_tree(user, rel_path, Permission::Read)?;
+    }
+    state.acl.check_tree(user, &dest_rel, Permission::Write)?;
+
+    let metadata = existing_metadata(full_path).await?;
+    if is_move {
+        state.webdav.check_locks(rel_path, true, headers, user)?;
+    }
+    state.webdav.check_locks(&dest_rel, true, headers, user)?;
+
+    if !parent_exists(&dest_path) {
+        return Err(AppError::Conflict("Destination parent does not exist".to_string()));
+    }
+
+    let existed = fs::symlink_metadata(&dest_path).await.is_ok();
+    if existed {
+        if !overwrite {
+            return Err(AppError::PreconditionFailed("Destination already exists".to_string()));
+        }
+        // 被覆盖的目标移入回收站
+        state.acl.check_tree(user, &dest_rel, Permission::Delete)?;
+        state.trash.remove(&dest_path, &dest_rel, user).await?;
+        state.webdav.remove_props(&dest_rel).await?;
+    }
+
+    if is_move {
+        if metadata.is_dir() {
+            fs::rename(full_path, &dest_path).await?;
+        } else {
+            move_file(full_path, &dest_path).await?;
+        }
+        state.webdav.release_locks(rel_path);
+        state.webdav.transfer_props(rel_path, &dest_rel, false).await?;
+    } else {
+        let shallow = headers
+            .get(&DEPTH)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.trim() == "0")
+            .unwrap_or(false);
+        if metadata.is_dir() {
+            if shallow {
+                fs::create_dir(&dest_path).await?;
+            } else {
+                copy_dir_recursive(full_path.to_path_buf(), dest_path.clone()).await?;
+            }
+        } else {
+            fs::copy(full_path, &dest_path).await?;
+        }
+        state.webdav.transfer_props(rel_path, &dest_rel, true).await?;
+    }
+    if is_move {
+        events::file_renamed(state, rel_path, &dest_rel, &user.username).await;
+    } else {
+        events::file_changed(state, ChangeKind::written(existed), &dest_rel, &user.username).await;
+    }
+
+    tracing::info!(
+        "{} {} {} to {} via WebDAV",
+        user,
+        if is_move { "moved" } else { "copied" },
+        full_path.display(),
+        dest_path.display()
+    );
+    Ok(if existed { StatusCode::NO_CONTENT } else { StatusCode::CREATED }.into_response())
+}
+
+async fn propfind(
+    state: &AppState,
+    user: &AuthUser,
+    rel_path: &str,
+    full_path: &std::path::Path,
+    headers: &HeaderMap,
+    body: Body,
+) -> Result<Response, AppError> {
+    state.acl.check(user, rel_path, Permission::Read)?;
+    let metadata = existing_metadata(full_path).await?;
+
+    // 不支持Depth: infinity，避免一次请求遍历整个目录树
+    let depth_one = match headers.get(&DEPTH).and_then(|v| v.to_str().ok()).map(str::trim) {
+        Some("0") => false,
+        Some("1") | None => true,
+        _ => {
+            return Ok((
+                StatusCode::FORBIDDEN,
+                [(header::CONTENT_TYPE, "application/xml; charset=utf-8")],
+                "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n\
+                 <D:error xmlns:D=\"DAV:\"><D:propfind-finite-depth/></D:error>",
+            )
+                .into_response())
+        }
+    };
+
+    let request = match read_xml_body(body).await? {
+        None => PropRequest::AllProp,
+        Some(root) if root.is(DAV_NS, "propfind") => {
+            if root.child(DAV_NS, "propname").is_some() {
+                PropRequest::PropName
+            } else if let Some(prop) = root.child(DAV_NS, "prop") {
+                PropRequest::Named(prop_children(prop))
+            } else {
+                PropRequest::AllProp
+            }
+        }
+        Some(_) => return Err(xml::bad_xml("expected propfind element")),
+    };
+
+    let mut responses = propfind_response(state, rel_path, &metadata, &request);
+
+    if depth_one && metadata.is_dir() {
+        let mut read_dir = fs::read_dir(full_path).await?;
+        let mut children = Vec::new();
+        while let Some(entry) = read_dir.next_entry().await? {
+            // 跳过回收站目录
+            if !is_safe_path(&state.base_dir, &entry.path()) {
+                continue;
+            }
+            if let Ok(child_metadata) = fs::metadata(entry.path()).await {
+                let name = entry.file_name().to_string_lossy().to_string();
+                children.push((join_relative_path(rel_path, &name), child_metadata));
+            }
+        }
+        children.sort_by(|a, b| a.0.cmp(&b.0));
+        for (child_rel, child_metadata) in children {
+            responses.push_str(&propfind_response(state, &child_rel, &child_metadata, &request));
+        }
+    }
+
+    Ok(multistatus(&responses))
+}
+
+fn prop_children(prop: &XmlElement) -> Vec<XmlElement> {
+    prop.children
+        .iter()
+        .map(|c| XmlElement {
+            ns: c.ns.clone(),
+            name: c.name.clone(),
+            text: c.text_content(),
+            children: Vec::new(),
+        })
+        .collect()
+}
+
+// 设置或删除自定义属性；DAV:命名空间下的活属性受保护，任一属性失败时整个请求不生效
+async fn proppatch(
+    state: &AppState,
+    user: &AuthUser,
+    rel_path: &str,
+    full_path: &std::path::Path,
+    headers: &HeaderMap,
+    body: Body,
+) -> Result<Response, AppError> {
+    state.acl.check(user, rel_path, Permission::Write)?;
+    let metadata = existing_metadata(full_path).await?;
+    state.webdav.check_locks(rel_path, false, headers, user)?;
+
+    let root = read_xml_body(body)
+        .await?
+        .filter(|root| root.is(DAV_NS, "propertyupdate"))
+        .ok_or_else(|| xml::bad_xml("expected propertyupdate element"))?;
+
+    // (属性, 是否为设置操作)
+    let mut updates = Vec::new();
+    for instruction in &root.children {
+        let is_set = match instruction.name.as_str() {
+            "set" if instruction.ns == DAV_NS => true,
+            "remove" if instruction.ns == DAV_NS => false,
+            _ => continue,
+        };
+        if let Some(prop) = instruction.child(DAV_NS, "prop") {
+            for element in prop_children(prop) {
+                updates.push((element, is_set));
+            }
+        }
+    }
+
+    let protected: Vec<&XmlElement> = updates
+        .iter()
+        .map(|(element, _)| element)
+        .filter(|element| element.ns == DAV_NS)
+        .collect();
+
+    let response = if protected.is_empty() {
+        {
+            let mut props = state.webdav.props.lock().unwrap_or_else(|e| e.into_inner());
+            let entry = props.entry(rel_path.to_string()).or_default();
+            for (element, is_set) in &updates {
+                if *is_set {
+                    entry.insert(element.key(), element.text.clone());
+                } else {
+                    entry.remove(&element.key());
+                }
+            }
+            if entry.is_empty() {
+                props.remove(rel_path);
+            }
+        }
+        state.webdav.persist_props().await?;
+
+        let names: String = updates
+            .iter()
+            .map(|(element, _)| dead_prop_element(&element.key(), None))
+            .collect();
+        propstat(&names, StatusCode::OK)
+    } else {
+        let (forbidden, failed): (Vec<_>, Vec<_>) =
+            updates.iter().partition(|(element, _)| element.ns == DAV_NS);
+        let forbidden: String = forbidden
+            .iter()
+            .map(|(element, _)| prop_element(&element.name, ""))
+            .collect();
+        let failed: String = failed
+            .iter()
+            .map(|(element, _)| dead_prop_element(&element.key(), None))
+            .collect();
+        let mut propstats = propstat(&forbidden, StatusCode::FORBIDDEN);
+        if !failed.is_empty() {
+            propstats.push_str(&propstat(&failed, StatusCode::FAILED_DEPENDENCY));
+        }
+        propstats
+    };
+
+    if protected.is_empty() {
+        tracing::info!("{} updated properties of {} via WebDAV", user, full_path.display());
+    }
+    Ok(multistatus(&format!(
+        "<D:response><D:href>{}</D:href>{}</D:response>",
+        xml::escape(&href(rel_path, metadata.is_dir())),
+        response
+    )))
+}
+
+// 解析Timeout头，如 "Second-3600" 或 "Infinite"
+fn lock_timeout(headers: &HeaderMap) -> u64 {
+    let value = match headers.get(&TIMEOUT).and_then(|v| v.to_str().ok()) {
+        Some(value) => value,
+        None => return DEFAULT_LOCK_SECS,
+    };
+    let first = value.split(',').next().unwrap_or_default().trim();
+    if first.eq_ignore_ascii_case("Infinite") {
+        return MAX_LOCK_SECS;
+    }
+    first
+        .strip_prefix("Second-")
+        .and_then(|secs| secs.parse::<u64>().ok())
+        .map(|secs| secs.clamp(1, MAX_LOCK_SECS))
+        .unwrap_or(DEFAULT_LOCK_SECS)
+}
+
+fn lock_response(lock: &DavLock, status: StatusCode) -> Response {
+    (
+        status,
+        [
+            (header::CONTENT_TYPE, "application/xml; charset=utf-8".to_string()),
+            (LOCK_TOKEN, format!("<{}>", lock.token)),
+        ],
+        format!(
+            "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<D:prop xmlns:D=\"DAV:\"><D:lockdiscovery>{}</D:lockdiscovery></D:prop>",
+            active_lock_xml(lock)
+        ),
+    )
+        .into_response()
+}
+
+async fn lock(
+    state: &AppState,
+    user: &AuthUser,
+    rel_path: &str,
+    full_path: &std::path::Path,
+    headers: &HeaderMap,
+    body: Body,
+) -> Result<Response, AppError> {
+    state.acl.check(user, rel_path, Permission::Write)?;
+    let timeout = lock_timeout(headers);
+
+    let lockinfo = match read_xml_body(body).await? {
+        // 没有请求体时刷新If头中提交的锁
+        None => {
+            let submitted = if_header_tokens(headers);
+            let mut locks = state.webdav.active_locks();
+            let lock = locks
+                .iter_mut()
+                .find(|l| {
+                    submitted.contains(&l.token)
+                        && l.user == user.username
+                        && (l.path == rel_path || (l.deep && is_ancestor(&l.path, rel_path)))
+                })
+                .ok_or_else(|| AppError::PreconditionFailed("No matching lock to refresh".to_string()))?;
+            lock.timeout = timeout;
+            lock.expires = Utc::now().timestamp() + timeout as i64;
+            return Ok(lock_response(lock, StatusCode::OK));
+        }
+        Some(root) if root.is(DAV_NS, "lockinfo") => root,
+        Some(_) => return Err(xml::bad_xml("expected lockinfo element")),
+    };
+
+    let exclusive = lockinfo
+        .child(DAV_NS, "lockscope")
+        .map(|scope| scope.child(DAV_NS, "shared").is_none())
+        .unwrap_or(true);
+    let owner = lockinfo
+        .child(DAV_NS, "owner")
+        .map(|owner| owner.text_content().trim().to_string())
+        .unwrap_or_default();
+    let deep = match headers.get(&DEPTH).and_then(|v| v.to_str().ok()).map(str::trim) {
+        Some("0") => false,
+        Some("infinity") | None => true,
+        _ => return Err(AppError::InvalidRequest("Depth must be 0 or infinity".to_string())),
+    };
+
+    let (lock, created) = {
+        let mut locks = state.webdav.active_locks();
+        let conflict = locks.iter().any(|l| {
+            let overlaps = l.path == rel_path
+                || (l.deep && is_ancestor(&l.path, rel_path))
+                || (deep && is_ancestor(rel_path, &l.path));
+            overlaps && (l.exclusive || exclusive)
+        });
+        if conflict {
+            return Err(AppError::Locked(format!("Resource is locked: /{}", rel_path)));
+        }
+
+        // 锁定不存在的资源时创建空文件；在锁表的保护下进行，冲突时不会留下空文件
+        let created = if std::fs::symlink_metadata(full_path).is_err() {
+            if !parent_exists(full_path) {
+                return Err(AppError::Conflict("Parent collection does not exist".to_string()));
+            }
+            std::fs::File::create(full_path)?;
+            true
+        } else {
+            false
+        };
+
+        let lock = DavLock {
+            token: format!("{}{}", LOCK_TOKEN_PREFIX, uuid::Uuid::new_v4()),
+            path: rel_path.to_string(),
+            deep,
+            exclusive,
+            owner,
+            user: user.username.clone(),
+            timeout,
+            expires: Utc::now().timestamp() + timeout as i64,
+        };
+        locks.push(lock.clone());
+        (lock, created)
+    };
+
+    if created {
+        events::file_changed(state, ChangeKind::Created, rel_path, &user.username).await;
+    }
+
+    tracing::info!("{} locked {} via WebDAV", user, full_path.display());
+    Ok(lock_response(
+        &lock,
+        if created { StatusCode::CREATED } else { StatusCode::OK },
+    ))
+}
+
+async fn unlock(
+    state: &AppState,
+    user: &AuthUser,
+    rel_path: &str,
+    headers: &HeaderMap,
+) -> Result<Response, AppError> {
+    let token = headers
+        .get(&LOCK_TOKEN)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| v.trim().trim_start_matches('<').trim_end_matches('>').to_string())
+        .ok_or_else(|| AppError::InvalidRequest("Lock-Token header is required".to_string()))?;
+
+    let mut locks = state.webdav.active_locks();
+    let index = locks
+        .iter()
+        .position(|l| {
+            l.token == token && (l.path == rel_path || (l.deep && is_ancestor(&l.path, rel_path)))
+        })
+        .ok_or_else(|| AppError::Conflict("Lock token does not match this resource".to_string()))?;
+
+    if locks[index].user != user.username && !user.is_admin {
+        return Err(AppError::PermissionDenied("Lock is held by another user".to_string()));
+    }
+    locks.remove(index);
+    drop(locks);
+
+    tracing::info!("{} unlocked /{} via WebDAV", user, rel_path);
+    Ok(StatusCode::NO_CONTENT.into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::test_state;
+
+    const LOCKINFO: &str = r#"<?xml version="1.0"?><D:lockinfo xmlns:D="DAV:"><D:lockscope><D:exclusive/></D:lockscope><D:locktype><D:write/></D:locktype></D:lockinfo>"#;
+
+    fn user(name: &str) -> AuthUser {
+        AuthUser {
+            username: name.to_string(),
+            groups: Vec::new(),
+            is_admin: false,
+            token: None,
+        }
+    }
+
+    async fn dav(
+        state: &Arc<AppState>,
+        user: AuthUser,
+        method: &str,
+        path: &str,
+        headers: &[(&str, &str)],
+        body: &str,
+    ) -> Response {
+        let mut builder = Request::builder().method(method).uri(format!("{}{}", DAV_PREFIX, path));
+        for (name, value) in headers {
+            builder = builder.header(*name, *value);
+        }
+        let req = builder.body(Body::from(body.to_string())).unwrap();
+        handle(State(state.clone()), user, req)
+            .await
+            .unwrap_or_else(|e| e.into_response())
+    }
+
+    async fn lock_as(state: &Arc<AppState>, user: AuthUser, path: &str, depth: &str) -> String {
+        let response = dav(state, user, "LOCK", path, &[("depth", depth)], LOCKINFO).await;
+        assert!(response.status().is_success(), "LOCK {} failed: {}", path, response.status());
+        response.headers()[LOCK_TOKEN]
+            .to_str()
+            .unwrap()
+            .trim_matches(|c| c == '<' || c == '>')
+            .to_string()
+    }
+
+    async fn writable_state() -> (Arc<AppState>, crate::test_support::TempDir) {
+        test_state(serde_json::json!({ "acl": { "default": ["read", "write", "delete"] } })).await
+    }
+
+    #[tokio::test]
+    async fn lock_blocks_other_writers_until_unlock() {
+        let (state, _dir) = writable_state().await;
+        let token = lock_as(&state, user("alice"), "/b", "0").await;
+        let if_header = format!("(<{}>)", token);
+
+        // 锁定不存在的资源会创建空文件
+        assert!(state.base_dir.join("b").is_file());
+
+        // 没有提交令牌，或令牌不属于自己时都不能修改
+        let response = dav(&state, user("alice"), "PUT", "/b", &[], "x").await;
+        assert_eq!(response.status(), StatusCode::LOCKED);
+        let response = dav(&state, user("bob"), "PUT", "/b", &[("if", &if_header)], "x").await;
+        assert_eq!(response.status(), StatusCode::LOCKED);
+        let response = dav(&state, user("alice"), "PUT", "/b", &[("if", &if_header)], "x").await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+        // 只有持有者可以解锁
+        let lock_token = format!("<{}>", token);
+        let response = dav(&state, user("bob"), "UNLOCK", "/b", &[("lock-token", &lock_token)], "").await;
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+        let response = dav(&state, user("alice"), "UNLOCK", "/b", &[("lock-token", &lock_token)], "").await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+
+        let response = dav(&state, user("bob"), "PUT", "/b", &[], "y").await;
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+    }
+
+    #[tokio::test]
+    async fn lock_applies_to_equivalent_paths() {
+        let (state, _dir) = writable_state().await;
+        std::fs::create_dir(state.base_dir.join("a")).unwrap();
+        std::fs::write(state.base_dir.join("c"), "c").unwrap();
+        lock_as(&state, user("alice"), "/b", "0").await;
+
+        for path in ["/./b", "/a/../b", "//b/"] {
+            let response = dav(&state, user("bob"), "PUT", path, &[], "x").await;
+            assert_eq!(response.status(), StatusCode::LOCKED, "PUT {}", path);
+        }
+
+        // Destination同样先规范化再检查锁
+        let response = dav(&state, user("bob"), "MOVE", "/c", &[("destination", "/dav/./b"), ("overwrite", "T")], "").await;
+        assert_eq!(response.status(), StatusCode::LOCKED);
+        assert!(state.base_dir.join("c").exists());
+    }
+
+    #[tokio::test]
+    async fn depth_infinity_lock_covers_subtree() {
+        let (state, _dir) = writable_state().await;
+        std::fs::create_dir_all(state.base_dir.join("dir/sub")).unwrap();
+        std::fs::create_dir(state.base_dir.join("flat")).unwrap();
+
+        let token = lock_as(&state, user("alice"), "/dir", "infinity").await;
+        let response = dav(&state, user("bob"), "PUT", "/dir/sub/x", &[], "x").await;
+        assert_eq!(response.status(), StatusCode::LOCKED);
+        let response = dav(&state, user("bob"), "LOCK", "/dir/sub", &[("depth", "0")], LOCKINFO).await;
+        assert_eq!(response.status(), StatusCode::LOCKED);
+
+        let if_header = format!("(<{}>)", token);
+        let response = dav(&state, user("alice"), "PUT", "/dir/sub/x", &[("if", &if_header)], "x").await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+
+        // 深度为0的锁只作用于目录本身
+        lock_as(&state, user("alice"), "/flat", "0").await;
+        let response = dav(&state, user("bob"), "PUT", "/flat/x", &[], "x").await;
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let response = dav(&state, user("bob"), "DELETE", "/flat", &[], "").await;
+        assert_eq!(response.status(), StatusCode::LOCKED);
+    }
+}
@@ -0,0 +1,116 @@
+use quick_xml::{events::Event, name::ResolveResult, NsReader};
+
+use crate::errors::AppError;
+
+// 转义XML文本和属性值
+pub fn escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+// 请求体解析后的简单XML元素树（WebDAV和S3接口使用）
+#[derive(Debug, Default)]
+pub struct XmlElement {
+    pub ns: String,
+    pub name: String,
+    pub text: String,
+    pub children: Vec<XmlElement>,
+}
+
+impl XmlElement {
+    pub fn is(&self, ns: &str, name: &str) -> bool {
+        self.ns == ns && self.name == name
+    }
+
+    pub fn child(&self, ns: &str, name: &str) -> Option<&XmlElement> {
+        self.children.iter().find(|c| c.is(ns, name))
+    }
+
+    // 按本地名查找子元素，忽略命名空间
+    pub fn children_named<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a XmlElement> {
+        self.children.iter().filter(move |c| c.name == name)
+    }
+
+    pub fn child_text(&self, name: &str) -> Option<String> {
+        self.children_named(name).next().map(XmlElement::text_content)
+    }
+
+    // 元素及其所有子元素的文本内容
+    pub fn text_content(&self) -> String {
+        let mut text = self.text.clone();
+        for child in &self.children {
+            text.push_str(&child.text_content());
+        }
+        text
+    }
+
+    pub fn key(&self) -> String {
+        format!("{{{}}}{}", self.ns, self.name)
+    }
+}
+
+pub fn bad_xml(e: impl std::fmt::Display) -> AppError {
+    AppError::InvalidRequest(format!("Invalid XML body: {}", e))
+}
+
+// 解析XML请求体，空请求体返回None
+pub fn parse(body: &[u8]) -> Result<Option<XmlElement>, AppError> {
+    if body.iter().all(|b| b.is_ascii_whitespace()) {
+        return Ok(None);
+    }
+
+    let mut reader = NsReader::from_reader(body);
+    reader.config_mut().trim_text(true);
+    let mut buf = Vec::new();
+    let mut stack: Vec<XmlElement> = Vec::new();
+    let mut root = None;
+
+    loop {
+        let (ns, event) = reader.read_resolved_event_into(&mut buf).map_err(bad_xml)?;
+        let ns = match ns {
+            ResolveResult::Bound(ns) => String::from_utf8_lossy(ns.as_ref()).to_string(),
+            _ => String::new(),
+        };
+        let element = |local: &[u8]| XmlElement {
+            ns: ns.clone(),
+            name: String::from_utf8_lossy(local).to_string(),
+            ..Default::default()
+        };
+
+        let finished = match event {
+            Event::Start(e) => {
+                stack.push(element(e.local_name().as_ref()));
+                None
+            }
+            Event::Empty(e) => Some(element(e.local_name().as_ref())),
+            Event::End(_) => stack.pop(),
+            Event::Text(t) => {
+                if let Some(top) = stack.last_mut() {
+                    top.text.push_str(&t.unescape().map_err(bad_xml)?);
+                }
+                None
+            }
+            Event::CData(t) => {
+                if let Some(top) = stack.last_mut() {
+                    top.text.push_str(&String::from_utf8_lossy(&t));
+                }
+                None
+            }
+            Event::Eof => break,
+            _ => None,
+        };
+
+        if let Some(finished) = finished {
+            match stack.last_mut() {
+                Some(parent) => parent.children.push(finished),
+                None => root = Some(finished),
+            }
+        }
+        buf.clear();
+    }
+
+    root.map(Some).ok_or_else(|| bad_xml("missing root element"))
+}
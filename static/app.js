@@ -1,3 +1,5 @@
+const PAGE_SIZE = 500;
+
 class FileManager {
     constructor() {
         this.currentPath = '';
@@ -17,6 +19,7 @@ class FileManager {
 
     setupEventListeners() {
         document.getElementById('refreshBtn').addEventListener('click', () => this.loadFiles());
+        document.getElementById('logoutBtn').addEventListener('click', () => this.logout());
         document.getElementById('newFolderBtn').addEventListener('click', () => this.showNewFolderModal());
         document.getElementById('uploadBtn').addEventListener('click', () => document.getElementById('fileInput').click());
         document.getElementById('fileInput').addEventListener('change', (e) => this.handleFileUpload(e));
@@ -48,30 +51,113 @@ class FileManager {
         });
     }
 
+    // 带认证的请求：收到401时提示登录并重试一次
+    async apiFetch(url, options = {}) {
+        const response = await fetch(url, options);
+        if (response.status !== 401) return response;
+
+        if (!(await this.login())) return response;
+        return fetch(url, options);
+    }
+
+    async login() {
+        const username = prompt('用户名:');
+        if (!username) return false;
+        const password = prompt('密码:');
+        if (password === null) return false;
+
+        const response = await fetch('/api/auth/login', {
+            method: 'POST',
+            headers: { 'Content-Type': 'application/json' },
+            body: JSON.stringify({ username, password })
+        });
+        const data = await response.json();
+        if (data.code !== 0) {
+            this.showNotification('登录失败: ' + data.message, 'error');
+            return false;
+        }
+        return true;
+    }
+
+    async logout() {
+        await fetch('/api/auth/logout', { method: 'POST' });
+        this.closeChanges();
+        this.loadFiles();
+    }
+
     async loadFiles() {
         try {
-            const url = this.currentPath ? `/api/files/${this.currentPath}` : '/api/files';
-            const response = await fetch(url);
-            
-            if (!response.ok) throw new Error('Failed to load files');
-            
-            const data = await response.json();
-            if (data.code !== 0) throw new Error(data.message || 'Failed to load files');
-            
-            this.renderFileList(data.data.items);
+            const data = await this.fetchFilePage();
+            this.renderFileList(data);
             this.updateBreadcrumb();
             this.selectedFiles.clear();
             this.updateToolbar();
+            this.subscribeChanges();
+        } catch (error) {
+            this.showNotification('加载文件失败: ' + error.message, 'error');
+        }
+    }
+
+    // 大目录分页加载，每页 PAGE_SIZE 项
+    async fetchFilePage(cursor) {
+        const query = new URLSearchParams({ limit: PAGE_SIZE });
+        if (cursor) query.set('cursor', cursor);
+        const url = this.currentPath ? `/api/files/${this.currentPath}` : '/api/files';
+        const response = await this.apiFetch(`${url}?${query}`);
+
+        if (!response.ok) throw new Error('Failed to load files');
+
+        const data = await response.json();
+        if (data.code !== 0) throw new Error(data.message || 'Failed to load files');
+        return data.data;
+    }
+
+    async loadMoreFiles() {
+        try {
+            const data = await this.fetchFilePage(this.nextCursor);
+            this.renderFileList(data, true);
         } catch (error) {
             this.showNotification('加载文件失败: ' + error.message, 'error');
         }
     }
 
-    renderFileList(files) {
+    // 订阅当前目录的变化，其他客户端或外部程序修改后自动刷新列表
+    subscribeChanges() {
+        if (!window.EventSource || (this.changes && this.changesPath === this.currentPath)) return;
+        this.closeChanges();
+
+        const query = new URLSearchParams({ path: this.currentPath });
+        this.changes = new EventSource(`/api/events?${query}`);
+        this.changesPath = this.currentPath;
+
+        // 连续的变化合并为一次刷新
+        const reload = () => {
+            clearTimeout(this.reloadTimer);
+            this.reloadTimer = setTimeout(() => this.loadFiles(), 300);
+        };
+        ['created', 'modified', 'deleted', 'renamed', 'resync'].forEach(type => {
+            this.changes.addEventListener(type, reload);
+        });
+    }
+
+    closeChanges() {
+        if (this.changes) {
+            this.changes.close();
+            this.changes = null;
+        }
+    }
+
+    renderFileList(page, append = false) {
         const tbody = document.getElementById('fileListBody');
-        tbody.innerHTML = '';
+        const files = page.items;
+        if (append) {
+            tbody.querySelector('.load-more')?.remove();
+        } else {
+            tbody.innerHTML = '';
+        }
+        this.nextCursor = page.next_cursor;
 
-        if (files.length === 0) {
+        if (files.length === 0 && !append) {
             tbody.innerHTML = '<tr class="loading"><td colspan="5">文件夹为空</td></tr>';
             return;
         }
@@ -81,7 +167,11 @@ class FileManager {
             row.dataset.path = file.path;
             row.dataset.isDir = file.is_dir;
 
-            const icon = file.is_dir ? '📁' : this.getFileIcon(file.name);
+            let icon = file.is_dir ? '📁' : this.getFileIcon(file.name);
+            // 图片显示缩略图，加载失败时退回图标
+            if (!file.is_dir && this.hasThumbnail(file.name)) {
+                icon = `<img class="file-item-thumb" src="/api/thumbnail/${file.path}?size=128" loading="lazy" alt="" onerror="this.replaceWith('${icon}')">`;
+            }
             const size = file.is_dir ? '-' : this.formatSize(file.size);
 
             row.innerHTML = `
@@ -93,6 +183,7 @@ class FileManager {
                     <div class="file-actions">
                         ${file.is_dir ? `<button class="file-action-btn" onclick="fileManager.enterFolder('${file.path}')">进入</button>` : `<button class="file-action-btn" onclick="fileManager.previewFile('${file.path}', '${file.name}')">预览</button>`}
                         <button class="file-action-btn" onclick="fileManager.downloadFile('${file.path}', '${file.name}')">下载</button>
+                        <button class="file-action-btn" onclick="fileManager.renameFile('${file.path}', '${file.name}')">重命名</button>
                     </div>
                 </td>
             `;
@@ -109,6 +200,14 @@ class FileManager {
 
             tbody.appendChild(row);
         });
+
+        if (page.has_more) {
+            const row = document.createElement('tr');
+            row.className = 'loading load-more';
+            row.innerHTML = `<td colspan="5"><button class="file-action-btn">加载更多（已显示 ${page.offset + files.length} / ${page.total}）</button></td>`;
+            row.querySelector('button').addEventListener('click', () => this.loadMoreFiles());
+            tbody.appendChild(row);
+        }
     }
 
     toggleFileSelection(path) {
@@ -194,7 +293,7 @@ class FileManager {
         }
 
         try {
-            const response = await fetch('/api/mkdir', {
+            const response = await this.apiFetch('/api/mkdir', {
                 method: 'POST',
                 headers: { 'Content-Type': 'application/json' },
                 body: JSON.stringify({ path: this.currentPath, name })
@@ -222,7 +321,7 @@ class FileManager {
             files.forEach(file => formData.append('files', file));
 
             const url = this.currentPath ? `/api/upload/${this.currentPath}` : '/api/upload';
-            const response = await fetch(url, {
+            const response = await this.apiFetch(url, {
                 method: 'POST',
                 body: formData
             });
@@ -261,7 +360,7 @@ class FileManager {
             }
             // Text preview
             else if (['txt', 'md', 'json', 'xml', 'html', 'css', 'js', 'py', 'java', 'cpp', 'c', 'h', 'rs', 'go', 'rb', 'php', 'sh', 'bat', 'ps1', 'ini', 'pem', 'crt', 'key', 'conf', 'config', 'log'].includes(ext)) {
-                const response = await fetch(`/api/preview/${path}`);
+                const response = await this.apiFetch(`/api/preview/${path}`);
                 const text = await response.text();
                 previewBody.innerHTML = `<pre style="max-height:600px;overflow:auto;background:#f5f5f5;padding:15px;border-radius:4px;font-size:12px;"><code>${this.escapeHtml(text)}</code></pre>`;
             }
@@ -287,6 +386,34 @@ class FileManager {
         link.click();
     }
 
+    async renameFile(path, name) {
+        const newName = prompt('新名称:', name);
+        if (!newName || newName === name) return;
+
+        try {
+            let data = await this.requestRename(path, newName, 'fail');
+            if (data.code === 409) {
+                if (!confirm(`${newName} 已存在，是否覆盖？`)) return;
+                data = await this.requestRename(path, newName, 'overwrite');
+            }
+            if (data.code !== 0) throw new Error(data.message);
+
+            this.showNotification('重命名成功', 'success');
+            this.loadFiles();
+        } catch (error) {
+            this.showNotification('重命名失败: ' + error.message, 'error');
+        }
+    }
+
+    async requestRename(path, newName, onConflict) {
+        const response = await this.apiFetch('/api/rename', {
+            method: 'POST',
+            headers: { 'Content-Type': 'application/json' },
+            body: JSON.stringify({ path, new_name: newName, on_conflict: onConflict })
+        });
+        return response.json();
+    }
+
     async deleteSelected() {
         if (this.selectedFiles.size === 0) return;
 
@@ -295,7 +422,7 @@ class FileManager {
         this.showLoadingOverlay();
 
         try {
-            const response = await fetch('/api/batch-delete', {
+            const response = await this.apiFetch('/api/batch-delete', {
                 method: 'POST',
                 headers: { 'Content-Type': 'application/json' },
                 body: JSON.stringify({ paths: Array.from(this.selectedFiles) })
@@ -326,7 +453,7 @@ class FileManager {
         document.getElementById('confirmTarget').dataset.action = action;
 
         try {
-            const response = await fetch('/api/files');
+            const response = await this.apiFetch('/api/files');
             const data = await response.json();
             if (data.code !== 0) throw new Error(data.message);
 
@@ -377,20 +504,26 @@ class FileManager {
 
         try {
             const endpoint = action === 'copy' ? '/api/batch-copy' : '/api/batch-move';
-            const response = await fetch(endpoint, {
-                method: 'POST',
-                headers: { 'Content-Type': 'application/json' },
-                body: JSON.stringify({
-                    paths: Array.from(this.selectedFiles),
-                    destination
-                })
-            });
-
-            const data = await response.json();
-            if (data.code !== 0) throw new Error(data.message);
+            const label = action === 'copy' ? '复制' : '移动';
+            let data = await this.requestTransfer(endpoint, Array.from(this.selectedFiles), destination, 'fail');
+            let done = data.items.filter(item => item.outcome !== 'failed' && item.outcome !== 'skipped').length;
+
+            // 目标位置已有同名条目时询问是否覆盖，文件夹合并内容
+            const conflicts = data.items
+                .filter(item => item.outcome === 'failed' && item.policy === 'fail')
+                .map(item => item.path);
+            if (conflicts.length > 0 && confirm(`${conflicts.length} 项在目标位置已存在，是否覆盖？（文件夹将合并内容）`)) {
+                const retry = await this.requestTransfer(endpoint, conflicts, destination, 'merge');
+                done += retry.items.filter(item => item.outcome !== 'failed').length;
+                data = { items: data.items.filter(item => !conflicts.includes(item.path)).concat(retry.items) };
+            }
 
-            const successKey = action === 'copy' ? 'copied' : 'moved';
-            this.showNotification(`成功${action === 'copy' ? '复制' : '移动'} ${data.data[successKey].length} 项`, 'success');
+            const failed = data.items.filter(item => item.outcome === 'failed').length;
+            if (failed > 0) {
+                this.showNotification(`成功${label} ${done} 项，${failed} 项失败`, 'warning');
+            } else {
+                this.showNotification(`成功${label} ${done} 项`, 'success');
+            }
             document.getElementById('selectTargetModal').classList.remove('active');
             this.loadFiles();
         } catch (error) {
@@ -400,6 +533,18 @@ class FileManager {
         }
     }
 
+    async requestTransfer(endpoint, paths, destination, onConflict) {
+        const response = await this.apiFetch(endpoint, {
+            method: 'POST',
+            headers: { 'Content-Type': 'application/json' },
+            body: JSON.stringify({ paths, destination, on_conflict: onConflict })
+        });
+
+        const data = await response.json();
+        if (data.code !== 0) throw new Error(data.message);
+        return data.data;
+    }
+
     filterFiles(query) {
         const rows = document.querySelectorAll('#fileListBody tr');
         rows.forEach(row => {
@@ -424,6 +569,11 @@ class FileManager {
         setTimeout(() => notification.classList.remove('active'), 3000);
     }
 
+    hasThumbnail(name) {
+        const ext = name.split('.').pop().toLowerCase();
+        return ['jpg', 'jpeg', 'png', 'gif', 'webp', 'bmp'].includes(ext);
+    }
+
     getFileIcon(name) {
         const ext = name.split('.').pop().toLowerCase();
         const icons = {